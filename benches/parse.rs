@@ -0,0 +1,53 @@
+//! Time reading a large JSON input, to compare builds with and without the `simd` feature:
+//!
+//! ```text
+//! cargo bench --bench parse
+//! cargo bench --bench parse --features simd
+//! ```
+
+use std::{env, fmt::Write as _, fs, process::Command};
+
+const RECORDS: usize = 500_000;
+const RUNS: usize = 5;
+
+fn main() {
+    let mut input = String::from("[");
+    for i in 0..RECORDS {
+        if i > 0 {
+            input.push(',');
+        }
+        write!(
+            input,
+            concat!(
+                r#"{{"id":{i},"name":"user {i}","active":{},"score":{}.{},"#,
+                r#""tags":["a","b","c"],"parent":null}}"#,
+            ),
+            i % 2 == 0,
+            i / 8,
+            i % 8 * 125,
+            i = i,
+        )
+        .unwrap();
+    }
+    input.push(']');
+    let path = env::temp_dir().join("jsq-bench-parse.json");
+    fs::write(&path, &input).unwrap();
+
+    println!("reading {} MiB of JSON", input.len() / 1024 / 1024);
+    for _ in 0..RUNS {
+        let output = Command::new(env!("CARGO_BIN_EXE_jsq"))
+            .args(["--profile", "-j", "undefined"])
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let profile = String::from_utf8(output.stderr).unwrap();
+        let line = profile
+            .lines()
+            .find(|line| line.starts_with("reading input"))
+            .unwrap();
+        println!("{line}");
+    }
+
+    fs::remove_file(&path).unwrap();
+}
@@ -1,20 +1,51 @@
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::rc::Rc;
+#[cfg(feature = "typescript")]
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context as _, Error, Result};
+use anyhow::{anyhow, Context as _, Error, Result};
+#[cfg(feature = "typescript")]
+use boa_engine::module::{resolve_module_specifier, Module, ModuleLoader, Referrer};
 use boa_engine::{
-    object::ObjectInitializer, property::Attribute, Context, JsArgs, JsError, JsResult, JsString,
-    JsValue, NativeFunction, Source,
+    builtins::promise::PromiseState,
+    module::SimpleModuleLoader,
+    object::{
+        builtins::{JsArray, JsDate, JsPromise},
+        ObjectInitializer,
+    },
+    property::{Attribute, PropertyDescriptor, PropertyKey},
+    Context, JsArgs, JsError, JsNativeError, JsObject, JsResult, JsString, JsValue, NativeFunction,
+    Script, Source,
 };
 
-use crate::{parse, print};
+#[cfg(feature = "typescript")]
+use crate::typescript;
+use crate::{
+    diagnostic, edit, hint, http, limit, merge, parse,
+    precision::{self, Numbers},
+    print,
+    profile::{self, Stage},
+    safe::{self, Capability},
+    undefined::{self, Undefined},
+};
 
-pub struct Options<'a, I> {
-    pub input: &'a str,
+pub struct Options<I> {
     pub env: I,
-    pub script: &'a str,
+    /// Variables given with --arg or --argjson, each as a name and a JSON value.
+    pub vars: Vec<(String, String)>,
+    /// Tables given with --lookup, each as a name and a JSON object of records by key.
+    pub lookups: Vec<(String, String)>,
+    /// The path and contents of a script to evaluate before SCRIPT, whose declarations are then
+    /// available to it.
+    pub init: Option<(String, String)>,
     pub parse: bool,
     pub stringify: bool,
+    /// What to do with undefined values inside results, when stringifying them.
+    pub undefined: Undefined,
 }
 
 trait ToAnyhow<T> {
@@ -37,6 +68,16 @@ impl<T, E: Into<Error>> ToJs<T> for std::result::Result<T, E> {
     }
 }
 
+/// The function which `source` evaluates to.
+fn eval_fn(source: &str, context: &mut Context) -> Result<JsObject> {
+    Ok(context
+        .eval(Source::from_bytes(source))
+        .to_anyhow(context)?
+        .as_callable()
+        .context("as callable")?
+        .clone())
+}
+
 fn call_fn(name: &str, args: &[JsValue], context: &mut Context) -> Result<JsValue> {
     context
         .eval(Source::from_bytes(name))
@@ -47,6 +88,63 @@ fn call_fn(name: &str, args: &[JsValue], context: &mut Context) -> Result<JsValu
         .to_anyhow(context)
 }
 
+/// `JSON.parse` `json`, using `reviver` to turn any integers too large to be doubles in to
+/// `BigInt`s.
+fn parse_json(json: &str, reviver: &JsObject, context: &mut Context) -> Result<JsValue> {
+    match precision::mark_bigints(json) {
+        Some(marked) => call_fn(
+            "JSON.parse",
+            &[
+                JsValue::from(JsString::from(marked)),
+                JsValue::from(reviver.clone()),
+            ],
+            context,
+        ),
+        None => call_fn(
+            "JSON.parse",
+            &[JsValue::from(JsString::from(json))],
+            context,
+        ),
+    }
+}
+
+/// `JSON.stringify` `value`, falling back on `replacer` to mark any `BigInt`s, which can't
+/// otherwise be serialized. If `always`, `replacer` is used from the start, so that it sees any
+/// undefined values too.
+fn stringify(
+    value: &JsValue,
+    replacer: &JsObject,
+    always: bool,
+    context: &mut Context,
+) -> Result<JsValue> {
+    if !always && let Ok(res) = call_fn("JSON.stringify", std::slice::from_ref(value), context) {
+        return Ok(res);
+    }
+    call_fn(
+        "JSON.stringify",
+        &[value.clone(), JsValue::from(replacer.clone())],
+        context,
+    )
+}
+
+/// If `value` is a promise, run jobs until it settles, and return what it resolves to.
+fn settle(value: JsValue, context: &mut Context) -> JsResult<JsValue> {
+    let Some(promise) = value
+        .as_object()
+        .and_then(|obj| JsPromise::from_object(obj.clone()).ok())
+    else {
+        return Ok(value);
+    };
+    context.run_jobs();
+    match promise.state() {
+        PromiseState::Fulfilled(value) => Ok(value),
+        PromiseState::Rejected(err) => Err(JsError::from_opaque(err)),
+        PromiseState::Pending => Err(JsNativeError::typ()
+            .with_message("promise never settled")
+            .into()),
+    }
+}
+
 fn get_std_string(args: &[JsValue], index: usize, context: &mut Context) -> JsResult<String> {
     args.get_or_undefined(index)
         .to_string(context)?
@@ -60,8 +158,10 @@ fn register_read(context: &mut Context) -> Result<()> {
             JsString::from("read"),
             1,
             NativeFunction::from_fn_ptr(|_, args, context| {
+                let path = get_std_string(args, 0, context)?;
+                safe::check_read(&path).to_js()?;
                 Ok(JsValue::from(JsString::from(
-                    std::fs::read_to_string(get_std_string(args, 0, context)?).to_js()?,
+                    std::fs::read_to_string(path).to_js()?,
                 )))
             }),
         )
@@ -74,37 +174,531 @@ fn register_write(context: &mut Context) -> Result<()> {
             JsString::from("write"),
             2,
             NativeFunction::from_fn_ptr(|_, args, context| {
-                let mut file = File::create(get_std_string(args, 0, context)?).to_js()?;
+                let path = get_std_string(args, 0, context)?;
                 let value = get_std_string(args, 1, context)?;
-                if value.ends_with('\n') {
-                    write!(file, "{value}").to_js()?;
-                } else {
-                    writeln!(file, "{value}").to_js()?;
-                }
+                edit::write(&path, &value).to_js()?;
                 Ok(JsValue::Undefined)
             }),
         )
         .to_anyhow(context)
 }
 
+/// Register `require`, which loads the ES module at a path relative to the current directory and
+/// returns its exports. Unlike `import()`, it waits for the module to be evaluated, so that it can
+/// be used in SCRIPT directly.
+fn register_require(context: &mut Context) -> Result<()> {
+    context
+        .register_global_builtin_callable(
+            JsString::from("require"),
+            1,
+            NativeFunction::from_fn_ptr(|_, args, context| {
+                // A script has no path for a relative import to be resolved against, so it's made
+                // absolute, relative to the current directory.
+                let path = std::path::absolute(get_std_string(args, 0, context)?).to_js()?;
+                safe::check_read(&path).to_js()?;
+                let path = path.to_string_lossy();
+                let import = format!("import({})", serde_json::to_string(&path).to_js()?);
+                let exports = context.eval(Source::from_bytes(&import))?;
+                settle(exports, context)
+            }),
+        )
+        .to_anyhow(context)
+}
+
+/// Loads modules as [`SimpleModuleLoader`] does, but transpiles those written in TypeScript first,
+/// so that `require` and `import` work with them too.
+#[cfg(feature = "typescript")]
+struct TypeScriptLoader {
+    root: PathBuf,
+    inner: SimpleModuleLoader,
+}
+
+#[cfg(feature = "typescript")]
+impl TypeScriptLoader {
+    fn new() -> Result<Self> {
+        let root = Path::new(".").canonicalize()?;
+        let inner = SimpleModuleLoader::new(&root).map_err(|err| anyhow!("{err}"))?;
+        Ok(Self { root, inner })
+    }
+
+    fn load(
+        &self,
+        referrer: &Referrer,
+        specifier: &JsString,
+        context: &mut Context,
+    ) -> JsResult<Module> {
+        let path = resolve_module_specifier(Some(&self.root), specifier, referrer.path(), context)?;
+        if let Some(module) = self.inner.get(&path) {
+            return Ok(module);
+        }
+        let display = path.display().to_string();
+        let source = std::fs::read_to_string(&path).map_err(|err| {
+            JsNativeError::typ().with_message(format!("could not read {display}: {err}"))
+        })?;
+        let js = typescript::transpile(&source)
+            .map_err(|err| JsNativeError::syntax().with_message(format!("{display}: {err}")))?;
+        let module = Module::parse(
+            Source::from_reader(js.as_bytes(), Some(&path)),
+            None,
+            context,
+        )?;
+        self.inner.insert(path, module.clone());
+        Ok(module)
+    }
+}
+
+#[cfg(feature = "typescript")]
+impl ModuleLoader for TypeScriptLoader {
+    fn load_imported_module(
+        &self,
+        referrer: Referrer,
+        specifier: JsString,
+        finish_load: Box<dyn FnOnce(JsResult<Module>, &mut Context)>,
+        context: &mut Context,
+    ) {
+        let extension = specifier.to_std_string_escaped();
+        let extension = Path::new(&extension).extension().and_then(OsStr::to_str);
+        if matches!(extension, Some("ts" | "mts" | "cts")) {
+            let module = self.load(&referrer, &specifier, context);
+            finish_load(module, context);
+        } else {
+            self.inner
+                .load_imported_module(referrer, specifier, finish_load, context);
+        }
+    }
+}
+
+/// A function which registers a global helper.
+type Register = fn(&mut Context) -> Result<()>;
+
+/// The helpers which need a capability, with the capability each needs.
+const HELPERS: [(&str, Capability, Register); 4] = [
+    ("read", Capability::Fs, register_read),
+    ("require", Capability::Fs, register_require),
+    ("fetch", Capability::Net, register_fetch),
+    ("write", Capability::Fs, register_write),
+];
+
+/// Register the helpers which need a capability, or in place of each one that was taken away, a
+/// function which says so.
+fn register_capabilities(context: &mut Context) -> Result<()> {
+    for (name, capability, register) in HELPERS {
+        if safe::allowed(capability) {
+            register(context)?;
+        } else {
+            register_disabled(name, capability, context)?;
+        }
+    }
+    Ok(())
+}
+
+/// Register `name` as a function which fails with a `TypeError` explaining that `capability` was
+/// taken away, rather than leaving it undefined.
+fn register_disabled(name: &str, capability: Capability, context: &mut Context) -> Result<()> {
+    call_fn(
+        "(name, message) => {
+            globalThis[name] = () => {
+                throw new TypeError(message);
+            };
+        }",
+        &[
+            JsValue::from(JsString::from(name)),
+            JsValue::from(JsString::from(safe::denied(capability, name))),
+        ],
+        context,
+    )?;
+    Ok(())
+}
+
+/// Make the request described by `args` (method, URL, headers as JSON, and body) for `fetch`,
+/// returning the response as JSON.
+fn request(args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let method = get_std_string(args, 0, context)?;
+    let url = get_std_string(args, 1, context)?;
+    let headers: Vec<(String, String)> =
+        serde_json::from_str(&get_std_string(args, 2, context)?).to_js()?;
+    let body = if args.get_or_undefined(3).is_undefined() {
+        None
+    } else {
+        Some(get_std_string(args, 3, context)?)
+    };
+    let res = http::fetch(&method, &url, &headers, body).to_js()?;
+    let headers = res
+        .headers
+        .into_iter()
+        .map(|(name, value)| (name, serde_json::Value::from(value)))
+        .collect::<serde_json::Map<_, _>>();
+    let res = serde_json::json!({
+        "status": res.status,
+        "headers": headers,
+        "body": res.body,
+    });
+    Ok(JsValue::from(JsString::from(res.to_string())))
+}
+
+/// Register `fetch`, a subset of the Fetch API. Requests are made synchronously, but it returns a
+/// promise like the real thing, so that scripts written for it work unchanged.
+fn register_fetch(context: &mut Context) -> Result<()> {
+    let request = NativeFunction::from_fn_ptr(|_, args, context| request(args, context))
+        .to_js_function(&context.realm().clone());
+    let fetch = call_fn(
+        "(request) => async function fetch(resource, options = {}) {
+            const url = String(resource);
+            const res = JSON.parse(request(
+                options.method ?? 'GET',
+                url,
+                JSON.stringify(
+                    Object.entries(options.headers ?? {}).map(([k, v]) => [k, String(v)]),
+                ),
+                options.body,
+            ));
+            const headers = new Map(Object.entries(res.headers));
+            return {
+                url,
+                status: res.status,
+                ok: res.status >= 200 && res.status < 300,
+                headers: { get: (name) => headers.get(String(name).toLowerCase()) ?? null },
+                text: async () => res.body,
+                json: async () => JSON.parse(res.body),
+            };
+        }",
+        &[JsValue::from(request)],
+        context,
+    )?;
+    context
+        .register_global_property(JsString::from("fetch"), fetch, Attribute::all())
+        .to_anyhow(context)
+}
+
+/// Deep-merge the JSON array of values `args[1]`, merging arrays by the strategy `args[0]`,
+/// returning the result as JSON.
+fn merge(args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let arrays = merge::parse_arrays(&get_std_string(args, 0, context)?)
+        .map_err(|err| anyhow!(err))
+        .to_js()?;
+    let values = serde_json::from_str::<Vec<_>>(&get_std_string(args, 1, context)?).to_js()?;
+    let merged = merge::merge(values, &arrays).unwrap_or_default();
+    Ok(JsValue::from(JsString::from(merged.to_string())))
+}
+
+/// Register `merge`, which deep-merges its arguments, later ones taking precedence, and
+/// `merge.with(strategy)`, which returns a `merge` that merges arrays by `strategy`, as for
+/// --merge-arrays.
+fn register_merge(context: &mut Context) -> Result<()> {
+    let merge = NativeFunction::from_fn_ptr(|_, args, context| merge(args, context))
+        .to_js_function(&context.realm().clone());
+    let merge = call_fn(
+        "(merge) => {
+            const withArrays = (strategy) => (...values) =>
+                JSON.parse(merge(strategy, JSON.stringify(values)));
+            const f = withArrays('replace');
+            f.with = withArrays;
+            return f;
+        }",
+        &[JsValue::from(merge)],
+        context,
+    )?;
+    context
+        .register_global_property(JsString::from("merge"), merge, Attribute::all())
+        .to_anyhow(context)
+}
+
+/// Register `std`, a small library of the helpers which most transforms end up writing for
+/// themselves. Where a helper takes a function of each element, it can be given the name of a
+/// property instead.
+fn register_std(context: &mut Context) -> Result<()> {
+    context
+        .eval(Source::from_bytes(
+            "(() => {
+                const by = (f) => typeof f === 'function' ? f : (x) => x[f];
+                const compare = (a, b) => a < b ? -1 : a > b ? 1 : 0;
+                globalThis.std = {
+                    groupBy: (xs, f) => {
+                        const groups = {};
+                        for (const x of xs) {
+                            (groups[by(f)(x)] ??= []).push(x);
+                        }
+                        return groups;
+                    },
+                    uniqBy: (xs, f) => {
+                        const seen = new Set();
+                        return xs.filter((x) => {
+                            const key = by(f)(x);
+                            if (seen.has(key)) {
+                                return false;
+                            }
+                            seen.add(key);
+                            return true;
+                        });
+                    },
+                    sortBy: (xs, f) => [...xs].sort((a, b) => compare(by(f)(a), by(f)(b))),
+                    pick: (obj, ...keys) => Object.fromEntries(
+                        keys.flat().filter((k) => k in obj).map((k) => [k, obj[k]]),
+                    ),
+                    omit: (obj, ...keys) => Object.fromEntries(
+                        Object.entries(obj).filter(([k]) => !keys.flat().includes(k)),
+                    ),
+                    chunk: (xs, n) => Array.from(
+                        { length: Math.ceil(xs.length / n) },
+                        (_, i) => xs.slice(i * n, i * n + n),
+                    ),
+                    sum: (xs, f = (x) => x) => xs.reduce((acc, x) => acc + by(f)(x), 0),
+                    flattenDeep: (xs) => xs.flat(Infinity),
+                };
+            })()",
+        ))
+        .to_anyhow(context)?;
+    Ok(())
+}
+
+/// Register `time`, for parsing and formatting timestamps and durations. Timestamps are formatted
+/// in UTC, or at a fixed offset such as `+05:30`, since the engine has no time zone database.
+fn register_time(context: &mut Context) -> Result<()> {
+    context
+        .eval(Source::from_bytes(
+            "(() => {
+                const units = { w: 604800000, d: 86400000, h: 3600000, m: 60000, s: 1000, ms: 1 };
+                const days = ['Sunday', 'Monday', 'Tuesday', 'Wednesday', 'Thursday', 'Friday',
+                    'Saturday'];
+                const months = ['January', 'February', 'March', 'April', 'May', 'June', 'July',
+                    'August', 'September', 'October', 'November', 'December'];
+                const pad = (n, width = 2) => String(n).padStart(width, '0');
+                const parse = (t) => {
+                    const date = t instanceof Date ? new Date(t)
+                        : typeof t === 'number' ? new Date(t * 1000)
+                        : new Date(String(t));
+                    if (isNaN(date)) {
+                        throw new RangeError(`invalid timestamp: ${t}`);
+                    }
+                    return date;
+                };
+                const minutes = (offset) => {
+                    if (offset === 'Z' || offset === 'UTC') {
+                        return 0;
+                    }
+                    if (offset === 'local') {
+                        return -new Date().getTimezoneOffset();
+                    }
+                    const m = /^([+-])(\\d\\d):?(\\d\\d)$/.exec(offset);
+                    if (!m) {
+                        throw new RangeError(`invalid offset: ${offset}`);
+                    }
+                    return (m[1] === '-' ? -1 : 1) * (Number(m[2]) * 60 + Number(m[3]));
+                };
+                const suffix = (mins, colon = true) => mins === 0 && colon ? 'Z'
+                    : (mins < 0 ? '-' : '+') + pad(Math.floor(Math.abs(mins) / 60))
+                        + (colon ? ':' : '') + pad(Math.abs(mins) % 60);
+                const format = (t, fmt, offset = 'Z') => {
+                    const date = parse(t);
+                    const mins = minutes(offset);
+                    // Shift by the offset, and then read the fields in UTC.
+                    const d = new Date(date.getTime() + mins * 60000);
+                    const start = Date.UTC(d.getUTCFullYear(), 0, 1);
+                    const fields = {
+                        Y: () => d.getUTCFullYear(),
+                        m: () => pad(d.getUTCMonth() + 1),
+                        d: () => pad(d.getUTCDate()),
+                        H: () => pad(d.getUTCHours()),
+                        I: () => pad((d.getUTCHours() + 11) % 12 + 1),
+                        p: () => d.getUTCHours() < 12 ? 'AM' : 'PM',
+                        M: () => pad(d.getUTCMinutes()),
+                        S: () => pad(d.getUTCSeconds()),
+                        L: () => pad(d.getUTCMilliseconds(), 3),
+                        j: () => pad(Math.floor((d - start) / units.d) + 1, 3),
+                        a: () => days[d.getUTCDay()].slice(0, 3),
+                        A: () => days[d.getUTCDay()],
+                        b: () => months[d.getUTCMonth()].slice(0, 3),
+                        B: () => months[d.getUTCMonth()],
+                        s: () => Math.floor(date.getTime() / 1000),
+                        z: () => suffix(mins, false),
+                        Z: () => suffix(mins),
+                        F: () => `${fields.Y()}-${fields.m()}-${fields.d()}`,
+                        T: () => `${fields.H()}:${fields.M()}:${fields.S()}`,
+                        '%': () => '%',
+                    };
+                    return fmt.replace(/%(.)/g, (s, c) => c in fields ? fields[c]() : s);
+                };
+                globalThis.time = {
+                    parse,
+                    format,
+                    toOffset: (t, offset) => {
+                        const mins = minutes(offset);
+                        const ms = parse(t).getUTCMilliseconds() ? '.%L' : '';
+                        return format(t, `%FT%T${ms}`, offset) + suffix(mins);
+                    },
+                    duration: (s) => {
+                        const re = /(\\d+(?:\\.\\d+)?)(ms|[wdhms])/g;
+                        if (!/^(\\d+(\\.\\d+)?(ms|[wdhms]))+$/.test(s)) {
+                            throw new RangeError(`invalid duration: ${s}`);
+                        }
+                        let total = 0;
+                        for (const [, n, unit] of s.matchAll(re)) {
+                            total += Number(n) * units[unit];
+                        }
+                        return total;
+                    },
+                    formatDuration: (ms) => {
+                        let rest = Math.round(ms);
+                        let s = rest < 0 ? '-' : '';
+                        rest = Math.abs(rest);
+                        for (const [unit, size] of Object.entries(units)) {
+                            if (rest >= size) {
+                                s += Math.floor(rest / size) + unit;
+                                rest %= size;
+                            }
+                        }
+                        return s === '' || s === '-' ? '0s' : s;
+                    },
+                };
+            })()",
+        ))
+        .to_anyhow(context)?;
+    Ok(())
+}
+
+/// Register `spread`, which marks an iterable so that each of its values is printed as a result of
+/// its own, and return a function which turns a result in to an array of such values, or undefined
+/// if it isn't to be spread. Generators, and generator functions, which are called with the input,
+/// are spread without being marked.
+fn register_spread(context: &mut Context) -> Result<JsObject> {
+    let spread = call_fn(
+        "(() => {
+            const marker = Symbol('spread');
+            globalThis.spread = (iterable) => ({ [marker]: iterable });
+            const GeneratorFunction = Object.getPrototypeOf(function* () {}).constructor;
+            return (res, input) => {
+                if (res instanceof GeneratorFunction) {
+                    res = res(input);
+                }
+                if (res?.[marker] !== undefined) {
+                    return [...res[marker]];
+                }
+                if (res?.[Symbol.toStringTag] === 'Generator') {
+                    return [...res];
+                }
+                return undefined;
+            };
+        })",
+        &[],
+        context,
+    )?;
+    Ok(spread.as_callable().context("as callable")?.clone())
+}
+
+/// Register `lookup`, with a property for each table, whose `get(key)` and `has(key)` look a record
+/// up by key, converting `key` to a string as --lookup does.
+fn register_lookup(
+    lookups: Vec<(String, String)>,
+    reviver: &JsObject,
+    context: &mut Context,
+) -> Result<()> {
+    let mut tables = Vec::new();
+    for (name, json) in lookups {
+        tables.push((name, parse_json(&json, reviver, context)?));
+    }
+    let mut obj = ObjectInitializer::new(context);
+    for (name, table) in tables {
+        obj.property(JsString::from(name), table, Attribute::all());
+    }
+    let obj = obj.build();
+    let lookup = call_fn(
+        "(tables) => {
+            for (const [name, records] of Object.entries(tables)) {
+                const index = new Map(Object.entries(records));
+                tables[name] = {
+                    get: (key) => index.get(String(key)),
+                    has: (key) => index.has(String(key)),
+                };
+            }
+            return tables;
+        }",
+        &[JsValue::from(obj)],
+        context,
+    )?;
+    context
+        .register_global_property(JsString::from("lookup"), lookup, Attribute::all())
+        .to_anyhow(context)
+}
+
 fn register_print(context: &mut Context) -> Result<()> {
     context
         .register_global_builtin_callable(
             JsString::from("print"),
             1,
             NativeFunction::from_fn_ptr(|_, args, context| {
-                let value = get_std_string(args, 0, context)?;
-                if value.ends_with('\n') {
-                    print!("{value}");
-                } else {
-                    println!("{value}");
-                }
+                print::text(&get_std_string(args, 0, context)?).to_js()?;
                 Ok(JsValue::Undefined)
             }),
         )
         .to_anyhow(context)
 }
 
+/// Print the JSON array of parts `args[0]` on a line to STDERR, separated by spaces. Each part is a
+/// pair of a kind and a string: either text, which is printed as is, or JSON, which is printed as
+/// results are, in color if STDERR supports it.
+fn debug(args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let parts = serde_json::from_str::<Vec<(String, String)>>(&get_std_string(args, 0, context)?)
+        .to_js()?;
+    let w = &mut print::stderr();
+    // Whether anything has been printed on the current line, since JSON ends with a newline.
+    let mut open = false;
+    for (kind, s) in parts {
+        if open {
+            write!(w, " ").to_js()?;
+        }
+        if kind == "json" {
+            print::json(w, &print::Options::default(), &s).to_js()?;
+            open = false;
+        } else {
+            write!(w, "{s}").to_js()?;
+            open = true;
+        }
+    }
+    if open {
+        writeln!(w).to_js()?;
+    }
+    Ok(JsValue::undefined())
+}
+
+/// Register `debug(value)`, which prints `value` to STDERR and returns it, so that it can be
+/// wrapped around any part of SCRIPT, and `console`, whose methods print their arguments to
+/// STDERR too, so that neither gets mixed up with results.
+fn register_debug(context: &mut Context) -> Result<()> {
+    let debug = NativeFunction::from_fn_ptr(|_, args, context| debug(args, context))
+        .to_js_function(&context.realm().clone());
+    call_fn(
+        "(debug) => {
+            const part = (x) => {
+                if (typeof x === 'string') {
+                    return ['text', x];
+                }
+                try {
+                    const json = JSON.stringify(x);
+                    if (json !== undefined) {
+                        return ['json', json];
+                    }
+                } catch {}
+                return ['text', String(x)];
+            };
+            const log = (...values) => debug(JSON.stringify(values.map(part)));
+            globalThis.debug = (value) => {
+                log(value);
+                return value;
+            };
+            globalThis.console = {
+                log,
+                info: log,
+                warn: log,
+                error: log,
+                debug: log,
+            };
+        }",
+        &[JsValue::from(debug)],
+        context,
+    )?;
+    Ok(())
+}
+
 macro_rules! register_parse_and_stringify {
     ($name:expr, $parse:expr, $print:expr, $context:expr) => {{
         let obj = ObjectInitializer::new($context)
@@ -146,45 +740,489 @@ macro_rules! register_parse_and_stringify {
     }};
 }
 
-pub fn eval<I: Iterator<Item = (String, String)>>(options: Options<'_, I>) -> Result<String> {
+fn register_jsq(filename: Option<&str>, context: &mut Context) -> Result<()> {
+    let filename = filename.map_or(JsValue::undefined(), |f| JsValue::from(JsString::from(f)));
+    let obj = ObjectInitializer::new(context)
+        .property(JsString::from("filename"), filename, Attribute::all())
+        .build();
+    context
+        .register_global_property(JsString::from("jsq"), obj, Attribute::all())
+        .to_anyhow(context)
+}
+
+/// The names of the globals which jsq adds to JavaScript's own, leaving out helpers whose
+/// capability has been taken away, as they are for SCRIPT in this run.
+pub fn globals() -> Result<Vec<String>> {
+    let runtime = Runtime::new(Options {
+        env: std::iter::empty(),
+        vars: Vec::new(),
+        lookups: Vec::new(),
+        init: None,
+        parse: true,
+        stringify: true,
+        undefined: Undefined::Omit,
+    })?;
+    let mut context = Context::default();
+    let builtins: HashSet<_> = context
+        .global_object()
+        .own_property_keys(&mut context)
+        .to_anyhow(&mut context)?
+        .into_iter()
+        .collect();
+    Ok(runtime
+        .globals
+        .into_iter()
+        .filter(|(key, _)| !builtins.contains(key))
+        .map(|(key, _)| key.to_string())
+        .filter(|name| {
+            HELPERS
+                .iter()
+                .all(|&(helper, capability, _)| helper != name || safe::allowed(capability))
+        })
+        .collect())
+}
+
+/// Parse `script` without evaluating it, failing as evaluating it would if it isn't valid.
+pub fn check(script: &str) -> Result<()> {
     let mut context = Context::default();
     context.strict(true);
+    Script::parse(Source::from_bytes(script), None, &mut context)
+        .to_anyhow(&mut context)
+        .map_err(|err| hint::eval_error(&err, script))?;
+    Ok(())
+}
+
+/// A script to evaluate as part of a pipeline, and the file it was read from, if it was read from
+/// one.
+#[derive(Clone, Default)]
+pub struct Step {
+    pub script: String,
+    pub file: Option<String>,
+}
 
-    register_read(&mut context)?;
-    register_write(&mut context)?;
-    register_print(&mut context)?;
+/// Evaluate `script` with `eval`, returning its result, or the values it spreads in to, and whether
+/// it was spread. A promise is awaited, so that `script` can be asynchronous.
+fn eval_step(
+    eval: &JsObject,
+    spread: &JsObject,
+    script: &str,
+    input: JsValue,
+    context: &mut Context,
+) -> JsResult<(Vec<JsValue>, bool)> {
+    let _evaluation = limit::evaluation();
+    profile::time(Stage::Script, || {
+        let res = eval.call(
+            &JsValue::undefined(),
+            &[JsValue::from(JsString::from(script))],
+            context,
+        )?;
+        let res = settle(res, context)?;
+        let values = spread.call(&JsValue::undefined(), &[res.clone(), input], context)?;
+        let Some(arr) = values.as_object() else {
+            return Ok((vec![res], false));
+        };
+        let arr = JsArray::from_object(arr.clone())?;
+        let len = arr.length(context)?;
+        let values = (0..len)
+            .map(|i| arr.get(i, context))
+            .collect::<JsResult<_>>()?;
+        Ok((values, true))
+    })
+}
 
-    register_parse_and_stringify!("YAML", parse::yaml, print::yaml_to_string, &mut context);
-    register_parse_and_stringify!("TOML", parse::toml, print::toml_to_string, &mut context);
+/// Whether SCRIPT could tell two descriptors of a property apart.
+fn same_property(a: &PropertyDescriptor, b: &PropertyDescriptor) -> bool {
+    let same = |a: Option<&JsValue>, b: Option<&JsValue>| match (a, b) {
+        (Some(a), Some(b)) => JsValue::same_value(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    };
+    same(a.value(), b.value())
+        && same(a.get(), b.get())
+        && same(a.set(), b.set())
+        && a.writable() == b.writable()
+        && a.enumerable() == b.enumerable()
+        && a.configurable() == b.configurable()
+}
 
-    let mut input = JsValue::from(JsString::from(options.input));
-    if options.parse {
-        input = call_fn("JSON.parse", &[input], &mut context)?;
-    }
+/// Each global, in order, as it is now. See [`Runtime::reset`].
+fn save_globals(context: &mut Context) -> Result<Vec<(PropertyKey, Option<PropertyDescriptor>)>> {
+    // $ and jsq are replaced for each input, but registering them now puts them in the same place
+    // among the globals as they'll be after each input.
+    register_jsq(None, context)?;
     context
-        .register_global_property(JsString::from("$"), input, Attribute::all())
-        .to_anyhow(&mut context)?;
+        .register_global_property(JsString::from("$"), JsValue::undefined(), Attribute::all())
+        .to_anyhow(context)?;
+
+    let global = context.global_object();
+    Ok(global
+        .own_property_keys(context)
+        .to_anyhow(context)?
+        .into_iter()
+        .map(|key| {
+            let per_input =
+                matches!(&key, PropertyKey::String(name) if name == "$" || name == "jsq");
+            let property = global.borrow().properties().get(&key);
+            (key, property.filter(|_| !per_input))
+        })
+        .collect())
+}
 
-    for (k, v) in options.env {
+/// JavaScript which saves the globals as they are, and returns a function which puts back the one
+/// named by its argument: removing it if it's been added, or restoring it if it's been replaced or
+/// removed.
+const RESTORE: &str = "(() => {
+    const saved = Object.getOwnPropertyDescriptors(globalThis);
+    return (key) => Object.hasOwn(saved, key)
+        ? Object.defineProperty(globalThis, key, saved[key])
+        : Reflect.deleteProperty(globalThis, key);
+})()";
+
+pub struct Runtime {
+    context: Context,
+    eval: JsObject,
+    /// Turns strings marked as `BigInt`s in to `BigInt`s when parsing JSON.
+    reviver: JsObject,
+    /// Turns `BigInt`s in to marked strings when stringifying JSON, and undefined values in to
+    /// whatever --undefined says.
+    replacer: JsObject,
+    /// Turns a result which is to be spread in to an array of results. See [`register_spread`].
+    spread: JsObject,
+    /// Each global, in order, as it was once the runtime was set up, to tell which have changed
+    /// since. Those which are replaced for each input anyway have no descriptor.
+    globals: Vec<(PropertyKey, Option<PropertyDescriptor>)>,
+    /// Puts a global back as it was once the runtime was set up. See [`RESTORE`].
+    restore: JsObject,
+    /// The accumulator for --reduce, once a result has been folded in to it.
+    accumulator: Option<JsValue>,
+    /// Functions given by flags, such as --reduce, by their source, so each is compiled only once.
+    functions: HashMap<String, JsObject>,
+    parse: bool,
+    stringify: bool,
+    undefined: Undefined,
+}
+
+impl Runtime {
+    pub fn new<I: Iterator<Item = (String, String)>>(options: Options<I>) -> Result<Self> {
+        let safe = safe::enabled();
+        let mut builder = Context::builder();
+        // Modules are loaded relative to the current directory, unless --safe or --no-fs rules out
+        // reading files at all.
+        if safe::allowed(Capability::Fs) {
+            #[cfg(feature = "typescript")]
+            let loader = TypeScriptLoader::new()?;
+            #[cfg(not(feature = "typescript"))]
+            let loader = SimpleModuleLoader::new(".").map_err(|err| anyhow!("{err}"))?;
+            builder = builder.module_loader(Rc::new(loader));
+        }
+        let mut context = builder.build().map_err(|err| anyhow!("{err}"))?;
+        context.strict(true);
+
+        if safe {
+            context
+                .runtime_limits_mut()
+                .set_loop_iteration_limit(safe::MAX_LOOP_ITERATIONS);
+            context
+                .eval(Source::from_bytes(safe::PRELUDE))
+                .to_anyhow(&mut context)?;
+        }
+        register_capabilities(&mut context)?;
+        register_print(&mut context)?;
+        register_debug(&mut context)?;
+        register_merge(&mut context)?;
+        register_std(&mut context)?;
+        register_time(&mut context)?;
+        let spread = register_spread(&mut context)?;
+
+        register_parse_and_stringify!("YAML", parse::yaml, print::yaml_to_string, &mut context);
+        register_parse_and_stringify!("TOML", parse::toml, print::toml_to_string, &mut context);
+
+        // Environment variables are available both individually, prefixed by $, and all together
+        // as the properties of env.
+        let env = options.env.collect::<Vec<_>>();
+        for (k, v) in &env {
+            context
+                .register_global_property(
+                    JsString::from(format!("${k}")),
+                    JsString::from(v.as_str()),
+                    Attribute::all(),
+                )
+                .to_anyhow(&mut context)?;
+        }
+        let mut obj = ObjectInitializer::new(&mut context);
+        for (k, v) in env {
+            obj.property(JsString::from(k), JsString::from(v), Attribute::all());
+        }
+        let obj = obj.build();
         context
-            .register_global_property(
-                JsString::from(format!("${k}")),
-                JsString::from(v),
-                Attribute::all(),
-            )
+            .register_global_property(JsString::from("env"), obj, Attribute::all())
             .to_anyhow(&mut context)?;
+
+        if let Some((path, init)) = &options.init {
+            context
+                .eval(Source::from_bytes(init))
+                .to_anyhow(&mut context)
+                .with_context(|| format!("evaluating {path}"))?;
+        }
+
+        // SCRIPT is evaluated with a direct eval inside a function, rather than as a script in its
+        // own right, so that its declarations are scoped to a single evaluation and it can be
+        // evaluated repeatedly in the same context.
+        let eval = eval_fn("(function () { return eval(arguments[0]); })", &mut context)?;
+
+        let marker = serde_json::to_string(precision::BIGINT)?;
+        let reviver = eval_fn(
+            &format!(
+                "(k, v) => typeof v === 'string' && v.startsWith({marker}) \
+                    ? BigInt(v.slice({marker}.length)) : v"
+            ),
+            &mut context,
+        )?;
+        let replacer = eval_fn(
+            &format!(
+                "(k, v) => typeof v === 'bigint' ? {marker} + v : {}",
+                options.undefined.replacement()
+            ),
+            &mut context,
+        )?;
+
+        // Registered after the environment, so that they take precedence.
+        for (k, v) in options.vars {
+            let value = parse_json(&v, &reviver, &mut context)?;
+            context
+                .register_global_property(JsString::from(format!("${k}")), value, Attribute::all())
+                .to_anyhow(&mut context)?;
+        }
+
+        register_lookup(options.lookups, &reviver, &mut context)?;
+
+        // Taken last, so that everything registered above is kept.
+        let restore = eval_fn(RESTORE, &mut context)?;
+        let globals = save_globals(&mut context)?;
+
+        Ok(Self {
+            context,
+            eval,
+            reviver,
+            replacer,
+            spread,
+            globals,
+            restore,
+            accumulator: None,
+            functions: HashMap::new(),
+            parse: options.parse,
+            stringify: options.stringify,
+            undefined: options.undefined,
+        })
     }
 
-    let mut res = context
-        .eval(Source::from_bytes(options.script))
-        .to_anyhow(&mut context)?;
+    /// Put the globals back as they were once the runtime was set up, so that nothing one input
+    /// leaves behind is seen by the next.
+    /// This is done for every input, so the globals are checked here rather than in JavaScript,
+    /// and without hashing each key unless some have been added or removed.
+    pub fn reset(&mut self) -> Result<()> {
+        let context = &mut self.context;
+        let global = context.global_object();
+        let keys = global.own_property_keys(context).to_anyhow(context)?;
+        let mut changed = Vec::new();
+        if keys.len() != self.globals.len()
+            || keys
+                .iter()
+                .zip(&self.globals)
+                .any(|(key, (saved, _))| key != saved)
+        {
+            let saved: HashSet<_> = self.globals.iter().map(|(key, _)| key).collect();
+            changed.extend(keys.into_iter().filter(|key| !saved.contains(key)));
+        }
+        {
+            let object = global.borrow();
+            for (key, saved) in &self.globals {
+                if let Some(saved) = saved
+                    && !object
+                        .properties()
+                        .get(key)
+                        .is_some_and(|property| same_property(&property, saved))
+                {
+                    changed.push(key.clone());
+                }
+            }
+        }
+        for key in changed {
+            self.restore
+                .call(&JsValue::undefined(), &[JsValue::from(key)], context)
+                .to_anyhow(context)?;
+        }
+        Ok(())
+    }
 
-    if options.stringify {
-        res = call_fn("JSON.stringify", &[res], &mut context)?;
+    /// Evaluate SCRIPT for `input`. The results of a spread are returned together, as an array.
+    pub fn eval(&mut self, script: &str, input: &str, filename: Option<&str>) -> Result<String> {
+        let step = Step {
+            script: script.to_string(),
+            file: None,
+        };
+        let (results, spread) = self.eval_all(std::slice::from_ref(&step), input, filename)?;
+        Ok(match results.as_slice() {
+            [res] if !spread => res.clone(),
+            _ if self.stringify => format!("[{}]", results.join(",")),
+            _ => results.join("\n"),
+        })
     }
 
-    Ok(res
-        .to_string(&mut context)
-        .to_anyhow(&mut context)?
-        .to_std_string()?)
+    /// Evaluate each of `steps` in turn for `input`, with the result of each as `$` in the next,
+    /// returning each result of a spread separately, or a single result otherwise. A step which
+    /// spreads its result has the steps after it evaluated for each value.
+    pub fn eval_steps(
+        &mut self,
+        steps: &[Step],
+        input: &str,
+        filename: Option<&str>,
+    ) -> Result<Vec<String>> {
+        Ok(self.eval_all(steps, input, filename)?.0)
+    }
+
+    /// Evaluate `steps` for `input`, returning the results, and whether they were spread.
+    fn eval_all(
+        &mut self,
+        steps: &[Step],
+        input: &str,
+        filename: Option<&str>,
+    ) -> Result<(Vec<String>, bool)> {
+        let context = &mut self.context;
+
+        register_jsq(filename, context)?;
+
+        let input_json = input;
+        let mut numbers = Numbers::default();
+        let mut input = JsValue::from(JsString::from(input));
+        if self.parse {
+            numbers = Numbers::new(input_json);
+            input = profile::time(Stage::Convert, || {
+                parse_json(input_json, &self.reviver, context)
+            })?;
+        }
+
+        let mut values = vec![input];
+        let mut spread = false;
+        for (i, step) in steps.iter().enumerate() {
+            let mut next = Vec::new();
+            for input in values {
+                context
+                    .register_global_property(JsString::from("$"), input.clone(), Attribute::all())
+                    .to_anyhow(context)?;
+                let (res, spread_step) =
+                    eval_step(&self.eval, &self.spread, &step.script, input, context)
+                        .to_anyhow(context)
+                        .map_err(|err| {
+                            // Only the first step is evaluated for the input as it was given.
+                            let err = if self.parse && i == 0 {
+                                hint::type_error(err, &step.script, input_json)
+                            } else {
+                                err
+                            };
+                            let err = hint::eval_error(&err, &step.script);
+                            match &step.file {
+                                Some(file) => diagnostic::from_file(err, file),
+                                None => err,
+                            }
+                        })?;
+                spread |= spread_step;
+                next.extend(res);
+            }
+            values = next;
+        }
+
+        let results = values
+            .into_iter()
+            .map(|res| self.convert(res, &numbers))
+            .collect::<Result<_>>()?;
+        Ok((results, spread))
+    }
+
+    /// Convert a result of SCRIPT to a string, as JSON if it's to be stringified. Either way, dates
+    /// are converted to RFC 3339 timestamps.
+    fn convert(&mut self, mut res: JsValue, numbers: &Numbers) -> Result<String> {
+        let context = &mut self.context;
+        profile::time(Stage::Convert, || -> Result<String> {
+            if self.stringify {
+                // A result which is itself undefined is printed as such, whatever --undefined says.
+                let always = self.undefined != Undefined::Omit && !res.is_undefined();
+                res = stringify(&res, &self.replacer, always, context)?;
+            } else if let Some(date) = res
+                .as_object()
+                .and_then(|obj| JsDate::from_object(obj.clone()).ok())
+            {
+                // As when stringified, rather than in the engine's own format.
+                res = date.to_iso_string(context).to_anyhow(context)?;
+            }
+            let res = res.to_string(context).to_anyhow(context)?.to_std_string()?;
+            let res = precision::unmark_bigints(res);
+            // Only JSON has numbers which can be told apart from the rest of the text.
+            let res = if self.stringify {
+                numbers.restore(res)
+            } else {
+                res
+            };
+            if self.undefined == Undefined::Error {
+                undefined::check(&res)?;
+            }
+            Ok(res)
+        })
+    }
+
+    /// Fold the JSON value `json` in to the accumulator with the JavaScript function `f`, starting
+    /// from the JSON value `init`. The accumulator is kept as a JavaScript value, rather than as
+    /// JSON, so that it isn't converted back and forth for every result.
+    pub fn reduce(&mut self, f: &str, init: &str, json: &str) -> Result<()> {
+        let f = self.function(f)?;
+        let context = &mut self.context;
+        let acc = match self.accumulator.take() {
+            Some(acc) => acc,
+            None => parse_json(init, &self.reviver, context)?,
+        };
+        let value = parse_json(json, &self.reviver, context)?;
+        let acc = f
+            .call(&JsValue::undefined(), &[acc, value], context)
+            .to_anyhow(context)?;
+        self.accumulator = Some(settle(acc, context).to_anyhow(context)?);
+        Ok(())
+    }
+
+    /// The accumulator as JSON, or `init` if nothing has been folded in to it.
+    pub fn reduced(&mut self, init: &str) -> Result<String> {
+        let context = &mut self.context;
+        let Some(acc) = self.accumulator.take() else {
+            return Ok(init.to_string());
+        };
+        let res = stringify(&acc, &self.replacer, false, context)?
+            .to_string(context)
+            .to_anyhow(context)?
+            .to_std_string()?;
+        Ok(precision::unmark_bigints(res))
+    }
+
+    /// Call the JavaScript function `f` on the JSON value `json`, returning the result as JSON.
+    pub fn apply(&mut self, f: &str, json: &str) -> Result<String> {
+        let f = self.function(f)?;
+        let context = &mut self.context;
+        let arg = parse_json(json, &self.reviver, context)?;
+        let res = f
+            .call(&JsValue::undefined(), &[arg], context)
+            .to_anyhow(context)?;
+        let res = settle(res, context).to_anyhow(context)?;
+        let res = stringify(&res, &self.replacer, false, context)?
+            .to_string(context)
+            .to_anyhow(context)?
+            .to_std_string()?;
+        Ok(Numbers::new(json).restore(precision::unmark_bigints(res)))
+    }
+
+    /// The JavaScript function `f`, compiled the first time it's called for.
+    fn function(&mut self, f: &str) -> Result<JsObject> {
+        if let Some(function) = self.functions.get(f) {
+            return Ok(function.clone());
+        }
+        let function = eval_fn(&format!("({f})"), &mut self.context)?;
+        self.functions.insert(f.to_string(), function.clone());
+        Ok(function)
+    }
 }
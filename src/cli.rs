@@ -0,0 +1,2281 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    ffi::OsStr,
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind, IsTerminal, Read, Write},
+    num::NonZeroUsize,
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::Parser;
+use regex::Regex;
+use termcolor::NoColor;
+
+#[cfg(feature = "lua")]
+use crate::lua;
+#[cfg(feature = "typescript")]
+use crate::typescript;
+#[cfg(feature = "self-update")]
+use crate::update;
+#[cfg(feature = "wasm")]
+use crate::wasm;
+use crate::{
+    boa::{self, Options, Runtime, Step},
+    compress::{self, Compression},
+    detect,
+    diagnostic::{self, Diagnostic, Kind},
+    diff,
+    dupes::Dupes,
+    edit,
+    encoding::{self, InvalidUtf8},
+    explore, http, index, info, limit, lint, lookup, manifest, merge, mmap, pager, parse, path,
+    plugin,
+    pool::{self, Output, Pool, Task},
+    print::{self, ColorWhen, Format},
+    profile::{self, Stage},
+    safe::{self, Capability},
+    schema::Schema,
+    soft,
+    stats::{Aggregate, Duplicates, Frequencies, Histogram, Normalized, Shapes, Table},
+    stream::Stream,
+    suite,
+    undefined::Undefined,
+    watch::Watcher,
+};
+
+/// Evaluate some JavaScript and print the result.
+#[derive(Parser)]
+#[command(
+    version,
+    arg_required_else_help(true),
+    after_help([
+        "Input is avaialable in SCRIPT as $.",
+        "Environment variables are available in SCRIPT prefixed by $.",
+    ].join(" "))
+)]
+#[expect(clippy::struct_excessive_bools)]
+struct Args {
+    /// Parse input as JSON.
+    #[arg(short('j'), long, conflicts_with_all(["yaml_in", "toml_in"]))]
+    json_in: bool,
+
+    /// Allow comments, trailing commas, unquoted keys, and single-quoted strings in JSON input, as
+    /// in JSON5 and JSONC.
+    #[arg(long, requires("json_in"), conflicts_with_all(["stream", "pointer"]))]
+    lenient: bool,
+
+    /// Parse input as YAML.
+    #[arg(short('y'), long, conflicts_with_all(["json_in", "toml_in"]))]
+    yaml_in: bool,
+
+    /// Parse input as TOML.
+    #[arg(short('t'), long, conflicts_with_all(["json_in", "yaml_in"]))]
+    toml_in: bool,
+
+    /// Parse input as gron, lines of the form json.a[0] = 1; such as gron prints.
+    #[arg(
+        short('g'),
+        long,
+        conflicts_with_all(["json_in", "yaml_in", "toml_in", "from", "stream", "pointer"]),
+    )]
+    gron_in: bool,
+
+    /// Parse input as INI, with a section for each object, such as [a.b] or [remote "origin"].
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_in",
+            "yaml_in",
+            "toml_in",
+            "gron_in",
+            "from",
+            "stream",
+            "pointer",
+        ]),
+    )]
+    ini_in: bool,
+
+    /// Parse input as a .env file, NAME=value lines, in to an object of strings.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_in",
+            "yaml_in",
+            "toml_in",
+            "gron_in",
+            "ini_in",
+            "from",
+            "stream",
+            "pointer",
+        ]),
+    )]
+    env_in: bool,
+
+    /// Parse input as a property list, XML or binary, as macOS keeps settings in.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_in",
+            "yaml_in",
+            "toml_in",
+            "gron_in",
+            "ini_in",
+            "env_in",
+            "from",
+            "stream",
+            "pointer",
+        ]),
+    )]
+    plist_in: bool,
+
+    /// What to do with keys which appear more than once in an object in JSON or YAML input: fail,
+    /// or keep the first or the last value.
+    #[arg(
+        long,
+        value_name("POLICY"),
+        default_value("last"),
+        hide_possible_values(true),
+        conflicts_with_all(["stream", "pointer"]),
+    )]
+    dupes: Dupes,
+
+    /// Parse input as FORMAT, using the plugin jsq-format-FORMAT on $PATH.
+    #[arg(
+        long,
+        value_name("FORMAT"),
+        value_parser(plugin::parse),
+        conflicts_with_all(["json_in", "yaml_in", "toml_in", "stream", "pointer"]),
+    )]
+    from: Option<String>,
+
+    /// Guess the format of input from the extension of the first of FILES, or from how STDIN
+    /// starts, and print results in the same format. Flags for input and output formats win.
+    #[arg(short('a'), long, conflicts_with_all(["diff", "merge"]))]
+    auto: bool,
+
+    /// The format --auto guessed, to print results in if no other format is asked for.
+    #[arg(skip)]
+    auto_out: Option<Format>,
+
+    /// Print result as JSON.
+    #[arg(
+        short('J'),
+        long,
+        conflicts_with_all([
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "no_out",
+        ])
+    )]
+    json_out: bool,
+
+    /// Print result as YAML.
+    #[arg(
+        short('Y'),
+        long,
+        conflicts_with_all([
+            "json_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "no_out",
+        ])
+    )]
+    yaml_out: bool,
+
+    /// Print result as TOML.
+    #[arg(
+        short('T'),
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "no_out",
+        ])
+    )]
+    toml_out: bool,
+
+    /// Print result as gron, an assignment to each path in it on its own line, for grep.
+    #[arg(
+        short('G'),
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "no_out",
+        ])
+    )]
+    gron_out: bool,
+
+    /// Print result as INI, with a section for each object in it.
+    #[arg(
+        long,
+        conflicts_with_all(["json_out", "yaml_out", "toml_out", "gron_out", "env_out", "no_out"])
+    )]
+    ini_out: bool,
+
+    /// Print result as a .env file, a NAME=value line for each value in it, named by its path.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "plist_out",
+            "no_out",
+        ])
+    )]
+    env_out: bool,
+
+    /// Print result as an XML property list, or a binary one with --opt plist.binary=true.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "no_out",
+        ])
+    )]
+    plist_out: bool,
+
+    /// Print each result as a record of an RFC 7464 JSON text sequence.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "no_out",
+        ])
+    )]
+    seq: bool,
+
+    /// Print each result as YAML documents, each starting with ---: one for each element if it's an
+    /// array, as kubectl apply -f - expects.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "seq",
+            "no_out",
+        ])
+    )]
+    yaml_docs: bool,
+
+    /// Print each result as RFC 8785 canonical JSON, for hashing or signing: keys sorted, numbers
+    /// written as JavaScript would write them, and no whitespace.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "seq",
+            "yaml_docs",
+            "no_out",
+        ])
+    )]
+    jcs: bool,
+
+    /// Also print each result as FORMAT (json, ndjson, json-seq, yaml, yaml-docs, jcs, toml, gron,
+    /// ini, env, plist, or a plugin) to FILE. May be given more than once.
+    #[arg(long, value_name("FORMAT=FILE"), value_parser(print::parse_output))]
+    to: Vec<(Format, String)>,
+
+    /// Write each result to files of its own instead of printing it: SCRIPT returns an object of
+    /// paths to the values to write to them, in the output format, or the format of the path's
+    /// extension if none is given. Directories are created as needed.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "no_out",
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "reduce",
+            "interactive",
+            "pick",
+            "diff",
+            "merge",
+        ])
+    )]
+    split: bool,
+
+    /// Write each result to the file at the path FN returns for it, as --split does.
+    #[arg(
+        long,
+        value_name("FN"),
+        conflicts_with_all([
+            "split",
+            "no_out",
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "reduce",
+            "interactive",
+            "pick",
+            "diff",
+            "merge",
+        ])
+    )]
+    split_by: Option<String>,
+
+    /// Write --split and --split-by files under DIR, rather than the current directory. Their
+    /// paths must be relative, and can't lead out of it.
+    #[arg(long, value_name("DIR"))]
+    split_dir: Option<String>,
+
+    /// Compress each file given to --to. Files ending in .gz or .zst are compressed anyway.
+    #[arg(long, value_name("ALGORITHM"), requires("to"))]
+    compress: Option<Compression>,
+
+    /// Append to each file given to --to, rather than replacing it.
+    #[arg(long, requires("to"))]
+    append: bool,
+
+    /// Write a manifest of each file given to --to to FILE, as JSON: its path, size, SHA-256, and
+    /// the number of results written to it, along with the SHA-256 of SCRIPT.
+    #[arg(long, value_name("FILE"), requires("to"))]
+    manifest: Option<String>,
+
+    /// Don't print result.
+    #[arg(
+        short('N'),
+        long,
+        conflicts_with_all(["json_out", "yaml_out", "toml_out", "gron_out", "ini_out", "env_out"])
+    )]
+    no_out: bool,
+
+    /// Set $NAME to the string VALUE in SCRIPT. May be given more than once.
+    #[arg(long, num_args(2), value_names(["NAME", "VALUE"]))]
+    arg: Vec<String>,
+
+    /// Set $NAME to the JSON value VALUE in SCRIPT. May be given more than once.
+    #[arg(long, num_args(2), value_names(["NAME", "VALUE"]))]
+    argjson: Vec<String>,
+
+    /// Index the records in PATH, an array or a sequence of values, by --lookup-key, so that
+    /// lookup.NAME.get(key) finds one in SCRIPT without searching. May be given more than once.
+    #[arg(long, value_name("NAME=PATH"), value_parser(lookup::parse_table))]
+    lookup: Vec<(String, String)>,
+
+    /// The key to index each --lookup table by.
+    #[arg(long, value_name("KEY"), default_value("id"), requires("lookup"))]
+    lookup_key: String,
+
+    /// Print each result wrapped in an object {"file", "line", "value"}, giving the file and line
+    /// of the input it came from, so that results merged from many inputs can be traced back.
+    #[arg(long, conflicts_with_all(["frequencies", "hist", "dups", "schema_drift", "table"]))]
+    annotate_source: bool,
+
+    /// What to do with undefined values inside results printed as JSON, YAML, or TOML: omit their
+    /// keys, as JSON.stringify does, print them as null, or fail, so that nothing is left out by
+    /// mistake. null is always kept.
+    #[arg(
+        long,
+        value_name("POLICY"),
+        default_value("omit"),
+        hide_possible_values(true)
+    )]
+    undefined: Undefined,
+
+    /// Exit with status 1 if the last result is false, null, or undefined.
+    #[arg(short('e'), long, conflicts_with("watch"))]
+    exit_status: bool,
+
+    /// Validate each input against the JSON Schema in FILE, reporting every violation by its JSON
+    /// Pointer, and fail if there are any.
+    #[arg(long, value_name("FILE"))]
+    schema: Option<String>,
+
+    /// Validate each result against --schema, rather than each input.
+    #[arg(long, requires("schema"))]
+    validate_output: bool,
+
+    /// The JavaScript to be evaluated [default: $]
+    script: Option<String>,
+
+    /// Evaluate SCRIPT once for each of FILES instead of reading STDIN. FILES may be HTTP(S) URLs.
+    files: Vec<String>,
+
+    /// Evaluate SCRIPT once for each JSON value in a stream, such as NDJSON, or with --pointer, for
+    /// each element of the array at POINTER.
+    #[arg(short('s'), long, conflicts_with_all(["yaml_in", "toml_in"]))]
+    stream: bool,
+
+    /// Read input in ENCODING, e.g. utf-16le or latin1, rather than detecting it.
+    #[arg(long, value_name("ENCODING"), value_parser(encoding::parse))]
+    input_encoding: Option<&'static encoding_rs::Encoding>,
+
+    /// What to do with invalid UTF-8 in input: fail, replace it with U+FFFD, or escape it as \xNN.
+    #[arg(long, value_name("MODE"), default_value("error"))]
+    invalid_utf8: InvalidUtf8,
+
+    /// Read FILES through a buffer, rather than mapping large ones in to memory, e.g. for files
+    /// which are still being written to.
+    #[arg(long)]
+    no_mmap: bool,
+
+    /// Only evaluate SCRIPT for streamed values whose KEY is VALUE. If a file has been indexed by
+    /// KEY with --index, just the matching records are read.
+    #[arg(
+        long("where"),
+        value_name("KEY=VALUE"),
+        value_parser(index::parse_condition),
+        requires("stream"),
+        conflicts_with("pointer")
+    )]
+    condition: Option<(String, String)>,
+
+    /// Evaluate SCRIPT once for each batch of N streamed values, as an array, rather than once for
+    /// each value, so that setup such as a request can be shared between them. Batches don't span
+    /// FILES.
+    #[arg(long, value_name("N"), requires("stream"))]
+    batch: Option<NonZeroUsize>,
+
+    /// Index each of FILES, which must be NDJSON, by KEY, for --where, instead of evaluating
+    /// SCRIPT. All positional arguments are then treated as FILES.
+    #[arg(long, value_name("KEY"))]
+    index: Option<String>,
+
+    /// Run the tests in each of FILES instead of evaluating SCRIPT, and report which fail and why.
+    /// FILES are TOML, each with an array of tests, which give an input, a script or a file to
+    /// read it from, and the result expected. All positional arguments are then treated as FILES.
+    #[arg(long, conflicts_with_all(["index", "watch", "lint"]))]
+    test: bool,
+
+    /// Parse only the value at the JSON Pointer POINTER in the input, e.g. /data/items.
+    #[arg(long, conflicts_with_all(["yaml_in", "toml_in"]))]
+    pointer: Option<String>,
+
+    /// Instead of printing results, print how often each distinct value of the function FN occurs
+    /// over them, most frequent first.
+    #[arg(long, value_name("FN"), conflicts_with_all(["hist", "dups", "schema_drift", "table"]))]
+    frequencies: Option<String>,
+
+    /// Instead of printing results, print a histogram of the numeric values of the function FN
+    /// over them.
+    #[arg(long, value_name("FN"), conflicts_with_all(["frequencies", "dups", "schema_drift", "table"]))]
+    hist: Option<String>,
+
+    /// The number of buckets to split the histogram in to.
+    #[arg(long, value_name("N"), default_value("10"), requires("hist"))]
+    buckets: NonZeroUsize,
+
+    /// Instead of printing results, print each value of the function FN shared by more than one
+    /// of them, with a count and the first result to share it.
+    #[arg(long, value_name("FN"), conflicts_with_all(["frequencies", "hist", "schema_drift", "table"]))]
+    dups: Option<String>,
+
+    /// Instead of printing results, print how each differs from the shape of the majority: the
+    /// paths they contain and the types at those paths.
+    #[arg(long, conflicts_with_all(["frequencies", "hist", "dups", "table"]))]
+    schema_drift: bool,
+
+    /// Instead of printing results as they come, print them all at the end as a table, with a
+    /// column for each key of the objects among them. Arrays of numbers are drawn as sparklines.
+    #[arg(long, conflicts_with_all(["frequencies", "hist", "dups", "schema_drift"]))]
+    table: bool,
+
+    /// Instead of printing results as they come, print them all at the end as NDJSON, with sorted
+    /// keys and canonical numbers, ordered by the function FN of each, or by the whole result if FN
+    /// isn't given, so that two exports of the same data can be compared with diff.
+    #[arg(
+        long,
+        value_name("FN"),
+        num_args(0..=1),
+        require_equals(true),
+        default_missing_value("x => x"),
+        conflicts_with_all(["frequencies", "hist", "dups", "schema_drift", "table", "annotate_source"]),
+    )]
+    normalize: Option<String>,
+
+    /// Instead of printing results, fold them in to an accumulator with the function FN, called as
+    /// FN(acc, result), starting from --init, and print the accumulator at the end. Only the
+    /// accumulator is kept, however many results there are.
+    #[arg(
+        long,
+        value_name("FN"),
+        conflicts_with_all([
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "jobs",
+            "annotate_source",
+        ]),
+    )]
+    reduce: Option<String>,
+
+    /// The JSON value --reduce starts from.
+    #[arg(
+        long("init"),
+        value_name("JSON"),
+        default_value("null"),
+        requires("reduce")
+    )]
+    initial: String,
+
+    /// Read SCRIPT from FILE. May be given more than once, to evaluate each file in turn, with the
+    /// result of one as $ in the next. All positional arguments are then treated as FILES.
+    #[arg(short('f'), long)]
+    file: Vec<String>,
+
+    /// Evaluate STEP for the result of SCRIPT, with it as $, and print the result of that instead,
+    /// as jq's a | b does. May be given more than once, to chain steps in to a pipeline.
+    #[arg(long, value_name("STEP"))]
+    then: Vec<String>,
+
+    /// Evaluate the WASI module FILE instead of SCRIPT, passing it each input as JSON on STDIN and
+    /// reading the result as JSON from STDOUT. All positional arguments are then treated as FILES.
+    #[cfg(feature = "wasm")]
+    #[arg(
+        long,
+        value_name("FILE"),
+        conflicts_with_all([
+            "file",
+            "then",
+            "jobs",
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "reduce",
+            "lint",
+        ]),
+    )]
+    wasm: Option<String>,
+
+    /// The language SCRIPT is written in. In Lua, input is available as _ rather than $.
+    #[cfg(feature = "lua")]
+    #[arg(long, value_name("LANG"), default_value("js"))]
+    lang: lua::Lang,
+
+    /// Send an additional header when fetching FILES from HTTP(S) URLs.
+    #[arg(short('H'), long, value_name("NAME: VALUE"))]
+    header: Vec<String>,
+
+    /// Print a diff of how each file `write` or --split would change, instead of writing it.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Evaluate SCRIPT without side effects, for scripts which aren't trusted: it can't read or
+    /// write files, make requests, run programs, or see the environment, and input and loops are
+    /// limited. Also turned on by `JSQ_SAFE=1`.
+    #[arg(long)]
+    safe: bool,
+
+    /// Stop SCRIPT reading or writing files, with `read`, `write`, `require`, or `import`.
+    #[arg(long)]
+    no_fs: bool,
+
+    /// Stop SCRIPT making requests with `fetch`.
+    #[arg(long)]
+    no_net: bool,
+
+    /// Hide the environment from SCRIPT, including /proc/self/environ.
+    #[arg(long)]
+    no_env: bool,
+
+    /// Fail once fetch in SCRIPT has made N requests, counting every evaluation.
+    #[arg(long, value_name("N"))]
+    max_requests: Option<usize>,
+
+    /// Fail once fetch in SCRIPT has spent SECONDS waiting on requests, counting every evaluation.
+    #[arg(long, value_name("SECONDS"))]
+    max_network_time: Option<f64>,
+
+    /// Fail if any one evaluation of SCRIPT takes longer than DURATION, e.g. 5s or 500ms.
+    #[arg(long, value_name("DURATION"), value_parser(limit::parse_duration))]
+    timeout: Option<Duration>,
+
+    /// Fail once the run has used more than SIZE of memory, e.g. 256M or 1G. Only on Linux.
+    #[arg(long, value_name("SIZE"), value_parser(limit::parse_size))]
+    max_memory: Option<u64>,
+
+    /// Warn, on STDERR, once the run has used more than MIB mebibytes of memory. Only on Linux.
+    #[arg(long, value_name("MIB"))]
+    warn_memory: Option<u64>,
+
+    /// Warn, on STDERR, once results add up to more than BYTES bytes of JSON.
+    #[arg(long, value_name("BYTES"))]
+    warn_output: Option<u64>,
+
+    /// Warn, on STDERR, about each input which takes more than SECONDS to evaluate.
+    #[arg(long, value_name("SECONDS"), conflicts_with("jobs"))]
+    warn_record_time: Option<f64>,
+
+    /// Annotate printed values with their types. Only when printing to a terminal, as with
+    /// --counts.
+    #[arg(long)]
+    types: bool,
+
+    /// Annotate arrays and objects printed as JSON or YAML with how many children they have,
+    /// aligned to the right edge of the terminal. Only when printing to a terminal, so the counts
+    /// never end up in a file or another program's input.
+    #[arg(long)]
+    counts: bool,
+
+    /// Print arrays and objects more than N levels deep as […] and {…} when printing as JSON or
+    /// YAML.
+    #[arg(long, value_name("N"))]
+    depth: Option<usize>,
+
+    /// Cut strings longer than LEN characters short with … when printing as JSON, YAML, or TOML.
+    #[arg(long, value_name("LEN"))]
+    max_string: Option<usize>,
+
+    /// Print only the first N elements of a result which is an array.
+    #[arg(long, visible_alias("limit"), value_name("N"), conflicts_with("tail"))]
+    head: Option<usize>,
+
+    /// Print only the last N elements of a result which is an array.
+    #[arg(long, value_name("N"))]
+    tail: Option<usize>,
+
+    /// Highlight keys and values which match REGEX when printing as JSON or YAML.
+    #[arg(long, value_name("REGEX"))]
+    grep: Option<String>,
+
+    /// Indent JSON and YAML by N spaces.
+    #[arg(long, value_name("N"))]
+    indent: Option<usize>,
+
+    /// Indent JSON with tabs.
+    #[arg(long, conflicts_with_all(["indent", "yaml_out", "yaml_docs"]))]
+    tab: bool,
+
+    /// Print the fields named in FILE as dates, integers, or inline when printing as YAML or TOML.
+    /// FILE is an object from field names or JSON Pointers, in which * stands for any key or
+    /// index, to date, integer, or inline, or a JSON Schema, in which values with a date or time
+    /// format are dates, and those of type integer are integers.
+    #[arg(long, value_name("FILE"))]
+    type_hints: Option<String>,
+
+    /// Sort object keys when printing as JSON, YAML, or TOML.
+    #[arg(long)]
+    sort_keys: bool,
+
+    /// When to print in color. auto prints in color to a terminal, or anywhere if `CLICOLOR_FORCE`
+    /// is set, but never if `NO_COLOR` is set.
+    #[arg(long, value_name("WHEN"), default_value("auto"))]
+    color: ColorWhen,
+
+    /// Escape non-ASCII characters when printing as JSON, YAML, or TOML.
+    #[arg(long)]
+    ascii_output: bool,
+
+    /// End output lines with CRLF rather than LF.
+    #[arg(long)]
+    crlf: bool,
+
+    /// Set a format specific printing option, e.g. yaml.indent=4.
+    #[arg(long, value_name("FORMAT.KEY=VALUE"))]
+    opt: Vec<String>,
+
+    /// Evaluate SCRIPT on N threads at once, each with its own runtime. Results are still printed
+    /// in order.
+    #[arg(long, value_name("N"), default_value("1"))]
+    jobs: NonZeroUsize,
+
+    /// Check SCRIPT for common mistakes, such as = in place of ===, instead of evaluating it.
+    #[arg(long)]
+    lint: bool,
+
+    /// Check that SCRIPT parses, without evaluating it or reading any input, and exit with status
+    /// 1 if it doesn't.
+    #[arg(long, conflicts_with_all(["lint", "watch", "test", "index"]))]
+    check: bool,
+
+    /// Print the version of jsq and what it supports as JSON, for tools which wrap it.
+    #[arg(long)]
+    info: bool,
+
+    /// Replace jsq with the latest release from GitHub, after checking it against its checksum
+    /// (which catches a corrupt download, but not a tampered release).
+    #[cfg(feature = "self-update")]
+    #[arg(long)]
+    self_update: bool,
+
+    /// Report the time spent evaluating SCRIPT, and in each other stage, and peak memory use, to
+    /// STDERR.
+    #[arg(long, visible_alias("timing"))]
+    profile: bool,
+
+    /// Evaluate SCRIPT again whenever any of FILES change.
+    #[arg(short('w'), long)]
+    watch: bool,
+
+    /// Explore the result interactively as a tree of foldable arrays and objects.
+    #[arg(
+        short('i'),
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "seq",
+            "yaml_docs",
+            "jcs",
+            "no_out",
+            "watch",
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "reduce",
+            "to",
+        ])
+    )]
+    interactive: bool,
+
+    /// Pick paths in to the result from a list narrowed down by fuzzy search, and print them as
+    /// they would be written in SCRIPT.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "seq",
+            "yaml_docs",
+            "jcs",
+            "no_out",
+            "watch",
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "reduce",
+            "to",
+            "interactive",
+        ])
+    )]
+    pick: bool,
+
+    /// Compare the result for A with the result for B, and print where they differ. A and B may be
+    /// in different formats, which are told apart by their extensions.
+    #[arg(
+        long,
+        num_args(2),
+        value_names(["A", "B"]),
+        conflicts_with_all([
+            "json_out",
+            "yaml_out",
+            "toml_out",
+            "gron_out",
+            "ini_out",
+            "env_out",
+            "plist_out",
+            "seq",
+            "yaml_docs",
+            "jcs",
+            "no_out",
+            "stream",
+            "pointer",
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "reduce",
+            "to",
+            "interactive",
+            "pick",
+            "annotate_source",
+        ])
+    )]
+    diff: Vec<String>,
+
+    /// Deep-merge the results, later ones taking precedence, and print the merged result instead.
+    /// FILES may be in different formats, which are told apart by their extensions.
+    #[arg(
+        long,
+        conflicts_with_all([
+            "seq",
+            "yaml_docs",
+            "jcs",
+            "frequencies",
+            "hist",
+            "dups",
+            "schema_drift",
+            "table",
+            "normalize",
+            "reduce",
+            "to",
+            "interactive",
+            "pick",
+            "diff",
+            "annotate_source",
+        ])
+    )]
+    merge: bool,
+
+    /// How --merge merges arrays: replace them, concat them, or by-key:KEY to merge objects with
+    /// the same value at KEY.
+    #[arg(
+        long,
+        value_name("STRATEGY"),
+        default_value("replace"),
+        value_parser(merge::parse_arrays),
+        requires("merge")
+    )]
+    merge_arrays: merge::Arrays,
+
+    /// Don't evaluate the init file, ~/.config/jsq/init.js, before SCRIPT.
+    #[arg(long)]
+    no_init: bool,
+
+    /// Don't send output longer than the screen through $PAGER.
+    #[arg(long)]
+    no_pager: bool,
+
+    /// Clear the screen before each evaluation.
+    #[arg(long, requires("watch"))]
+    clear: bool,
+
+    /// Print the result in full the first time, and then only how it has changed, rather than all
+    /// of it, each time SCRIPT is evaluated again.
+    #[arg(long, requires("watch"), conflicts_with_all(["interactive", "pick", "diff", "merge"]))]
+    changes: bool,
+}
+
+impl Args {
+    /// The WASI module to evaluate instead of SCRIPT, if any.
+    #[cfg_attr(not(feature = "wasm"), expect(clippy::unused_self))]
+    fn transform(&self) -> Option<&str> {
+        #[cfg(feature = "wasm")]
+        return self.wasm.as_deref();
+        #[cfg(not(feature = "wasm"))]
+        None
+    }
+
+    /// Whether results are printed as JSON, or in a format which is printed from JSON.
+    fn stringify(&self) -> bool {
+        self.annotate_source
+            || self.json_out
+            || self.yaml_out
+            || self.toml_out
+            || self.gron_out
+            || self.ini_out
+            || self.env_out
+            || self.plist_out
+            || self.seq
+            || self.yaml_docs
+            || self.jcs
+            || self.frequencies.is_some()
+            || self.hist.is_some()
+            || self.dups.is_some()
+            || self.schema_drift
+            || self.table
+            || self.normalize.is_some()
+            || self.reduce.is_some()
+            || self.interactive
+            || self.pick
+            || self.validate_output
+            || !self.diff.is_empty()
+            || self.merge
+            || self.changes
+            || self.split
+            || self.split_by.is_some()
+            || self.auto_out.is_some()
+    }
+
+    /// Whether results are stringified only so that they can be written to --to files, or so that
+    /// --head or --tail can take elements from them, and so are printed as text all the same.
+    fn text_from_json(&self) -> bool {
+        !self.stringify() && (!self.to.is_empty() || self.head.is_some() || self.tail.is_some())
+    }
+
+    /// Whether SCRIPT is Lua rather than JavaScript.
+    #[cfg_attr(not(feature = "lua"), expect(clippy::unused_self))]
+    fn lua(&self) -> bool {
+        #[cfg(feature = "lua")]
+        return matches!(self.lang, lua::Lang::Lua);
+        #[cfg(not(feature = "lua"))]
+        false
+    }
+}
+
+enum Evaluator {
+    Runtime(Box<Runtime>),
+    Pool(Pool),
+    #[cfg(feature = "wasm")]
+    Wasm(wasm::Transform),
+    #[cfg(feature = "lua")]
+    Lua(lua::Runtime),
+}
+
+struct Jsq {
+    args: Args,
+    print_options: print::Options,
+    /// How results are printed to --to and --split files.
+    file_options: print::Options,
+    task: Task,
+    evaluator: Evaluator,
+    aggregate: Option<Aggregate>,
+    /// Files to print each result to as well as STDOUT.
+    outputs: Vec<(Format, NoColor<compress::Writer>)>,
+    /// Files written to by --split or --split-by so far, by path, each with what would have been
+    /// written to it if --dry-run is set.
+    splits: BTreeMap<String, (Format, Vec<u8>)>,
+    /// How many results have been printed to `outputs`, for --manifest.
+    records: usize,
+    /// Results to be explored, or compared with the last evaluation, once evaluation is complete.
+    results: Vec<String>,
+    /// The result of the last evaluation, for --changes.
+    previous: Option<serde_json::Value>,
+    /// Whether the last result printed was false, null, or undefined.
+    falsy: bool,
+    /// The file and line of each input evaluated but not yet printed, for --annotate-source.
+    sources: VecDeque<(Option<String>, Option<usize>)>,
+    schema: Option<Schema>,
+    /// Streamed values waiting to be evaluated together, each with the line it's on, for --batch.
+    batch: Vec<(usize, String)>,
+}
+
+impl Jsq {
+    fn new(args: Args, print_options: print::Options, steps: Vec<Step>) -> Result<Self> {
+        let task = Task {
+            steps,
+            aggregate: args
+                .frequencies
+                .clone()
+                .or_else(|| args.hist.clone())
+                .or_else(|| args.dups.clone())
+                // Shapes and tables are of the results themselves.
+                .or_else(|| (args.schema_drift || args.table).then(|| String::from("x => x")))
+                .or_else(|| args.normalize.clone())
+                .map(path::function),
+        };
+        let evaluator = Self::evaluator(&args, &task)?;
+        let aggregate = Self::aggregate(&args);
+        let outputs = Self::outputs(&args)?;
+        let schema = args.schema.as_deref().map(Schema::load).transpose()?;
+        Ok(Self {
+            args,
+            file_options: print_options.for_files(),
+            print_options,
+            task,
+            evaluator,
+            aggregate,
+            outputs,
+            splits: BTreeMap::new(),
+            records: 0,
+            results: Vec::new(),
+            previous: None,
+            falsy: false,
+            sources: VecDeque::new(),
+            schema,
+            batch: Vec::new(),
+        })
+    }
+
+    fn evaluator(args: &Args, task: &Task) -> Result<Evaluator> {
+        let parse = args.json_in
+            || args.yaml_in
+            || args.toml_in
+            || args.gron_in
+            || args.ini_in
+            || args.env_in
+            || args.plist_in
+            || args.from.is_some()
+            || args.stream
+            || args.pointer.is_some()
+            || !args.diff.is_empty()
+            || args.merge;
+        let stringify = args.stringify() || args.text_from_json();
+
+        #[cfg(feature = "wasm")]
+        if let Some(path) = args.transform() {
+            return Ok(Evaluator::Wasm(wasm::Transform::new(path)?));
+        }
+
+        let vars = Self::vars(args)?;
+        let lookups = Self::lookups(args)?;
+
+        #[cfg(feature = "lua")]
+        if args.lua() {
+            ensure!(
+                args.split_by.is_none(),
+                "--split-by needs SCRIPT to be JavaScript"
+            );
+            ensure!(
+                args.jobs.get() == 1
+                    && task.aggregate.is_none()
+                    && args.reduce.is_none()
+                    && task.steps.len() == 1,
+                "--lang lua doesn't support --jobs, pipelines, or aggregating with a function"
+            );
+            return Ok(Evaluator::Lua(lua::Runtime::new(
+                safe::env(),
+                vars,
+                lookups,
+                parse,
+                stringify,
+            )?));
+        }
+
+        let init = Self::init(args)?;
+
+        Ok(if args.jobs.get() > 1 {
+            Evaluator::Pool(Pool::new(
+                args.jobs.get(),
+                task,
+                &vars,
+                &lookups,
+                init.as_ref(),
+                parse,
+                stringify,
+                args.undefined,
+            ))
+        } else {
+            Evaluator::Runtime(Box::new(Runtime::new(Options {
+                env: safe::env(),
+                vars,
+                lookups,
+                init,
+                parse,
+                stringify,
+                undefined: args.undefined,
+            })?))
+        })
+    }
+
+    /// The path and contents of the init file, `$XDG_CONFIG_HOME/jsq/init.js` or
+    /// `~/.config/jsq/init.js`, if there is one and it isn't disabled with --no-init.
+    fn init(args: &Args) -> Result<Option<(String, String)>> {
+        if args.no_init {
+            return Ok(None);
+        }
+        let config = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(config) if !config.is_empty() => PathBuf::from(config),
+            _ => match std::env::home_dir() {
+                Some(home) => home.join(".config"),
+                None => return Ok(None),
+            },
+        };
+        let path = config.join("jsq").join("init.js");
+        match std::fs::read_to_string(&path) {
+            Ok(init) => Ok(Some((path.display().to_string(), init))),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// The variables given with --arg and --argjson, each as a name and a JSON value.
+    fn vars(args: &Args) -> Result<Vec<(String, String)>> {
+        let mut vars = Vec::new();
+        for pair in args.arg.chunks(2) {
+            vars.push((pair[0].clone(), serde_json::to_string(&pair[1])?));
+        }
+        for pair in args.argjson.chunks(2) {
+            let json =
+                parse::json(&pair[1]).with_context(|| format!("parsing --argjson {}", pair[0]))?;
+            vars.push((pair[0].clone(), json));
+        }
+        Ok(vars)
+    }
+
+    /// The tables given with --lookup, each as a name and a JSON object of records by key.
+    fn lookups(args: &Args) -> Result<Vec<(String, String)>> {
+        args.lookup
+            .iter()
+            .map(|(name, path)| Ok((name.clone(), lookup::load(name, path, &args.lookup_key)?)))
+            .collect()
+    }
+
+    fn aggregate(args: &Args) -> Option<Aggregate> {
+        if args.frequencies.is_some() {
+            Some(Aggregate::Frequencies(Frequencies::default()))
+        } else if args.hist.is_some() {
+            Some(Aggregate::Histogram(Histogram::default()))
+        } else if args.dups.is_some() {
+            Some(Aggregate::Duplicates(Duplicates::default()))
+        } else if args.schema_drift {
+            Some(Aggregate::Shapes(Shapes::default()))
+        } else if args.table {
+            Some(Aggregate::Table(Table::default()))
+        } else if args.normalize.is_some() {
+            Some(Aggregate::Normalized(Normalized::default()))
+        } else {
+            None
+        }
+    }
+
+    fn outputs(args: &Args) -> Result<Vec<(Format, NoColor<compress::Writer>)>> {
+        args.to
+            .iter()
+            .map(|(format, file)| {
+                let compression = args.compress.or_else(|| Compression::detect(file));
+                let w = compress::Writer::create(file, compression, args.append)?;
+                Ok((format.clone(), NoColor::new(w)))
+            })
+            .collect()
+    }
+
+    /// Start afresh, with a new runtime, nothing aggregated, and empty output files.
+    fn reset(&mut self) -> Result<()> {
+        self.evaluator = Self::evaluator(&self.args, &self.task)?;
+        self.aggregate = Self::aggregate(&self.args);
+        self.outputs = Self::outputs(&self.args)?;
+        self.splits.clear();
+        self.records = 0;
+        self.results.clear();
+        self.sources.clear();
+        self.batch.clear();
+        self.schema = self.args.schema.as_deref().map(Schema::load).transpose()?;
+        http::reset();
+        soft::reset();
+        Ok(())
+    }
+
+    /// Wrap each of `results` with the file and line of the input they came from, if
+    /// --annotate-source is set. Results are printed in the order inputs were evaluated, so it's the
+    /// oldest source.
+    fn annotate(&mut self, results: Vec<String>) -> Result<Vec<String>> {
+        if !self.args.annotate_source {
+            return Ok(results);
+        }
+        let (file, line) = self.sources.pop_front().context("annotating source")?;
+        results
+            .into_iter()
+            .map(|res| {
+                if res == "undefined" {
+                    return Ok(res);
+                }
+                let value = serde_json::from_str::<serde_json::Value>(&res)?;
+                Ok(serde_json::json!({ "file": file, "line": line, "value": value }).to_string())
+            })
+            .collect()
+    }
+
+    /// Check `json`, the input or a result, against --schema, printing each violation, and fail if
+    /// there are any.
+    fn validate(&self, what: &str, json: &str) -> Result<()> {
+        let Some(schema) = &self.schema else {
+            return Ok(());
+        };
+        let value = serde_json::from_str(&parse::json(json)?)?;
+        let violations = schema.validate(&value);
+        let mut stderr = print::stderr();
+        for violation in &violations {
+            let pointer = if violation.pointer.is_empty() {
+                "the root"
+            } else {
+                &violation.pointer
+            };
+            let message = format!("{} at {pointer}", violation.message);
+            print::error(&mut stderr, &Diagnostic::new(Kind::Schema, message).into())
+                .context("printing error")?;
+        }
+        ensure!(
+            violations.is_empty(),
+            "{what} doesn't match {}",
+            schema.path()
+        );
+        Ok(())
+    }
+
+    fn print(&mut self, res: &str) -> Result<()> {
+        self.falsy = matches!(res, "false" | "null" | "undefined");
+
+        if res != "undefined" {
+            soft::output(res.len() + 1)?;
+        }
+
+        if self.args.validate_output && res != "undefined" {
+            self.validate("result", res)?;
+        }
+
+        if res != "undefined" && !self.outputs.is_empty() {
+            for (format, w) in &mut self.outputs {
+                print::format(w, &self.file_options, format, res)?;
+            }
+            self.records += 1;
+        }
+
+        if self.args.split || self.args.split_by.is_some() {
+            return self.split(res);
+        }
+
+        if self.args.no_out {
+            return Ok(());
+        }
+
+        if self.args.interactive
+            || self.args.pick
+            || !self.args.diff.is_empty()
+            || self.args.merge
+            || self.args.changes
+        {
+            if res != "undefined" {
+                self.results.push(res.to_owned());
+            }
+            return Ok(());
+        }
+
+        self.write(res)
+    }
+
+    /// Write each value of `res`, an object of paths to values, to the file at its path, for
+    /// --split and --split-by. A file written to more than once in a run is added to each time.
+    /// Each file is closed once it's written to, so that any number of them can be written.
+    fn split(&mut self, res: &str) -> Result<()> {
+        if res == "undefined" {
+            return Ok(());
+        }
+        let serde_json::Value::Object(files) = serde_json::from_str(res)? else {
+            bail!("--split needs each result to be an object of paths to values");
+        };
+        for (path, value) in files {
+            let path = split_path(&self.args, &path)?;
+            let append = self.splits.contains_key(&path);
+            let (format, preview) = self.splits.entry(path.clone()).or_insert_with(|| {
+                let format = output_format(&self.args)
+                    .or_else(|| detect::from_extension(&path))
+                    .unwrap_or(Format::Json);
+                (format, Vec::new())
+            });
+            let value = value.to_string();
+            if edit::dry_run() {
+                // Shown as a diff once every result has been written.
+                print::format(
+                    &mut NoColor::new(preview),
+                    &self.file_options,
+                    format,
+                    &value,
+                )
+                .with_context(|| format!("writing {path}"))?;
+                continue;
+            }
+            if let Some(dir) = Path::new(&path).parent() {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("creating {}", dir.display()))?;
+            }
+            let mut w = NoColor::new(compress::Writer::create(
+                &path,
+                Compression::detect(&path),
+                append,
+            )?);
+            print::format(&mut w, &self.file_options, format, &value)
+                .with_context(|| format!("writing {path}"))?;
+            w.into_inner().finish()?;
+        }
+        Ok(())
+    }
+
+    /// Print what --reduce has folded the results in to.
+    fn print_reduced(&mut self) -> Result<()> {
+        let Evaluator::Runtime(runtime) = &mut self.evaluator else {
+            bail!("--reduce needs SCRIPT to be JavaScript");
+        };
+        let res = runtime.reduced(&self.args.initial)?;
+        self.falsy = matches!(res.as_str(), "false" | "null");
+        self.write(&res)
+    }
+
+    /// Print how the results have changed since the last evaluation, or the results themselves if
+    /// this is the first. Several results are compared as a single array.
+    fn print_changes(&mut self) -> Result<()> {
+        let results = std::mem::take(&mut self.results);
+        let mut values = results
+            .iter()
+            .map(|res| serde_json::from_str(res))
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            serde_json::Value::Array(values)
+        };
+        if let Some(previous) = &self.previous {
+            let changes = diff::diff(previous, &value);
+            print::diff(&mut print::stdout(), &changes).context("printing changes")?;
+        } else {
+            for res in &results {
+                self.write(res)?;
+            }
+        }
+        self.previous = Some(value);
+        Ok(())
+    }
+
+    /// Print a result to STDOUT in the format asked for.
+    fn write(&self, res: &str) -> Result<()> {
+        let mut stdout = print::stdout();
+        // undefined is a valid output of JSON.stringify
+        if self.args.json_out && res != "undefined" {
+            print::json(&mut stdout, &self.print_options, res).context("printing JSON")?;
+        } else if self.args.yaml_out && res != "undefined" {
+            print::yaml(&mut stdout, &self.print_options, res).context("printing YAML")?;
+        } else if self.args.toml_out && res != "undefined" {
+            print::toml(&mut stdout, &self.print_options, res).context("printing TOML")?;
+        } else if self.args.gron_out && res != "undefined" {
+            print::gron(&mut stdout, &self.print_options, res).context("printing gron")?;
+        } else if self.args.ini_out && res != "undefined" {
+            print::ini(&mut stdout, &self.print_options, res).context("printing INI")?;
+        } else if self.args.env_out && res != "undefined" {
+            print::env(&mut stdout, &self.print_options, res).context("printing env")?;
+        } else if self.args.plist_out && res != "undefined" {
+            print::plist(&mut stdout, &self.print_options, res).context("printing plist")?;
+        } else if self.args.seq && res != "undefined" {
+            print::json_seq(&mut stdout, &self.print_options, res)
+                .context("printing JSON text sequence")?;
+        } else if self.args.yaml_docs && res != "undefined" {
+            print::yaml_docs(&mut stdout, &self.print_options, res)
+                .context("printing YAML documents")?;
+        } else if self.args.jcs && res != "undefined" {
+            print::jcs(&mut stdout, &self.print_options, res).context("printing canonical JSON")?;
+        } else if let Some(format) = &self.args.auto_out
+            && res != "undefined"
+        {
+            print::format(&mut stdout, &self.print_options, format, res)?;
+        } else if self.args.text_from_json() && res != "undefined" {
+            print::json_as_text(&self.print_options, res)?;
+        } else {
+            print::text(res)?;
+        }
+        // Each result is written in full before the next is evaluated.
+        stdout.flush()?;
+
+        Ok(())
+    }
+
+    fn emit(&mut self, output: Output) -> Result<()> {
+        match output {
+            Output::Print(results) if self.args.reduce.is_some() => {
+                profile::time(Stage::Aggregate, || {
+                    let f = self.args.reduce.as_deref().context("reducing")?;
+                    let Evaluator::Runtime(runtime) = &mut self.evaluator else {
+                        bail!("--reduce needs SCRIPT to be JavaScript");
+                    };
+                    for res in results.iter().filter(|res| *res != "undefined") {
+                        runtime.reduce(f, &self.args.initial, res)?;
+                    }
+                    Ok(())
+                })
+            }
+            Output::Print(results) => profile::time(Stage::Print, || {
+                for res in self.annotate(results)? {
+                    self.print(&res)?;
+                }
+                Ok(())
+            }),
+            Output::Aggregate(values) => profile::time(Stage::Aggregate, || {
+                let aggregate = self.aggregate.as_mut().context("aggregating")?;
+                for (key, value) in values {
+                    aggregate.add(key, value)?;
+                }
+                Ok(())
+            }),
+        }
+    }
+
+    /// Evaluate SCRIPT for `input`, read from `filename` at `line` if it was streamed.
+    fn eval(&mut self, filename: Option<&str>, line: Option<usize>, input: String) -> Result<()> {
+        if self.args.annotate_source {
+            self.sources.push_back((filename.map(String::from), line));
+        }
+        if !self.args.validate_output {
+            pool::label(filename, self.validate("input", &input))?;
+        }
+        let start = Instant::now();
+        match &mut self.evaluator {
+            Evaluator::Runtime(runtime) => {
+                let output = self.task.eval(runtime, filename, &input)?;
+                self.emit(output)?;
+            }
+            #[cfg(feature = "wasm")]
+            Evaluator::Wasm(transform) => {
+                let evaluation = limit::evaluation();
+                let res = profile::time(Stage::Script, || transform.eval(&input));
+                drop(evaluation);
+                self.emit(Output::Print(vec![pool::label(filename, res)?]))?;
+            }
+            #[cfg(feature = "lua")]
+            Evaluator::Lua(runtime) => {
+                let res = runtime.eval(&self.task.steps[0].script, &input, filename);
+                self.emit(Output::Print(vec![pool::label(filename, res)?]))?;
+            }
+            Evaluator::Pool(pool) => {
+                pool.submit(filename, input)?;
+                let ready = std::iter::from_fn(|| pool.try_recv()).collect::<Vec<_>>();
+                for output in ready {
+                    self.emit(output?)?;
+                }
+                // Inputs are evaluated on other threads, so how long each takes isn't known here.
+                return soft::record(None);
+            }
+        }
+        soft::record(Some(start.elapsed()))
+    }
+
+    /// Evaluate SCRIPT for the streamed `value`, on `line`, or if --batch is set, add it to the
+    /// batch, and evaluate SCRIPT for the batch once it's full.
+    fn eval_streamed(&mut self, filename: Option<&str>, line: usize, value: String) -> Result<()> {
+        let Some(size) = self.args.batch else {
+            return self.eval(filename, Some(line), value);
+        };
+        self.batch.push((line, value));
+        if self.batch.len() == size.get() {
+            self.eval_batch(filename)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate SCRIPT for what's left in the batch, as an array, from the line of its first value.
+    fn eval_batch(&mut self, filename: Option<&str>) -> Result<()> {
+        let batch = std::mem::take(&mut self.batch);
+        let Some(&(line, _)) = batch.first() else {
+            return Ok(());
+        };
+        let values = batch
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        self.eval(filename, Some(line), format!("[{}]", values.join(",")))
+    }
+
+    /// Wait for any outstanding evaluations to complete.
+    fn finish(&mut self) -> Result<()> {
+        if let Evaluator::Pool(pool) = &mut self.evaluator {
+            pool.close();
+            let outputs = std::iter::from_fn(|| pool.recv()).collect::<Vec<_>>();
+            for output in outputs {
+                self.emit(output?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, filename: Option<&str>, reader: impl BufRead) -> Result<String> {
+        let mut input = String::new();
+        safe::limit(reader).read_to_string(&mut input)?;
+        self.parse_input(filename, Cow::Owned(input))
+    }
+
+    /// Parse `input` as the flags say, or as its extension says where that's what counts, to the
+    /// JSON or text SCRIPT is evaluated for.
+    fn parse_input(&self, filename: Option<&str>, input: Cow<str>) -> Result<String> {
+        safe::check_input(input.len())?;
+        let input = if input.contains("\r\n") {
+            Cow::Owned(input.replace("\r\n", "\n"))
+        } else {
+            input
+        };
+
+        // The two sides of a diff, or the layers of a merge, may be in different formats, so each
+        // is parsed according to its extension, or as JSON if the flags don't say otherwise.
+        let by_extension = !self.args.diff.is_empty() || self.args.merge;
+        let extension = filename
+            .filter(|_| by_extension)
+            .and_then(|f| Path::new(f).extension())
+            .and_then(OsStr::to_str)
+            .map(str::to_ascii_lowercase);
+        let (json_in, yaml_in, toml_in, ini_in) = match extension.as_deref() {
+            Some("json") => (true, false, false, false),
+            Some("yaml" | "yml") => (false, true, false, false),
+            Some("toml") => (false, false, true, false),
+            Some("ini") => (false, false, false, true),
+            _ => (
+                self.args.json_in
+                    || by_extension
+                        && !self.args.yaml_in
+                        && !self.args.toml_in
+                        && !self.args.gron_in
+                        && !self.args.ini_in
+                        && !self.args.env_in,
+                self.args.yaml_in,
+                self.args.toml_in,
+                self.args.ini_in,
+            ),
+        };
+
+        Ok(if json_in && self.args.lenient {
+            parse::lenient(&input, self.args.dupes)?
+        } else if json_in {
+            parse::json_with(&input, self.args.dupes)?
+        } else if yaml_in {
+            parse::yaml_with(&input, self.args.dupes)?
+        } else if toml_in {
+            parse::toml(&input)?
+        } else if self.args.gron_in {
+            parse::gron(&input)?
+        } else if ini_in {
+            parse::ini(&input)?
+        } else if self.args.env_in {
+            parse::env(&input)?
+        } else {
+            input.into_owned()
+        })
+    }
+
+    // Errors from evaluation are labelled with the filename by the task, so only errors from
+    // reading need labelling here.
+    fn process(&mut self, filename: Option<&str>, mut reader: impl BufRead) -> Result<()> {
+        if self.args.from.is_some() || self.args.plist_in {
+            // Plugins and property lists get input exactly as it was read, since it might not be
+            // text.
+            let input = profile::time(Stage::Read, || {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes)?;
+                match &self.args.from {
+                    Some(format) => plugin::decode(format, bytes),
+                    None => parse::plist(&bytes),
+                }
+            });
+            let input = pool::label(filename, input)?;
+            return self.eval(filename, None, input);
+        }
+
+        let reader = encoding::decode(
+            reader,
+            self.args.input_encoding,
+            self.args.invalid_utf8,
+            self.args.json_in || self.args.stream || self.args.pointer.is_some(),
+        );
+        let reader = pool::label(filename, reader)?;
+        if self.args.stream {
+            let mut stream = match &self.args.pointer {
+                Some(pointer) => pool::label(
+                    filename,
+                    profile::time(Stage::Read, || Stream::at(reader, pointer)),
+                )?,
+                None => Stream::new(reader),
+            };
+            while let Some(value) = profile::time(Stage::Read, || stream.next()) {
+                let value = pool::label(filename, value)?;
+                if let Some((key, v)) = &self.args.condition
+                    && lookup::key(&value, key).as_ref() != Some(v)
+                {
+                    continue;
+                }
+                let value = value.to_string();
+                self.eval_streamed(filename, stream.line(), value)?;
+            }
+            return self.eval_batch(filename);
+        }
+
+        let input = profile::time(Stage::Read, || match &self.args.pointer {
+            Some(pointer) => Stream::extract(reader, pointer).map(|value| value.to_string()),
+            None => self.read(filename, reader),
+        });
+        let input = pool::label(filename, input)?;
+        self.eval(filename, None, input)
+    }
+
+    /// Process a file which has been mapped in to memory. Unless it's streamed, and so never held in
+    /// full anyway, it's parsed from the map where it can be, rather than read in to a copy first.
+    fn process_mapped(&mut self, filename: Option<&str>, bytes: &[u8]) -> Result<()> {
+        let plain = !self.args.stream
+            && self.args.from.is_none()
+            && !self.args.plist_in
+            && self.args.pointer.is_none();
+        if plain && let Some(input) = encoding::as_utf8(bytes, self.args.input_encoding) {
+            let input = profile::time(Stage::Read, || {
+                self.parse_input(filename, Cow::Borrowed(input))
+            });
+            let input = pool::label(filename, input)?;
+            return self.eval(filename, None, input);
+        }
+        self.process(filename, bytes)
+    }
+
+    fn fetch(&self, url: &str) -> Result<String> {
+        let accept = if self.args.json_in || self.args.stream || self.args.pointer.is_some() {
+            "application/json"
+        } else if self.args.yaml_in {
+            "application/yaml"
+        } else if self.args.toml_in {
+            "application/toml"
+        } else {
+            "*/*"
+        };
+        http::get(url, accept, &self.args.header)
+    }
+
+    fn run(&mut self, files: &[String]) -> Result<()> {
+        if files.is_empty() {
+            let stdin = std::io::stdin();
+            if stdin.is_terminal() {
+                self.process(None, std::io::empty())?;
+            } else {
+                self.process(None, stdin.lock())?;
+            }
+        }
+
+        for file in files {
+            if http::is_url(file) {
+                let input = profile::time(Stage::Read, || self.fetch(file))
+                    .with_context(|| format!("reading {file}"))?;
+                self.process(Some(file), input.as_bytes())?;
+            } else if let Some((key, value)) = &self.args.condition
+                && let Some(records) =
+                    index::find(file, key, value).with_context(|| format!("reading {file}"))?
+            {
+                for (line, record) in records {
+                    let reader = encoding::decode(
+                        &record[..],
+                        self.args.input_encoding,
+                        self.args.invalid_utf8,
+                        true,
+                    );
+                    for value in Stream::new(pool::label(Some(file), reader)?) {
+                        let value = pool::label(Some(file), value)?;
+                        self.eval_streamed(Some(file), line, value.to_string())?;
+                    }
+                }
+                self.eval_batch(Some(file))?;
+            } else {
+                let f = File::open(file).with_context(|| format!("reading {file}"))?;
+                let map = if self.args.no_mmap {
+                    None
+                } else {
+                    mmap::map(&f)
+                };
+                match map {
+                    Some(map) => self.process_mapped(Some(file), &map)?,
+                    None => self.process(Some(file), BufReader::new(f))?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finish writing --to files, and with --dry-run, show how --split files would change.
+    fn finish_files(&mut self) -> Result<()> {
+        // Compressed files are only complete once finished. Resetting opens them again.
+        for (_, w) in self.outputs.drain(..) {
+            w.into_inner().finish()?;
+        }
+        for (path, (_, preview)) in std::mem::take(&mut self.splits) {
+            if edit::dry_run() {
+                edit::write(&path, &String::from_utf8_lossy(&preview))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, files: &[String]) -> Result<()> {
+        // Outputs of inputs read before any error are still emitted, in order.
+        let res = self.run(files);
+        self.finish()?;
+        self.finish_files()?;
+        res?;
+
+        if let Some(path) = &self.args.manifest {
+            let files = self
+                .args
+                .to
+                .iter()
+                .map(|(_, file)| file.clone())
+                .collect::<Vec<_>>();
+            manifest::write(path, &self.task.script(), &files, self.records)?;
+        }
+
+        profile::time(Stage::Print, || -> Result<()> {
+            match &self.aggregate {
+                Some(Aggregate::Frequencies(frequencies)) => {
+                    print::frequencies(&mut print::stdout(), &frequencies.sorted())
+                        .context("printing frequencies")?;
+                }
+                Some(Aggregate::Histogram(histogram)) => {
+                    print::histogram(&mut print::stdout(), &histogram.buckets(self.args.buckets))
+                        .context("printing histogram")?;
+                }
+                Some(Aggregate::Duplicates(duplicates)) => {
+                    print::duplicates(&mut print::stdout(), &duplicates.sorted())
+                        .context("printing duplicates")?;
+                }
+                Some(Aggregate::Shapes(shapes)) => {
+                    print::drift(&mut print::stdout(), &shapes.drift())
+                        .context("printing drift")?;
+                }
+                Some(Aggregate::Table(table)) => {
+                    let (columns, rows) = table.rows();
+                    print::table(&mut print::stdout(), &columns, &rows)
+                        .context("printing table")?;
+                }
+                Some(Aggregate::Normalized(normalized)) => {
+                    let mut stdout = print::stdout();
+                    for record in normalized.sorted() {
+                        print::json_line(&mut stdout, &self.print_options, record)
+                            .context("printing NDJSON")?;
+                    }
+                }
+                None => {}
+            }
+            Ok(())
+        })?;
+
+        if self.args.profile {
+            print::profile(&mut print::stderr(), &profile::profile())
+                .context("printing profile")?;
+            profile::reset();
+        }
+
+        if !self.args.diff.is_empty() {
+            let results = std::mem::take(&mut self.results);
+            ensure!(
+                results.len() == 2,
+                "--diff expects a result for each of A and B"
+            );
+            let changes = diff::diff(
+                &serde_json::from_str(&results[0])?,
+                &serde_json::from_str(&results[1])?,
+            );
+            print::diff(&mut print::stdout(), &changes).context("printing diff")?;
+            // The result is whether A and B are the same, so that like diff(1), -e exits with
+            // status 1 if they differ.
+            self.falsy = !changes.is_empty();
+        }
+
+        if self.args.merge {
+            let results = std::mem::take(&mut self.results);
+            let values = results
+                .iter()
+                .map(|res| serde_json::from_str(res))
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(merged) = merge::merge(values, &self.args.merge_arrays) {
+                let res = merged.to_string();
+                self.falsy = matches!(res.as_str(), "false" | "null");
+                self.write(&res)?;
+            }
+        }
+
+        if self.args.reduce.is_some() {
+            self.print_reduced()?;
+        }
+
+        if self.args.changes {
+            self.print_changes()?;
+        }
+
+        if self.args.interactive || self.args.pick {
+            // Explore multiple results as a single array.
+            let results = std::mem::take(&mut self.results);
+            let json = match results.len() {
+                0 => return Ok(()),
+                1 => results[0].clone(),
+                _ => format!("[{}]", results.join(",")),
+            };
+            if self.args.pick {
+                for path in explore::pick(&json)? {
+                    print::text(&path)?;
+                }
+            } else {
+                explore::explore(&json)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn print_options(args: &Args) -> Result<print::Options> {
+    let mut print_options = print::Options {
+        types: args.types && std::io::stdout().is_terminal(),
+        counts: if args.counts && std::io::stdout().is_terminal() {
+            Some(crossterm::terminal::size()?.0.into())
+        } else {
+            None
+        },
+        depth: args.depth,
+        max_string: args.max_string,
+        head: args.head,
+        tail: args.tail,
+        sort_keys: args.sort_keys,
+        ascii: args.ascii_output,
+        ..print::Options::default()
+    };
+    if let Some(indent) = args.indent {
+        print_options.json_indent = indent;
+        if args.yaml_out || args.yaml_docs || matches!(args.auto_out, Some(Format::Yaml)) {
+            print_options
+                .set(&format!("yaml.indent={indent}"))
+                .context("setting indent")?;
+        }
+    }
+    print_options.json_tabs = args.tab;
+    if let Some(re) = &args.grep {
+        print_options.grep = Some(Regex::new(re).context("parsing --grep")?);
+    }
+    if let Some(path) = &args.type_hints {
+        print_options.type_hints = Schema::load(path)?.type_hints()?;
+    }
+    for opt in &args.opt {
+        print_options
+            .set(opt)
+            .with_context(|| format!("setting option {opt}"))?;
+    }
+    Ok(print_options)
+}
+
+/// Print a warning for each common mistake in SCRIPT, and fail if there are any.
+fn lint(script: &str) -> Result<()> {
+    let warnings = lint::lint(script);
+    let mut stderr = print::stderr();
+    for warning in &warnings {
+        print::warning(&mut stderr, warning).context("printing warning")?;
+    }
+    ensure!(warnings.is_empty(), "linting SCRIPT failed");
+    Ok(())
+}
+
+/// Apply the settings in `args` which hold for the whole run: safe mode and what SCRIPT is denied,
+/// color, --dry-run, profiling, line endings, the network budget, and limits.
+fn configure(args: &Args) -> Result<()> {
+    if args.safe || std::env::var_os("JSQ_SAFE").is_some_and(|v| v == "1") {
+        safe::enable();
+    }
+    for (deny, capability) in [
+        (args.no_fs, Capability::Fs),
+        (args.no_net, Capability::Net),
+        (args.no_env, Capability::Env),
+    ] {
+        if deny {
+            safe::deny(capability);
+        }
+    }
+    // WASI modules are programs in their own right, which safe mode can't vouch for.
+    #[cfg(feature = "wasm")]
+    if args.wasm.is_some() {
+        safe::forbid("--wasm")?;
+    }
+    // SCRIPT chooses which files --split writes.
+    ensure!(
+        !(args.split || args.split_by.is_some()) || safe::allowed(Capability::Fs),
+        safe::denied(Capability::Fs, "--split")
+    );
+    if args.reduce.is_some() {
+        serde_json::from_str::<serde_json::Value>(&args.initial).context("parsing --init")?;
+    }
+    print::set_color(args.color);
+    if args.dry_run {
+        edit::enable_dry_run();
+    }
+    if args.profile {
+        profile::enable();
+    }
+    if let Ok(colors) = std::env::var("JSQ_COLORS") {
+        print::set_theme(&colors).context("parsing JSQ_COLORS")?;
+    }
+    if args.crlf {
+        print::use_crlf();
+    }
+    http::set_budget(http::Budget {
+        requests: args.max_requests,
+        time: args
+            .max_network_time
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .context("parsing --max-network-time")?,
+    });
+    limit::set_limits(limit::Limits {
+        timeout: args.timeout,
+        memory: args.max_memory,
+    });
+    soft::set_limits(soft::Limits {
+        memory: args.warn_memory.map(|mib| mib << 20),
+        output: args.warn_output,
+        record_time: args
+            .warn_record_time
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .context("parsing --warn-record-time")?,
+    });
+    Ok(())
+}
+
+/// Run the tests in each of `files`, printing how each went, and fail if any did.
+fn test(args: &Args, files: &[String]) -> Result<ExitCode> {
+    ensure!(!files.is_empty(), "--test requires FILES");
+    ensure!(!args.lua(), "--test only runs JavaScript");
+    let mut runtime = Runtime::new(Options {
+        env: safe::env(),
+        vars: Jsq::vars(args)?,
+        lookups: Jsq::lookups(args)?,
+        init: Jsq::init(args)?,
+        parse: true,
+        stringify: true,
+        undefined: args.undefined,
+    })?;
+    let mut stdout = print::stdout();
+    let (mut passed, mut failed) = (0, 0);
+    for file in files {
+        for test in suite::load(file).with_context(|| format!("loading {file}"))? {
+            let outcome = test.run(&mut runtime, file);
+            if let suite::Outcome::Pass = outcome {
+                passed += 1;
+            } else {
+                failed += 1;
+            }
+            print::outcome(&mut stdout, &format!("{file}: {}", test.name), &outcome)
+                .context("printing outcome")?;
+        }
+    }
+    writeln!(stdout, "{passed} passed, {failed} failed")?;
+    stdout.flush()?;
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// The steps of the pipeline to evaluate, from SCRIPT or the files given to --file, followed by
+/// those given to --then, and the FILES to evaluate them for.
+fn steps(args: &Args) -> Result<(Vec<Step>, Vec<String>)> {
+    let (steps, files) = if !args.file.is_empty() {
+        let files = args.script.iter().chain(&args.files).cloned().collect();
+        let steps = args
+            .file
+            .iter()
+            .map(|f| {
+                let script = std::fs::read_to_string(f)?;
+                Ok(Step {
+                    script: if args.lua() {
+                        script
+                    } else {
+                        transpile(f, script)?
+                    },
+                    file: Some(f.clone()),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        (steps, files)
+    } else if args.transform().is_some() || args.index.is_some() || args.test {
+        let files = args.script.iter().chain(&args.files).cloned().collect();
+        (vec![Step::default()], files)
+    } else {
+        let input = if args.lua() { "_" } else { "$" };
+        let script = args.script.clone().unwrap_or_else(|| String::from(input));
+        (vec![Step { script, file: None }], args.files.clone())
+    };
+
+    let steps = steps
+        .into_iter()
+        .chain(args.then.iter().map(|script| Step {
+            script: script.clone(),
+            file: None,
+        }))
+        // --split-by is --split, with a last step which puts each result at the path FN returns.
+        .chain(args.split_by.iter().map(|f| Step {
+            script: format!("({{ [String(({})($))]: $ }})", path::function(f.clone())),
+            file: None,
+        }))
+        .map(|step| Step {
+            script: if args.lua() {
+                step.script
+            } else {
+                path::script(step.script)
+            },
+            ..step
+        })
+        .collect::<Vec<_>>();
+    Ok((steps, files))
+}
+
+/// `script`, read from `file`, as JavaScript, transpiled from TypeScript if that's what it is.
+fn transpile(file: &str, script: String) -> Result<String> {
+    let extension = Path::new(file).extension().and_then(OsStr::to_str);
+    if !matches!(extension, Some("ts" | "mts" | "cts")) {
+        return Ok(script);
+    }
+    #[cfg(feature = "typescript")]
+    return typescript::transpile(&script).map_err(|err| diagnostic::from_file(err, file));
+    #[cfg(not(feature = "typescript"))]
+    bail!("{file} is TypeScript, which needs jsq to be built with --features typescript");
+}
+
+/// The format results are printed in, if it's one of those which can be written to a file.
+fn output_format(args: &Args) -> Option<Format> {
+    [
+        (args.json_out, Format::Json),
+        (args.yaml_out, Format::Yaml),
+        (args.toml_out, Format::Toml),
+        (args.gron_out, Format::Gron),
+        (args.ini_out, Format::Ini),
+        (args.env_out, Format::Env),
+        (args.plist_out, Format::Plist),
+        (args.seq, Format::JsonSeq),
+        (args.yaml_docs, Format::YamlDocs),
+        (args.jcs, Format::Jcs),
+    ]
+    .into_iter()
+    .find_map(|(set, format)| set.then_some(format))
+    .or_else(|| args.auto_out.clone())
+}
+
+/// Where to write the --split file at `path`, as SCRIPT gave it: under --split-dir, if it's given.
+/// SCRIPT might not be trusted to choose files, so `path` must stay inside the directory.
+fn split_path(args: &Args, path: &str) -> Result<String> {
+    ensure!(
+        Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir)),
+        "--split paths must be relative and stay inside the directory, but got {path}"
+    );
+    Ok(match &args.split_dir {
+        Some(dir) => Path::new(dir).join(path).to_string_lossy().into_owned(),
+        None => path.to_string(),
+    })
+}
+
+/// Set the input and output formats to the format of the first of FILES, or of STDIN, as guessed
+/// for --auto, unless they've been set already.
+fn auto(args: &mut Args, files: &[String]) -> Result<()> {
+    let format = if let Some(file) = files.first() {
+        detect::from_extension(file).or_else(|| {
+            // Reading a URL twice isn't worth it, and a file which can't be read fails later.
+            let f = File::open(file).ok().filter(|_| !http::is_url(file))?;
+            detect::sniff(BufReader::new(f).fill_buf().ok()?)
+        })
+    } else {
+        // What's sniffed stays in STDIN's buffer, to be read again as usual.
+        let stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            None
+        } else {
+            detect::sniff(stdin.lock().fill_buf()?)
+        }
+    };
+    let Some(format) = format else {
+        return Ok(());
+    };
+
+    let input = args.json_in
+        || args.yaml_in
+        || args.toml_in
+        || args.gron_in
+        || args.ini_in
+        || args.env_in
+        || args.plist_in
+        || args.from.is_some()
+        || args.stream
+        || args.pointer.is_some();
+    if !input {
+        match &format {
+            Format::Json => args.json_in = true,
+            Format::Ndjson => args.stream = true,
+            Format::Yaml => args.yaml_in = true,
+            Format::Toml => args.toml_in = true,
+            Format::Gron => args.gron_in = true,
+            Format::Ini => args.ini_in = true,
+            Format::Env => args.env_in = true,
+            Format::Plist => args.plist_in = true,
+            Format::Plugin(name) => args.from = Some(name.clone()),
+            Format::JsonSeq | Format::YamlDocs | Format::Jcs => {}
+        }
+    }
+
+    let output = args.json_out
+        || args.yaml_out
+        || args.toml_out
+        || args.gron_out
+        || args.ini_out
+        || args.env_out
+        || args.plist_out
+        || args.seq
+        || args.yaml_docs
+        || args.jcs
+        || args.no_out
+        || args.interactive
+        || args.pick;
+    if !output {
+        args.auto_out = Some(format);
+    }
+    Ok(())
+}
+
+fn try_main() -> Result<ExitCode> {
+    let mut args = Args::parse();
+    configure(&args)?;
+
+    #[cfg(feature = "self-update")]
+    if args.self_update {
+        update::update()?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.info {
+        print::json(
+            &mut print::stdout(),
+            &print_options(&args)?,
+            &info::info()?.to_string(),
+        )
+        .context("printing info")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let (steps, files) = steps(&args)?;
+
+    let files = if args.diff.is_empty() {
+        files
+    } else {
+        ensure!(files.is_empty(), "--diff reads A and B rather than FILES");
+        args.diff.clone()
+    };
+
+    if let Some(key) = &args.index {
+        ensure!(!files.is_empty(), "--index requires FILES");
+        for file in &files {
+            index::build(file, key).with_context(|| format!("indexing {file}"))?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.test {
+        return test(&args, &files);
+    }
+
+    if args.lint {
+        ensure!(!args.lua(), "--lint only checks JavaScript");
+        for step in &steps {
+            lint(&step.script)?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.check {
+        ensure!(!args.lua(), "--check only checks JavaScript");
+        for step in &steps {
+            boa::check(&step.script).map_err(|err| match &step.file {
+                Some(file) => diagnostic::from_file(err, file),
+                None => err,
+            })?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.auto {
+        auto(&mut args, &files)?;
+    }
+
+    let print_options = print_options(&args)?;
+    let watch = args.watch;
+    let mut jsq = Jsq::new(args, print_options, steps)?;
+
+    if !watch {
+        if !jsq.args.no_pager
+            && !jsq.args.interactive
+            && !jsq.args.pick
+            && std::io::stdout().is_terminal()
+        {
+            let command = std::env::var("PAGER").unwrap_or_else(|_| String::from("less -R"));
+            let (_, height) = crossterm::terminal::size()?;
+            pager::start(command, height.into());
+        }
+        jsq.evaluate(&files)?;
+        return Ok(if jsq.args.exit_status && jsq.falsy {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    ensure!(!files.is_empty(), "--watch requires FILES");
+    let mut watcher = Watcher::new(&files);
+    loop {
+        if jsq.args.clear {
+            write!(std::io::stdout(), "\x1b[2J\x1b[H")?;
+        }
+        if let Err(err) = jsq.evaluate(&files) {
+            print::error(&mut print::stderr(), &err).context("printing error")?;
+        }
+        std::io::stdout().flush()?;
+        watcher.wait();
+        jsq.reset()?;
+    }
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|err| {
+        err.downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == ErrorKind::BrokenPipe)
+    })
+}
+
+/// Run jsq as it runs from the command line, with the arguments it was started with.
+///
+/// # Panics
+///
+/// If an error can't be printed to STDERR.
+#[must_use]
+pub fn main() -> ExitCode {
+    let res = try_main();
+    // Any output held back by the pager comes before the error.
+    let paged = pager::finish().context("paging output");
+    match res.and_then(|code| paged.map(|()| code)) {
+        Ok(code) => code,
+        // Whatever STDOUT was piped in to has stopped reading, e.g. head, so there's no one left
+        // to tell.
+        Err(err) if is_broken_pipe(&err) => ExitCode::SUCCESS,
+        Err(err) => {
+            print::error(&mut print::stderr(), &err).expect("printing error");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex, Weak},
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+
+/// How to compress an output file.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression implied by the extension of `file`, if any.
+    pub fn detect(file: &str) -> Option<Self> {
+        let extension = Path::new(file).extension()?;
+        if extension.eq_ignore_ascii_case("gz") {
+            Some(Self::Gzip)
+        } else if extension.eq_ignore_ascii_case("zst") {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file which output is written to, compressed as it's written if need be. Until it's finished,
+/// it can also be finished by [`finish_open`], so it's shared with [`OPEN`].
+pub struct Writer(Arc<Mutex<Option<Inner>>>);
+
+enum Inner {
+    Plain(BufWriter<File>),
+    Gzip(BufWriter<GzEncoder<File>>),
+    Zstd(BufWriter<zstd::Encoder<'static, File>>),
+}
+
+/// Every file still being written, so that they can be finished if the run is cut short.
+static OPEN: Mutex<Vec<Weak<Mutex<Option<Inner>>>>> = Mutex::new(Vec::new());
+
+/// Open `file` to append to, locked so that concurrent runs can't interleave their output until
+/// it's closed. If it doesn't end in a newline, one is added so that lines stay separate.
+fn open_append(file: &str, compression: Option<Compression>) -> io::Result<File> {
+    let mut f = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(file)?;
+    f.lock()?;
+    if compression.is_none() && f.metadata()?.len() > 0 {
+        let mut last = [0];
+        f.seek(SeekFrom::End(-1))?;
+        f.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            f.write_all(b"\n")?;
+        }
+    }
+    Ok(f)
+}
+
+impl Writer {
+    pub fn create(file: &str, compression: Option<Compression>, append: bool) -> Result<Self> {
+        let f = if append {
+            open_append(file, compression).with_context(|| format!("opening {file}"))?
+        } else {
+            File::create(file).with_context(|| format!("creating {file}"))?
+        };
+        let inner = match compression {
+            None => Inner::Plain(BufWriter::new(f)),
+            Some(Compression::Gzip) => Inner::Gzip(BufWriter::new(GzEncoder::new(
+                f,
+                flate2::Compression::default(),
+            ))),
+            Some(Compression::Zstd) => Inner::Zstd(BufWriter::new(zstd::Encoder::new(f, 0)?)),
+        };
+        let w = Arc::new(Mutex::new(Some(inner)));
+        let mut open = OPEN.lock().expect("locking open files");
+        open.retain(|w| w.strong_count() > 0);
+        open.push(Arc::downgrade(&w));
+        Ok(Self(w))
+    }
+
+    /// Flush everything written, and end the compressed stream so that the file is complete.
+    pub fn finish(self) -> io::Result<()> {
+        let inner = self.0.lock().expect("locking file").take();
+        inner.map_or(Ok(()), Inner::finish)
+    }
+}
+
+impl Inner {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(mut w) => w.flush(),
+            Self::Gzip(w) => w.into_inner()?.finish().map(drop),
+            Self::Zstd(w) => w.into_inner()?.finish().map(drop),
+        }
+    }
+}
+
+/// Finish every file which is still being written, as [`Writer::finish`] would, so that compressed
+/// files are complete even when the run is about to exit without finishing them itself. Anything
+/// written to them afterwards fails.
+pub fn finish_open() {
+    let open = std::mem::take(&mut *OPEN.lock().expect("locking open files"));
+    for w in open.iter().filter_map(Weak::upgrade) {
+        if let Some(inner) = w.lock().expect("locking file").take() {
+            inner.finish().ok();
+        }
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *self.0.lock().expect("locking file") {
+            Some(Inner::Plain(w)) => w.write(buf),
+            Some(Inner::Gzip(w)) => w.write(buf),
+            Some(Inner::Zstd(w)) => w.write(buf),
+            None => Err(io::Error::other("the file has already been finished")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.0.lock().expect("locking file") {
+            Some(Inner::Plain(w)) => w.flush(),
+            Some(Inner::Gzip(w)) => w.flush(),
+            Some(Inner::Zstd(w)) => w.flush(),
+            None => Ok(()),
+        }
+    }
+}
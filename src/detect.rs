@@ -0,0 +1,106 @@
+//! Guessing the format of input for --auto, from the extension of the file it's read from, or
+//! failing that from how it starts.
+
+use std::path::Path;
+
+use serde::de::IgnoredAny;
+
+use crate::print::Format;
+
+/// The format of a file named `path`, if its extension is one jsq knows. CSV is left to the plugin
+/// `jsq-format-csv`.
+pub fn from_extension(path: &str) -> Option<Format> {
+    let path = Path::new(path);
+    // .env has no extension, only a name.
+    if path.file_name()?.to_str()?.starts_with(".env") {
+        return Some(Format::Env);
+    }
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "json" => Some(Format::Json),
+        "ndjson" | "jsonl" => Some(Format::Ndjson),
+        "yaml" | "yml" => Some(Format::Yaml),
+        "toml" => Some(Format::Toml),
+        "gron" => Some(Format::Gron),
+        "ini" => Some(Format::Ini),
+        "env" => Some(Format::Env),
+        "plist" => Some(Format::Plist),
+        "csv" => Some(Format::Plugin(String::from("csv"))),
+        _ => None,
+    }
+}
+
+/// Guess the format of input which starts with `start`, which needn't be all of it, or `None` if
+/// it doesn't look like anything but text.
+pub fn sniff(start: &[u8]) -> Option<Format> {
+    if start.starts_with(b"bplist") {
+        return Some(Format::Plist);
+    }
+    let start = String::from_utf8_lossy(start);
+    let mut lines = start
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let first = lines.next()?;
+
+    if first.starts_with("<?xml")
+        || first.starts_with("<!DOCTYPE plist")
+        || first.starts_with("<plist")
+    {
+        return start.contains("<plist").then_some(Format::Plist);
+    }
+
+    if first.starts_with('{') || first.starts_with('[') {
+        // A line which is a whole value, followed by another line, is the start of NDJSON.
+        if serde_json::from_str::<IgnoredAny>(first).is_ok() {
+            return Some(if lines.next().is_some() {
+                Format::Ndjson
+            } else {
+                Format::Json
+            });
+        }
+        // [table] is the only line starting with a bracket which isn't the start of JSON.
+        let table = first.trim_start_matches('[').trim_end_matches(']');
+        if first.ends_with(']') && is_key(table) {
+            return Some(Format::Toml);
+        }
+        return Some(Format::Json);
+    }
+
+    if first.starts_with("json") && first.ends_with(';') {
+        Some(Format::Gron)
+    } else if first == "---" || first == "-" || first.starts_with("- ") {
+        Some(Format::Yaml)
+    } else if first.starts_with("export ") || first.split_once('=').is_some_and(is_env_name) {
+        Some(Format::Env)
+    } else if let Some((key, _)) = first.split_once('=')
+        && is_key(key.trim_end())
+    {
+        Some(Format::Toml)
+    } else if let Some((key, _)) = first.split_once(':')
+        && is_key(key.trim_end())
+    {
+        Some(Format::Yaml)
+    } else {
+        None
+    }
+}
+
+/// Whether `s` looks like a key, bare or quoted, or a dotted path of them, such as a line of YAML
+/// or TOML would start with.
+fn is_key(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '"' | '\''))
+}
+
+/// Whether `key` and `value` either side of an = look like a line of a .env file, NAME=value, with
+/// a name in capitals, rather than a line of TOML.
+fn is_env_name((key, value): (&str, &str)) -> bool {
+    !key.is_empty()
+        && !value.starts_with(' ')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
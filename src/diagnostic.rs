@@ -0,0 +1,186 @@
+use std::{fmt, ops::Range};
+
+use anyhow::Error;
+
+/// The most characters of a line to show either side of the part of it an excerpt points at.
+const MAX_CONTEXT: usize = 40;
+
+/// What was being done when an error occurred.
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Parse,
+    Eval,
+    Convert,
+    Schema,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Parse => "parse",
+            Kind::Eval => "eval",
+            Kind::Convert => "convert",
+            Kind::Schema => "schema",
+        })
+    }
+}
+
+/// The line of input or SCRIPT an error was found on, and the part of it to underline.
+#[derive(Debug)]
+pub struct Excerpt {
+    /// Counting from 1.
+    pub line: usize,
+    /// Counting characters from 1.
+    pub column: usize,
+    pub text: String,
+    /// The characters of `text` to underline.
+    pub span: Range<usize>,
+    /// The lines leading up to `line`, each with its number, to show where in a longer source
+    /// `line` is.
+    pub before: Vec<(usize, String)>,
+}
+
+/// An error which says where it was found and how it might be fixed, as well as what went wrong.
+/// Attached to an error anywhere in its chain of context, it's rendered in full by `print::error`.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub kind: Kind,
+    pub message: String,
+    /// The file the excerpt is from, if it's from a file.
+    pub path: Option<String>,
+    pub excerpt: Option<Excerpt>,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: Kind, message: impl fmt::Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+            path: None,
+            excerpt: None,
+            hint: None,
+        }
+    }
+
+    /// Point at the bytes `span` of `source`, which should start and end on the same line.
+    pub fn excerpt(mut self, source: &str, span: Range<usize>) -> Self {
+        let start = floor_char_boundary(source, span.start);
+        let end = floor_char_boundary(source, span.end).max(start);
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+
+        // Only the part of a long line, such as minified JSON, around the span is shown.
+        let line = &source[line_start..line_end];
+        let before = source[line_start..start].chars().count();
+        let len = source[start..end.min(line_end)].chars().count().max(1);
+        let skip = before.saturating_sub(MAX_CONTEXT);
+        let mut text = line
+            .chars()
+            .skip(skip)
+            .take(before - skip + len + MAX_CONTEXT)
+            .map(|c| if c == '\t' { ' ' } else { c })
+            .collect::<String>();
+        let mut offset = before - skip;
+        if skip > 0 {
+            text.insert(0, '…');
+            offset += 1;
+        }
+        if line.chars().count() > before + len + MAX_CONTEXT {
+            text.push('…');
+        }
+
+        self.excerpt = Some(Excerpt {
+            line: source[..start].matches('\n').count() + 1,
+            column: before + 1,
+            text,
+            span: offset..offset + len,
+            before: Vec::new(),
+        });
+        self
+    }
+
+    /// Show up to `n` lines of `source` before the excerpt, which should be from `source` too.
+    pub fn lines_before(mut self, source: &str, n: usize) -> Self {
+        if let Some(excerpt) = &mut self.excerpt {
+            let first = excerpt.line.saturating_sub(n).max(1);
+            excerpt.before = source
+                .lines()
+                .enumerate()
+                .skip(first - 1)
+                .take(excerpt.line - first)
+                .map(|(i, line)| {
+                    let mut text = line
+                        .chars()
+                        .take(2 * MAX_CONTEXT)
+                        .map(|c| if c == '\t' { ' ' } else { c })
+                        .collect::<String>();
+                    if line.chars().count() > 2 * MAX_CONTEXT {
+                        text.push('…');
+                    }
+                    (i + 1, text)
+                })
+                .collect();
+        }
+        self
+    }
+
+    /// Say that the excerpt is from the file at `path`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+fn floor_char_boundary(s: &str, mut i: usize) -> usize {
+    i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+/// The byte offset in `source` of `line` and `column`, both counting from 1, where columns count
+/// characters.
+pub fn offset(source: &str, line: usize, column: usize) -> usize {
+    let line_start = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    line_start
+        + source[line_start..]
+            .chars()
+            .take_while(|&c| c != '\n')
+            .take(column.saturating_sub(1))
+            .map(char::len_utf8)
+            .sum::<usize>()
+}
+
+/// Say that the excerpt in `err`, if it's a diagnostic with one, is from the file at `path`.
+pub fn from_file(err: Error, path: &str) -> Error {
+    match err.downcast::<Diagnostic>() {
+        Ok(diagnostic) => diagnostic.path(path).into(),
+        Err(err) => err,
+    }
+}
+
+/// The diagnostic attached to `err`, if there is one.
+pub fn find(err: &Error) -> Option<&Diagnostic> {
+    err.chain().find_map(|err| err.downcast_ref())
+}
@@ -0,0 +1,67 @@
+use serde_json::Value;
+
+use crate::stats;
+
+/// How the value at a path differs between two documents.
+pub enum Change {
+    Added(Value),
+    Removed(Value),
+    Changed(Value, Value),
+}
+
+/// Append a key to a path, as it would be accessed in SCRIPT.
+pub fn key_path(path: &str, key: &str) -> String {
+    let mut chars = key.chars();
+    if chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    {
+        format!("{path}.{key}")
+    } else {
+        format!("{path}[{}]", Value::from(key))
+    }
+}
+
+fn walk(path: &str, a: &Value, b: &Value, changes: &mut Vec<(String, Change)>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, a) in a {
+                let path = key_path(path, key);
+                match b.get(key) {
+                    Some(b) => walk(&path, a, b, changes),
+                    None => changes.push((path, Change::Removed(a.clone()))),
+                }
+            }
+            for (key, b) in b {
+                if !a.contains_key(key) {
+                    changes.push((key_path(path, key), Change::Added(b.clone())));
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let path = format!("{path}[{i}]");
+                match (a.get(i), b.get(i)) {
+                    (Some(a), Some(b)) => walk(&path, a, b, changes),
+                    (Some(a), None) => changes.push((path, Change::Removed(a.clone()))),
+                    (None, Some(b)) => changes.push((path, Change::Added(b.clone()))),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        // Numbers are compared by value, so that 1 and 1.0 are the same.
+        (Value::Number(x), Value::Number(y))
+            if stats::canonical_number(x) == stats::canonical_number(y) => {}
+        _ if a != b => changes.push((path.to_string(), Change::Changed(a.clone(), b.clone()))),
+        _ => {}
+    }
+}
+
+/// Every difference between `a` and `b`, by path. Objects are compared key by key, regardless of
+/// the order of their keys, and arrays element by element.
+pub fn diff(a: &Value, b: &Value) -> Vec<(String, Change)> {
+    let mut changes = Vec::new();
+    walk("$", a, b, &mut changes);
+    changes
+}
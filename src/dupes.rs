@@ -0,0 +1,150 @@
+//! Keys which appear more than once in an object in JSON or YAML input. Usually the last value
+//! silently wins, which --dupes can turn in to an error, so that a config can't be overridden by a
+//! key hidden further down it.
+
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::{map::Entry, Map, Number, Value};
+
+use crate::diff;
+
+/// What to do with a key which appears more than once in an object.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dupes {
+    /// Fail, saying where the key is.
+    Error,
+    /// Keep the first value.
+    First,
+    /// Keep the last value, as JSON.parse does.
+    Last,
+}
+
+/// Deserializes a value at `path`, doing as `dupes` says with duplicate keys.
+struct Seed {
+    dupes: Dupes,
+    path: String,
+}
+
+impl<'de> DeserializeSeed<'de> for Seed {
+    type Value = Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Seed {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(v).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut arr = Vec::new();
+        loop {
+            let path = format!("{}[{}]", self.path, arr.len());
+            let Some(v) = seq.next_element_seed(Seed {
+                dupes: self.dupes,
+                path,
+            })?
+            else {
+                return Ok(Value::Array(arr));
+            };
+            arr.push(v);
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut obj = Map::new();
+        while let Some(k) = map.next_key::<String>()? {
+            let path = diff::key_path(&self.path, &k);
+            match obj.entry(k) {
+                Entry::Vacant(entry) => {
+                    let dupes = self.dupes;
+                    entry.insert(map.next_value_seed(Seed { dupes, path })?);
+                }
+                Entry::Occupied(mut entry) => match self.dupes {
+                    Dupes::Error => {
+                        return Err(de::Error::custom(format!("duplicate key {path}")));
+                    }
+                    Dupes::First => {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                    Dupes::Last => {
+                        let dupes = self.dupes;
+                        entry.insert(map.next_value_seed(Seed { dupes, path })?);
+                    }
+                },
+            }
+        }
+        Ok(Value::Object(obj))
+    }
+}
+
+/// Parse JSON, doing as `dupes` says with duplicate keys.
+pub fn json(s: &str, dupes: Dupes) -> serde_json::Result<Value> {
+    if dupes == Dupes::Last {
+        return serde_json::from_str(s);
+    }
+    let mut deserializer = serde_json::Deserializer::from_str(s);
+    let value = Seed {
+        dupes,
+        path: String::from("$"),
+    }
+    .deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    // Numbers are kept as they were written by way of objects with a special key, which are turned
+    // back in to numbers here.
+    serde_json::from_value(value)
+}
+
+/// Parse YAML, doing as `dupes` says with duplicate keys.
+pub fn yaml(s: &str, dupes: Dupes) -> Result<Value, serde_yaml::Error> {
+    if dupes == Dupes::Last {
+        return serde_yaml::from_str(s);
+    }
+    Seed {
+        dupes,
+        path: String::from("$"),
+    }
+    .deserialize(serde_yaml::Deserializer::from_str(s))
+}
@@ -0,0 +1,48 @@
+//! Writing files from SCRIPT with `write`, or with --dry-run, printing how each would change
+//! instead, so that an edit to many files can be looked over before it's made.
+
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Context, Result};
+
+use crate::print;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Print diffs rather than writing files for the rest of the run.
+pub fn enable_dry_run() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+/// Whether --dry-run is set.
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Write `value` to the file at `path`, ending with a newline if it doesn't already, or with
+/// --dry-run, print a diff of how the file would change.
+pub fn write(path: &str, value: &str) -> Result<()> {
+    let mut value = value.to_string();
+    if !value.ends_with('\n') {
+        value.push('\n');
+    }
+
+    if !dry_run() {
+        return fs::write(path, value).with_context(|| format!("writing {path}"));
+    }
+
+    // A file which doesn't exist yet would be created, so it's compared with nothing.
+    let old = match fs::read_to_string(path) {
+        Ok(old) => Some(old),
+        Err(err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => return Err(err).with_context(|| format!("reading {path}")),
+    };
+    let mut stdout = print::stdout();
+    print::unified_diff(&mut stdout, path, old.as_deref(), &value)?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Parse an encoding label, such as `utf-16le` or `latin1`, for clap.
+pub fn parse(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding {label}"))
+}
+
+/// Guess the encoding of input without a byte order mark from its first few bytes: UTF-16 if
+/// every other byte is zero, UTF-8 if they contain no invalid UTF-8 or any valid multi-byte
+/// characters, and Latin-1 otherwise.
+fn sniff(bytes: &[u8]) -> Option<&'static Encoding> {
+    if Encoding::for_bom(bytes).is_some() || bytes.len() < 2 {
+        return None;
+    }
+    let zeros = |parity| {
+        bytes
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let pairs = bytes.len() / 2;
+    if zeros(1) * 2 > pairs && zeros(0) == 0 {
+        return Some(UTF_16LE);
+    }
+    if zeros(0) * 2 > pairs && zeros(1) == 0 {
+        return Some(UTF_16BE);
+    }
+    match std::str::from_utf8(bytes) {
+        // A multi-byte character may have been cut off at the end.
+        Err(err)
+            if err.error_len().is_some()
+                && bytes.utf8_chunks().all(|chunk| chunk.valid().is_ascii()) =>
+        {
+            Some(WINDOWS_1252)
+        }
+        _ => None,
+    }
+}
+
+/// `bytes` as a string, without copying them, if [`decode`] would read them as they are: as UTF-8
+/// without a byte order mark, and no other encoding was given.
+pub fn as_utf8<'a>(bytes: &'a [u8], encoding: Option<&'static Encoding>) -> Option<&'a str> {
+    if encoding.is_some_and(|e| e != UTF_8)
+        || Encoding::for_bom(bytes).is_some()
+        || sniff(&bytes[..bytes.len().min(CHUNK_SIZE)]).is_some()
+    {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()
+}
+
+/// What to do with invalid UTF-8 in input: fail to read it, replace each invalid sequence with
+/// U+FFFD, or replace each invalid byte with an escape of the form \xNN.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum InvalidUtf8 {
+    Error,
+    Replace,
+    Escape,
+}
+
+/// Handles invalid UTF-8 in the bytes read from `reader`, a chunk at a time.
+struct Lossy<R> {
+    reader: R,
+    invalid: InvalidUtf8,
+    /// The escape for a byte, minus its hex digits.
+    escape: &'static str,
+    out: Vec<u8>,
+    pos: usize,
+    /// An incomplete character at the end of the last chunk, which the next might complete.
+    pending: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Lossy<R> {
+    fn refill(&mut self) -> io::Result<()> {
+        let mut data = std::mem::take(&mut self.pending);
+        let start = data.len();
+        data.resize(start + CHUNK_SIZE, 0);
+        let n = self.reader.read(&mut data[start..])?;
+        data.truncate(start + n);
+        self.eof = n == 0;
+
+        self.out.clear();
+        self.pos = 0;
+        let mut end = 0;
+        for chunk in data.utf8_chunks() {
+            self.out.extend_from_slice(chunk.valid().as_bytes());
+            let invalid = chunk.invalid();
+            end += chunk.valid().len() + invalid.len();
+            if invalid.is_empty() {
+                continue;
+            }
+            let incomplete =
+                std::str::from_utf8(invalid).is_err_and(|err| err.error_len().is_none());
+            if end == data.len() && incomplete && !self.eof {
+                self.pending = invalid.to_vec();
+            } else {
+                match self.invalid {
+                    InvalidUtf8::Error => self.out.extend_from_slice(invalid),
+                    InvalidUtf8::Replace => self.out.extend_from_slice("\u{FFFD}".as_bytes()),
+                    InvalidUtf8::Escape => {
+                        for b in invalid {
+                            write!(self.out, "{}{b:02X}", self.escape)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Lossy<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.out.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.refill()?;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Transcode input to UTF-8 from `encoding`, or from the encoding indicated by a byte order mark,
+/// or failing that from the encoding guessed from the start of the input. Byte order marks are
+/// removed. Unless `invalid` is [`InvalidUtf8::Error`], input which isn't valid UTF-8 is assumed to
+/// be UTF-8 with errors rather than Latin-1. If `json` is set then escapes are themselves escaped,
+/// so that they survive being parsed as part of a JSON string.
+pub fn decode(
+    mut reader: impl BufRead,
+    encoding: Option<&'static Encoding>,
+    invalid: InvalidUtf8,
+    json: bool,
+) -> Result<impl BufRead> {
+    let encoding = match encoding {
+        Some(encoding) => Some(encoding),
+        None => sniff(reader.fill_buf()?)
+            .filter(|&e| e != WINDOWS_1252 || invalid == InvalidUtf8::Error),
+    };
+    let decoded = DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .utf8_passthru(true)
+        .strip_bom(true)
+        .build(reader);
+    Ok(BufReader::new(Lossy {
+        reader: decoded,
+        invalid,
+        escape: if json { "\\\\x" } else { "\\x" },
+        out: Vec::new(),
+        pos: 0,
+        pending: Vec::new(),
+        eof: false,
+    }))
+}
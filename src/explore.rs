@@ -0,0 +1,598 @@
+use std::{
+    collections::HashSet,
+    fmt::Write as _,
+    io::{stderr, stdout, IsTerminal, Write},
+};
+
+use anyhow::{ensure, Result};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    terminal::{
+        self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use serde_json::Value;
+use termcolor::{BufferWriter, ColorChoice, WriteColor};
+
+use crate::print::{theme, write_scalar, write_with_color};
+
+const HELP: &str = "↑↓ move  ←→ fold  / search  n next  y copy path  q quit";
+const PICK_HELP: &str = "type to search  ↑↓ move  tab mark  enter pick  esc quit";
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A path to a value, written as it would be accessed in SCRIPT, e.g. `$.data.items[0]`.
+fn js_path(path: &[Segment]) -> String {
+    let mut res = String::from("$");
+    for segment in path {
+        match segment {
+            Segment::Key(key)
+                if key.chars().next().is_some_and(|c| !c.is_ascii_digit())
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$') =>
+            {
+                res.push('.');
+                res.push_str(key);
+            }
+            Segment::Key(key) => {
+                write!(res, "[{}]", Value::from(key.as_str())).expect("writing to a String");
+            }
+            Segment::Index(i) => write!(res, "[{i}]").expect("writing to a String"),
+        }
+    }
+    res
+}
+
+enum Kind<'a> {
+    Value(&'a Value),
+    /// The closing bracket of an expanded array or object.
+    Close(char),
+}
+
+struct Line<'a> {
+    path: Vec<Segment>,
+    kind: Kind<'a>,
+}
+
+fn children(value: &Value) -> Vec<(Segment, &Value)> {
+    match value {
+        Value::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Segment::Index(i), v))
+            .collect(),
+        Value::Object(obj) => obj
+            .iter()
+            .map(|(k, v)| (Segment::Key(k.clone()), v))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_container(value: &Value) -> bool {
+    matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+/// The lines visible when every container not in `expanded` is folded.
+fn visible<'a>(
+    value: &'a Value,
+    path: &mut Vec<Segment>,
+    expanded: &HashSet<Vec<Segment>>,
+    lines: &mut Vec<Line<'a>>,
+) {
+    lines.push(Line {
+        path: path.clone(),
+        kind: Kind::Value(value),
+    });
+    if is_container(value) && expanded.contains(path) {
+        for (segment, child) in children(value) {
+            path.push(segment);
+            visible(child, path, expanded, lines);
+            path.pop();
+        }
+        lines.push(Line {
+            path: path.clone(),
+            kind: Kind::Close(if value.is_array() { ']' } else { '}' }),
+        });
+    }
+}
+
+/// The paths of every value, in document order.
+fn all_paths(value: &Value, path: &mut Vec<Segment>, paths: &mut Vec<Vec<Segment>>) {
+    paths.push(path.clone());
+    for (segment, child) in children(value) {
+        path.push(segment);
+        all_paths(child, path, paths);
+        path.pop();
+    }
+}
+
+fn write_line(
+    w: &mut impl WriteColor,
+    line: &Line,
+    expanded: &HashSet<Vec<Segment>>,
+) -> Result<()> {
+    write!(w, "{}", "  ".repeat(line.path.len()))?;
+    let value = match line.kind {
+        Kind::Close(bracket) => {
+            write!(w, "{bracket}")?;
+            return Ok(());
+        }
+        Kind::Value(value) => value,
+    };
+    match line.path.last() {
+        Some(Segment::Key(key)) => {
+            write_with_color!(w, theme().key, "{}", Value::from(key.as_str()))?;
+            write!(w, ": ")?;
+        }
+        Some(Segment::Index(i)) => write_with_color!(w, theme().annotation, "{i}: ")?,
+        None => {}
+    }
+    match value {
+        Value::Array(arr) if expanded.contains(&line.path) || arr.is_empty() => {
+            write!(w, "{}", if arr.is_empty() { "[]" } else { "[" })?;
+        }
+        Value::Object(obj) if expanded.contains(&line.path) || obj.is_empty() => {
+            write!(w, "{}", if obj.is_empty() { "{}" } else { "{" })?;
+        }
+        Value::Array(arr) => {
+            write!(w, "[…]")?;
+            write_with_color!(w, theme().annotation, " {} items", arr.len())?;
+        }
+        Value::Object(obj) => {
+            write!(w, "{{…}}")?;
+            write_with_color!(w, theme().annotation, " {} keys", obj.len())?;
+        }
+        Value::String(_) => write_with_color!(w, theme().string, "{value}")?,
+        _ => write_scalar(w, value)?,
+    }
+    Ok(())
+}
+
+/// Copy `s` to the clipboard of the terminal emulator with an OSC 52 escape sequence, which works
+/// over SSH too.
+fn copy(s: &str) -> Result<()> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in s.as_bytes().chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    write!(stdout(), "\x1b]52;c;{encoded}\x07")?;
+    Ok(())
+}
+
+/// The terminal is drawn on through STDOUT, or STDERR when STDOUT is for output.
+fn screen(on_stderr: bool) -> Box<dyn Write> {
+    if on_stderr {
+        Box::new(stderr())
+    } else {
+        Box::new(stdout())
+    }
+}
+
+/// Restores the terminal when dropped, however exploring ends.
+struct Guard {
+    on_stderr: bool,
+}
+
+impl Guard {
+    fn new(on_stderr: bool) -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(
+            screen(on_stderr),
+            EnterAlternateScreen,
+            Hide,
+            DisableLineWrap
+        )?;
+        Ok(Self { on_stderr })
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let _ = execute!(
+            screen(self.on_stderr),
+            EnableLineWrap,
+            Show,
+            LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Explorer<'a> {
+    value: &'a Value,
+    paths: Vec<Vec<Segment>>,
+    expanded: HashSet<Vec<Segment>>,
+    lines: Vec<Line<'a>>,
+    cursor: usize,
+    offset: usize,
+    search: Option<String>,
+    query: String,
+    message: String,
+}
+
+impl<'a> Explorer<'a> {
+    fn new(value: &'a Value) -> Self {
+        let mut paths = Vec::new();
+        all_paths(value, &mut Vec::new(), &mut paths);
+        let mut explorer = Self {
+            value,
+            paths,
+            expanded: HashSet::from([Vec::new()]),
+            lines: Vec::new(),
+            cursor: 0,
+            offset: 0,
+            search: None,
+            query: String::new(),
+            message: String::new(),
+        };
+        explorer.refresh(&[]);
+        explorer
+    }
+
+    /// Recompute the visible lines, keeping the cursor on the value at `path`.
+    fn refresh(&mut self, path: &[Segment]) {
+        self.lines.clear();
+        visible(self.value, &mut Vec::new(), &self.expanded, &mut self.lines);
+        self.cursor = self
+            .lines
+            .iter()
+            .position(|line| line.path == path && matches!(line.kind, Kind::Value(_)))
+            .unwrap_or(0);
+    }
+
+    fn current(&self) -> (Vec<Segment>, &'a Value) {
+        let line = &self.lines[self.cursor];
+        let mut value = self.value;
+        for segment in &line.path {
+            value = match segment {
+                Segment::Key(key) => &value[key],
+                Segment::Index(i) => &value[i],
+            };
+        }
+        (line.path.clone(), value)
+    }
+
+    fn fold(&mut self, expand: bool) {
+        let (path, value) = self.current();
+        if expand && is_container(value) {
+            self.expanded.insert(path.clone());
+        } else if !expand && self.expanded.contains(&path) && !path.is_empty() {
+            self.expanded.remove(&path);
+        } else if !expand && !path.is_empty() {
+            self.refresh(&path[..path.len() - 1]);
+            return;
+        }
+        self.refresh(&path);
+    }
+
+    /// Move to the next value, after the cursor, with a key containing the query.
+    fn find_next(&mut self) {
+        let query = self.query.to_lowercase();
+        let (path, _) = self.current();
+        let start = self.paths.iter().position(|p| *p == path).unwrap_or(0);
+        let found = (1..=self.paths.len())
+            .map(|i| &self.paths[(start + i) % self.paths.len()])
+            .find(
+                |p| matches!(p.last(), Some(Segment::Key(k)) if k.to_lowercase().contains(&query)),
+            )
+            .cloned();
+        let Some(found) = found else {
+            self.message = format!("no key matching {}", self.query);
+            return;
+        };
+        for i in 0..found.len() {
+            self.expanded.insert(found[..i].to_vec());
+        }
+        self.refresh(&found);
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let (_, height) = terminal::size()?;
+        let rows = usize::from(height.saturating_sub(1)).max(1);
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + rows {
+            self.offset = self.cursor + 1 - rows;
+        }
+
+        let writer = BufferWriter::stdout(ColorChoice::Auto);
+        let mut buf = writer.buffer();
+        for (i, line) in self.lines.iter().enumerate().skip(self.offset).take(rows) {
+            write!(buf, "{}", if i == self.cursor { "› " } else { "  " })?;
+            write_line(&mut buf, line, &self.expanded)?;
+            write!(buf, "\r\n")?;
+        }
+
+        let mut out = stdout().lock();
+        queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+        out.write_all(buf.as_slice())?;
+        queue!(out, MoveTo(0, height.saturating_sub(1)))?;
+        let status = if let Some(search) = &self.search {
+            format!("/{search}")
+        } else if self.message.is_empty() {
+            format!("{}  {HELP}", js_path(&self.lines[self.cursor].path))
+        } else {
+            format!(
+                "{}  {}",
+                js_path(&self.lines[self.cursor].path),
+                self.message
+            )
+        };
+        let mut buf = writer.buffer();
+        write_with_color!(buf, theme().annotation, "{status}")?;
+        out.write_all(buf.as_slice())?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Handle a key press, returning false if it's time to quit.
+    fn key(&mut self, key: KeyEvent) -> Result<bool> {
+        self.message.clear();
+
+        if let Some(search) = &mut self.search {
+            match key.code {
+                KeyCode::Enter => {
+                    self.query = self.search.take().unwrap_or_default();
+                    self.find_next();
+                }
+                KeyCode::Esc => self.search = None,
+                KeyCode::Backspace => {
+                    search.pop();
+                }
+                KeyCode::Char(c) => search.push(c),
+                _ => {}
+            }
+            return Ok(true);
+        }
+
+        let (_, height) = terminal::size()?;
+        let page = usize::from(height.saturating_sub(1)).max(1);
+        let last = self.lines.len() - 1;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false)
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(page),
+            KeyCode::PageDown => self.cursor = (self.cursor + page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.cursor = 0,
+            KeyCode::End | KeyCode::Char('G') => self.cursor = last,
+            KeyCode::Right | KeyCode::Char('l') => self.fold(true),
+            KeyCode::Left | KeyCode::Char('h') => self.fold(false),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let (path, _) = self.current();
+                self.fold(!self.expanded.contains(&path));
+            }
+            KeyCode::Char('/') => self.search = Some(String::new()),
+            KeyCode::Char('n') if !self.query.is_empty() => self.find_next(),
+            KeyCode::Char('y') => {
+                let path = js_path(&self.lines[self.cursor].path);
+                copy(&path)?;
+                self.message = format!("copied {path}");
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+}
+
+/// Explore the JSON value `json` interactively as a tree of foldable arrays and objects.
+pub fn explore(json: &str) -> Result<()> {
+    ensure!(stdout().is_terminal(), "--interactive requires a terminal");
+    let value = serde_json::from_str(json)?;
+    let mut explorer = Explorer::new(&value);
+
+    let _guard = Guard::new(false)?;
+    loop {
+        explorer.draw()?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && !explorer.key(key)?
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// How well `query` matches `candidate`, ignoring case, if its characters appear there in order.
+/// Lower is better: the number of characters skipped between the first match and the last.
+fn fuzzy(query: &str, candidate: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut started = false;
+    let mut chars = candidate.chars().flat_map(char::to_lowercase);
+    for q in query.chars().flat_map(char::to_lowercase) {
+        loop {
+            let c = chars.next()?;
+            if c == q {
+                started = true;
+                break;
+            }
+            if started {
+                score += 1;
+            }
+        }
+    }
+    Some(score)
+}
+
+struct Picker<'a> {
+    value: &'a Value,
+    paths: Vec<Vec<Segment>>,
+    query: String,
+    /// Indices in to `paths` of those matching the query, best first.
+    matches: Vec<usize>,
+    marked: Vec<usize>,
+    cursor: usize,
+    offset: usize,
+}
+
+impl<'a> Picker<'a> {
+    fn new(value: &'a Value) -> Self {
+        let mut paths = Vec::new();
+        all_paths(value, &mut Vec::new(), &mut paths);
+        let mut picker = Self {
+            value,
+            paths,
+            query: String::new(),
+            matches: Vec::new(),
+            marked: Vec::new(),
+            cursor: 0,
+            offset: 0,
+        };
+        picker.filter();
+        picker
+    }
+
+    fn filter(&mut self) {
+        let mut matches = self
+            .paths
+            .iter()
+            .enumerate()
+            .filter_map(|(i, path)| Some((fuzzy(&self.query, &js_path(path))?, i)))
+            .collect::<Vec<_>>();
+        // Shorter paths first among equally good matches, which is document order otherwise.
+        matches.sort_by_key(|&(score, i)| (score, self.paths[i].len(), i));
+        self.matches = matches.into_iter().map(|(_, i)| i).collect();
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    fn value_at(&self, path: &[Segment]) -> &'a Value {
+        let mut value = self.value;
+        for segment in path {
+            value = match segment {
+                Segment::Key(key) => &value[key],
+                Segment::Index(i) => &value[i],
+            };
+        }
+        value
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let (_, height) = terminal::size()?;
+        let rows = usize::from(height.saturating_sub(2)).max(1);
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + rows {
+            self.offset = self.cursor + 1 - rows;
+        }
+
+        let writer = BufferWriter::stderr(ColorChoice::Auto);
+        let mut buf = writer.buffer();
+        write!(buf, "> {}\r\n", self.query)?;
+        for (i, &index) in self.matches.iter().enumerate().skip(self.offset).take(rows) {
+            let path = &self.paths[index];
+            let marker = if self.marked.contains(&index) {
+                "*"
+            } else {
+                " "
+            };
+            write!(buf, "{}{marker}", if i == self.cursor { "›" } else { " " })?;
+            write!(buf, "{}  ", js_path(path))?;
+            let line = Line {
+                path: Vec::new(),
+                kind: Kind::Value(self.value_at(path)),
+            };
+            write_line(&mut buf, &line, &HashSet::new())?;
+            write!(buf, "\r\n")?;
+        }
+
+        let mut out = stderr().lock();
+        queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+        out.write_all(buf.as_slice())?;
+        queue!(out, MoveTo(0, height.saturating_sub(1)))?;
+        let mut buf = writer.buffer();
+        write_with_color!(
+            buf,
+            theme().annotation,
+            "{}/{}  {PICK_HELP}",
+            self.matches.len(),
+            self.paths.len()
+        )?;
+        out.write_all(buf.as_slice())?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Handle a key press, returning the picked paths once picking is over.
+    fn key(&mut self, key: KeyEvent) -> Option<Vec<String>> {
+        let last = self.matches.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc => return Some(Vec::new()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(Vec::new())
+            }
+            KeyCode::Enter => {
+                let mut picked = self.marked.clone();
+                if picked.is_empty() {
+                    picked.extend(self.matches.get(self.cursor));
+                }
+                return Some(picked.iter().map(|&i| js_path(&self.paths[i])).collect());
+            }
+            KeyCode::Tab => {
+                if let Some(&index) = self.matches.get(self.cursor) {
+                    if let Some(i) = self.marked.iter().position(|&m| m == index) {
+                        self.marked.remove(i);
+                    } else {
+                        self.marked.push(index);
+                    }
+                    self.cursor = (self.cursor + 1).min(last);
+                }
+            }
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Pick paths in to the JSON value `json` from a list which can be narrowed down by fuzzy search,
+/// returning each as it would be accessed in SCRIPT. The list is drawn on STDERR, so that the
+/// paths can be captured from STDOUT.
+pub fn pick(json: &str) -> Result<Vec<String>> {
+    ensure!(stderr().is_terminal(), "--pick requires a terminal");
+    let value = serde_json::from_str(json)?;
+    let mut picker = Picker::new(&value);
+
+    let _guard = Guard::new(true)?;
+    loop {
+        picker.draw()?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && let Some(picked) = picker.key(key)
+        {
+            return Ok(picked);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt::Write as _;
+
+use anyhow::{anyhow, Error};
+use serde_json::Value;
+
+use crate::{
+    diagnostic::{self, Diagnostic, Kind},
+    print,
+};
+
+/// The most paths to describe, and the most characters of each value to show.
+const MAX_PATHS: usize = 5;
+const MAX_EXCERPT: usize = 60;
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Parse the property accesses following a `$` at the start of `s`, such as `.a?.b[0]["c"]`,
+/// stopping before any method call.
+fn segments(mut s: &str) -> Vec<(Segment, &str)> {
+    let mut res = Vec::new();
+    loop {
+        let rest = s.strip_prefix("?.").or_else(|| s.strip_prefix('.'));
+        if let Some(rest) = rest {
+            let len = rest.find(|c| !is_ident(c)).unwrap_or(rest.len());
+            if len == 0 || rest[len..].trim_start().starts_with('(') {
+                break;
+            }
+            res.push((
+                Segment::Key(rest[..len].to_string()),
+                &s[..s.len() - rest.len() + len],
+            ));
+            s = &rest[len..];
+        } else if let Some(rest) = s.strip_prefix('[') {
+            let Some(end) = rest.find(']') else {
+                break;
+            };
+            let inner = rest[..end].trim();
+            let segment = if let Ok(i) = inner.parse() {
+                Segment::Index(i)
+            } else if inner.len() >= 2
+                && (inner.starts_with('"') && inner.ends_with('"')
+                    || inner.starts_with('\'') && inner.ends_with('\''))
+            {
+                Segment::Key(inner[1..inner.len() - 1].to_string())
+            } else {
+                break;
+            };
+            res.push((segment, &s[..end + 2]));
+            s = &rest[end + 1..];
+        } else {
+            break;
+        }
+    }
+    res
+}
+
+/// The paths into the input read by `script`, e.g. `$.items[0].name`, each as its segments.
+fn paths(script: &str) -> Vec<Vec<(Segment, &str)>> {
+    let mut res: Vec<Vec<(Segment, &str)>> = Vec::new();
+    for (i, _) in script.match_indices('$') {
+        let before = script[..i].chars().next_back();
+        let after = script[i + 1..].chars().next();
+        if before.is_some_and(|c| is_ident(c) || c == '.') || after.is_some_and(is_ident) {
+            continue;
+        }
+        let path = segments(&script[i + 1..]);
+        let text = |path: &[(Segment, &str)]| path.iter().map(|(_, s)| *s).collect::<String>();
+        if !path.is_empty() && !res.iter().any(|p| text(p) == text(&path)) {
+            res.push(path);
+        }
+    }
+    res
+}
+
+fn excerpt(value: &Value) -> String {
+    let s = value.to_string();
+    if s.chars().count() > MAX_EXCERPT {
+        format!("{}…", s.chars().take(MAX_EXCERPT).collect::<String>())
+    } else {
+        s
+    }
+}
+
+/// Describe the value at `path` in `input`, or where following it breaks down.
+fn describe(input: &Value, path: &[(Segment, &str)]) -> String {
+    let mut text = String::from("$");
+    let mut value = input;
+    for (segment, s) in path {
+        let next = match (segment, value) {
+            (Segment::Key(k), Value::Object(obj)) => obj.get(k),
+            (Segment::Index(i), Value::Array(arr)) => arr.get(*i),
+            (_, Value::Null) => return format!("`{text}` is null"),
+            _ => None,
+        };
+        text.push_str(s);
+        let Some(next) = next else {
+            return format!("`{text}` is undefined");
+        };
+        value = next;
+    }
+    format!(
+        "`{text}` is {}: {}",
+        print::type_name(value),
+        excerpt(value)
+    )
+}
+
+/// If `err` is a `TypeError`, add a description of the value at each path into `input` read by
+/// `script`, so that any mismatch between the script and the shape of the input is clear.
+pub fn type_error(err: Error, script: &str, input: &str) -> Error {
+    if !err.to_string().starts_with("TypeError") {
+        return err;
+    }
+    let Ok(input) = input.parse::<Value>() else {
+        return err;
+    };
+    let paths = paths(script);
+    if paths.is_empty() {
+        return err;
+    }
+    let mut msg = format!("{err:#}");
+    for path in paths.iter().take(MAX_PATHS) {
+        write!(msg, "\n  note: {}", describe(&input, path)).expect("writing to a String");
+    }
+    anyhow!(msg)
+}
+
+/// Attach a diagnostic to an error evaluating `script`, with an excerpt of `script` if the error
+/// says where in it it was found, e.g. `SyntaxError: unexpected token ')' at line 1, col 9`.
+pub fn eval_error(err: &Error, script: &str) -> Error {
+    let message = format!("{err:#}");
+    let mut diagnostic = Diagnostic::new(Kind::Eval, &message);
+    let first_line = message.lines().next().unwrap_or_default();
+    if let Some((_, position)) = first_line.rsplit_once(" at line ")
+        && let Some((line, col)) = position.split_once(", col ")
+        && let Ok(line) = line.parse()
+        && let Ok(col) = col
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .unwrap_or(col)
+            .parse()
+    {
+        let offset = diagnostic::offset(script, line, col);
+        diagnostic = diagnostic
+            .excerpt(script, offset..offset + 1)
+            .lines_before(script, 2);
+    }
+    if first_line.starts_with("SyntaxError") && script.trim_start().starts_with("return") {
+        diagnostic = diagnostic.hint("SCRIPT is an expression, so it doesn't need a return");
+    }
+    if first_line.starts_with("SyntaxError") && script.trim_start().starts_with("function") {
+        diagnostic = diagnostic.hint(
+            "SCRIPT is an expression, so a function needs parentheses, e.g. (function* (x) { ... })",
+        );
+    }
+    diagnostic.into()
+}
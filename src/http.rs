@@ -0,0 +1,133 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Context, Result};
+use ureq::Agent;
+
+/// How many requests `fetch` may make, and how long it may spend waiting on them, across every
+/// evaluation of SCRIPT, so that a mistake can't make requests without end.
+#[derive(Clone, Copy, Default)]
+pub struct Budget {
+    pub requests: Option<usize>,
+    pub time: Option<Duration>,
+}
+
+static BUDGET: OnceLock<Budget> = OnceLock::new();
+static REQUESTS: AtomicUsize = AtomicUsize::new(0);
+static NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Limit the requests `fetch` makes from now on.
+pub fn set_budget(budget: Budget) {
+    BUDGET.set(budget).ok();
+}
+
+/// Start afresh, as if no requests had been made.
+pub fn reset() {
+    REQUESTS.store(0, Ordering::Relaxed);
+    NANOS.store(0, Ordering::Relaxed);
+}
+
+/// Count a request against the budget, returning how long it may take, or an error if the budget
+/// is already spent.
+fn spend() -> Result<Option<Duration>> {
+    let budget = BUDGET.get().copied().unwrap_or_default();
+    let requests = REQUESTS.fetch_add(1, Ordering::Relaxed);
+    if let Some(max) = budget.requests {
+        ensure!(requests < max, "fetch exceeded --max-requests of {max}");
+    }
+    let Some(max) = budget.time else {
+        return Ok(None);
+    };
+    let remaining = max.saturating_sub(Duration::from_nanos(NANOS.load(Ordering::Relaxed)));
+    ensure!(
+        !remaining.is_zero(),
+        "fetch exceeded --max-network-time of {}s",
+        max.as_secs_f64()
+    );
+    Ok(Some(remaining))
+}
+
+pub fn is_url(s: &str) -> bool {
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+/// Fetch the body of `url` as a string. `headers` are of the form `NAME: VALUE`.
+pub fn get(url: &str, accept: &str, headers: &[String]) -> Result<String> {
+    let mut req = ureq::get(url).header("Accept", accept);
+    for header in headers {
+        let (name, value) = header
+            .split_once(':')
+            .with_context(|| format!("expected a header of the form NAME: VALUE, got {header}"))?;
+        req = req.header(name.trim(), value.trim());
+    }
+    let mut res = req.call()?;
+    Ok(res
+        .body_mut()
+        .with_config()
+        .limit(u64::MAX)
+        .read_to_string()?)
+}
+
+/// The response to a request made by `fetch` in SCRIPT.
+pub struct Response {
+    pub status: u16,
+    /// Each header, with its name in lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Make a request for `fetch` in SCRIPT. Unlike [`get`], a response with an error status is
+/// returned rather than treated as an error, so that SCRIPT can check it.
+pub fn fetch(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: Option<String>,
+) -> Result<Response> {
+    let timeout = spend()?;
+    let start = Instant::now();
+    let res = request(method, url, headers, body, timeout);
+    let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+    NANOS.fetch_add(elapsed, Ordering::Relaxed);
+    res
+}
+
+fn request(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: Option<String>,
+    timeout: Option<Duration>,
+) -> Result<Response> {
+    let agent: Agent = Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(timeout)
+        .build()
+        .into();
+    let mut req = ureq::http::Request::builder().method(method).uri(url);
+    for (name, value) in headers {
+        req = req.header(name, value);
+    }
+    let mut res = match body {
+        Some(body) => agent.run(req.body(body)?)?,
+        None => agent.run(req.body(())?)?,
+    };
+    Ok(Response {
+        status: res.status().as_u16(),
+        headers: res
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: res
+            .body_mut()
+            .with_config()
+            .limit(u64::MAX)
+            .read_to_string()?,
+    })
+}
@@ -0,0 +1,106 @@
+//! Sidecar indexes of NDJSON files, built with --index, which let --where seek straight to the
+//! records it matches, rather than reading the whole file.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+use crate::{lookup, print};
+
+/// Parse a condition of the form `KEY=VALUE`, for clap.
+pub fn parse_condition(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| String::from("expected a condition of the form KEY=VALUE"))
+}
+
+/// The path of the index of `path`.
+fn sidecar(path: &str) -> String {
+    format!("{path}.jsq-index")
+}
+
+/// The length and modification time of `path`, so that an index of an older version of the file
+/// isn't used.
+fn stamp(path: &str) -> Result<Value> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok(json!([
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos()
+    ]))
+}
+
+/// Index the records in the NDJSON file `path`, one to a line, by the value at `key`, and write
+/// the index alongside it. Each key maps to the byte offset and line of every record with that key.
+pub fn build(path: &str, key: &str) -> Result<()> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("reading {path}"))?);
+    let mut offsets = HashMap::<String, Vec<(u64, usize)>>::new();
+    let mut offset = 0;
+    let mut line = String::new();
+    for n in 1.. {
+        line.clear();
+        let len = reader.read_line(&mut line)?;
+        if len == 0 {
+            break;
+        }
+        if !line.trim().is_empty() {
+            let record: Value = serde_json::from_str(&line)
+                .with_context(|| format!("parsing {path} at line {n}"))?;
+            if let Some(k) = lookup::key(&record, key) {
+                offsets.entry(k).or_default().push((offset, n));
+            }
+        }
+        offset += len as u64;
+    }
+    let index = json!({ "key": key, "stamp": stamp(path)?, "offsets": offsets });
+    let sidecar = sidecar(path);
+    std::fs::write(&sidecar, index.to_string()).with_context(|| format!("writing {sidecar}"))
+}
+
+/// A record as it was read, to be decoded and parsed as the rest of the stream would be, with the
+/// line it's on.
+type Record = (usize, Vec<u8>);
+
+/// The records in `path` whose value at `key` is `value`, if `path` has an index by `key` which is
+/// up to date.
+pub fn find(path: &str, key: &str, value: &str) -> Result<Option<Vec<Record>>> {
+    let sidecar = sidecar(path);
+    let Ok(index) = std::fs::read_to_string(&sidecar) else {
+        return Ok(None);
+    };
+    let index: Value =
+        serde_json::from_str(&index).with_context(|| format!("parsing {sidecar}"))?;
+    if index["key"] != key {
+        return Ok(None);
+    }
+    if index["stamp"] != stamp(path)? {
+        print::warn(
+            &mut print::stderr(),
+            &anyhow!("{path} has changed since it was indexed, so it's read in full"),
+        )?;
+        return Ok(None);
+    }
+
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("reading {path}"))?);
+    let mut records = Vec::new();
+    let entries = index["offsets"][value]
+        .as_array()
+        .map_or(&[][..], Vec::as_slice);
+    for entry in entries {
+        let (Some(offset), Some(n)) = (entry[0].as_u64(), entry[1].as_u64()) else {
+            continue;
+        };
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        records.push((usize::try_from(n)?, line));
+    }
+    Ok(Some(records))
+}
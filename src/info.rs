@@ -0,0 +1,50 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::{
+    boa,
+    compress::Compression,
+    encoding::InvalidUtf8,
+    print,
+    safe::{self, Capability},
+};
+
+/// Incremented whenever a field of the report is removed or changes meaning, so that tools can
+/// rely on the fields they know about.
+const SCHEMA: u32 = 2;
+
+/// The names of the values of an argument.
+fn names<T: ValueEnum>() -> Vec<String> {
+    T::value_variants()
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+/// A report of the version of jsq and what it supports, for tools which wrap it to check for
+/// features without parsing --help. The globals and whether requests can be made are as they are
+/// for SCRIPT in this run, after --safe and --no-net.
+pub fn info() -> Result<Value> {
+    Ok(json!({
+        "schema": SCHEMA,
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "engine": {
+            "name": "boa",
+        },
+        "input_formats": ["json", "yaml", "toml", "gron", "ini", "env", "plist"],
+        "output_formats": print::FORMATS.map(|(name, _)| name),
+        "compression": names::<Compression>(),
+        "invalid_utf8": names::<InvalidUtf8>(),
+        "globals": boa::globals()?,
+        "http": safe::allowed(Capability::Net),
+        "format_plugins": true,
+        "json_schema": true,
+        "wasm": cfg!(feature = "wasm"),
+        "lua": cfg!(feature = "lua"),
+        "simd": cfg!(feature = "simd"),
+        "typescript": cfg!(feature = "typescript"),
+    }))
+}
@@ -0,0 +1,116 @@
+//! jsq as a library, for tools which would rather link it than run it: [`Engine`] evaluates
+//! JavaScript against JSON values, and [`printers`] prints them as JSON, YAML, or TOML, in color
+//! if the writer supports it.
+
+#![warn(clippy::pedantic)]
+
+mod boa;
+mod cli;
+mod compress;
+mod detect;
+mod diagnostic;
+mod diff;
+mod dupes;
+mod edit;
+mod encoding;
+mod explore;
+mod hint;
+mod http;
+mod index;
+mod info;
+mod limit;
+mod lint;
+mod lookup;
+#[cfg(feature = "lua")]
+mod lua;
+mod manifest;
+mod merge;
+mod mmap;
+mod pager;
+mod parse;
+mod path;
+mod plugin;
+mod pool;
+mod precision;
+mod print;
+mod profile;
+mod safe;
+mod schema;
+#[cfg(feature = "simd")]
+mod simd;
+mod soft;
+mod stats;
+mod stream;
+mod suite;
+#[cfg(feature = "typescript")]
+mod typescript;
+mod undefined;
+#[cfg(feature = "self-update")]
+mod update;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod watch;
+
+use anyhow::Result;
+use serde_json::Value;
+
+pub use cli::main;
+
+/// Printers for JSON values, each taking the value as a JSON string.
+pub mod printers {
+    pub use crate::print::{env, gron, ini, json, json_line, plist, toml, yaml, Options, TypeHint};
+}
+
+/// Evaluates JavaScript against JSON values, with the same helpers as SCRIPT, such as `YAML` and
+/// `fetch`. Declarations persist from one evaluation to the next.
+pub struct Engine {
+    runtime: boa::Runtime,
+}
+
+impl Engine {
+    /// # Errors
+    ///
+    /// If the JavaScript runtime can't be started.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            runtime: boa::Runtime::new(boa::Options {
+                env: std::iter::empty(),
+                vars: Vec::new(),
+                lookups: Vec::new(),
+                init: None,
+                parse: true,
+                stringify: true,
+                undefined: undefined::Undefined::Omit,
+            })?,
+        })
+    }
+
+    /// Evaluate `script` with `value` as `$`, as jsq evaluates SCRIPT, returning `None` if the
+    /// result is `undefined`.
+    ///
+    /// # Errors
+    ///
+    /// If `script` throws, or its result can't be serialized as JSON.
+    pub fn eval(&mut self, script: &str, value: &Value) -> Result<Option<Value>> {
+        let res = self.runtime.eval(script, &value.to_string(), None)?;
+        from_result(&res)
+    }
+
+    /// Call the JavaScript function `f`, e.g. `x => x.id`, on `value`, returning `None` if the
+    /// result is `undefined`.
+    ///
+    /// # Errors
+    ///
+    /// If `f` isn't a function, throws, or its result can't be serialized as JSON.
+    pub fn apply(&mut self, f: &str, value: &Value) -> Result<Option<Value>> {
+        let res = self.runtime.apply(f, &value.to_string())?;
+        from_result(&res)
+    }
+}
+
+fn from_result(res: &str) -> Result<Option<Value>> {
+    if res == "undefined" {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(res)?))
+}
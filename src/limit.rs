@@ -0,0 +1,191 @@
+//! Hard limits on evaluating SCRIPT, set with --timeout and --max-memory, so that a mistake such as
+//! an infinite loop fails the run rather than wedging it. Lua checks the limits itself as SCRIPT
+//! runs, and fails as it would for any other error. JavaScript can't be interrupted from outside,
+//! so a watchdog thread checks the limits, and once one is crossed finishes the files being
+//! written, prints the error, and exits, as if evaluating SCRIPT had failed with it. Output held
+//! back until the end of the run, such as by --normalize, is lost.
+
+use std::{
+    collections::BTreeMap,
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+
+use crate::{
+    compress,
+    diagnostic::{Diagnostic, Kind},
+    print, profile,
+};
+
+/// How often the watchdog checks the limits.
+const INTERVAL: Duration = Duration::from_millis(10);
+
+/// How much longer than --timeout the watchdog gives an evaluation which checks the limit itself,
+/// in case it's stuck somewhere that isn't checked, such as a single long call in to Rust.
+#[cfg(feature = "lua")]
+const GRACE: Duration = Duration::from_secs(1);
+
+/// The longest any one evaluation of SCRIPT may take, and the most memory, in bytes, the run may
+/// use.
+#[derive(Clone, Copy, Default)]
+pub struct Limits {
+    pub timeout: Option<Duration>,
+    pub memory: Option<u64>,
+}
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+static NEXT: AtomicU64 = AtomicU64::new(0);
+/// When each evaluation still going, on any thread, has to be done by.
+static DEADLINES: Mutex<BTreeMap<u64, Instant>> = Mutex::new(BTreeMap::new());
+
+/// Parse a duration such as `5s`, `500ms`, `2m`, or `1h`. A bare number is in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (n, unit) = split_unit(s);
+    let scale = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => {
+            return Err(format!(
+                "unknown unit {unit:?}, expected one of ms, s, m, or h"
+            ))
+        }
+    };
+    let n = n.parse::<f64>().map_err(|err| err.to_string())?;
+    Duration::try_from_secs_f64(n * scale).map_err(|err| err.to_string())
+}
+
+/// Parse a number of bytes such as `256M`, `1G`, or `512KiB`, in powers of 1024. A bare number is
+/// in bytes.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (n, unit) = split_unit(s);
+    let shift = match unit.trim_end_matches("iB").trim_end_matches('B') {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => {
+            return Err(format!(
+                "unknown unit {unit:?}, expected one of K, M, G, or T"
+            ))
+        }
+    };
+    let n = n.parse::<u64>().map_err(|err| err.to_string())?;
+    n.checked_mul(1 << shift)
+        .ok_or_else(|| format!("{s} is too large"))
+}
+
+/// Split `s` in to the number it starts with and the unit which follows.
+fn split_unit(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    let i = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    (&s[..i], s[i..].trim_start())
+}
+
+/// Enforce `limits` for the rest of the run, if there are any.
+pub fn set_limits(limits: Limits) {
+    if limits.timeout.is_none() && limits.memory.is_none() {
+        return;
+    }
+    LIMITS.set(limits).ok();
+    thread::spawn(move || loop {
+        thread::sleep(INTERVAL);
+        if let Some(err) = check(limits) {
+            // Compressed files are only complete once finished.
+            compress::finish_open();
+            print::error(&mut print::stderr(), &err).expect("printing error");
+            process::exit(1);
+        }
+    });
+}
+
+/// The limits for the rest of the run.
+pub fn limits() -> Limits {
+    LIMITS.get().copied().unwrap_or_default()
+}
+
+/// The error for evaluating SCRIPT taking longer than `timeout`.
+pub fn timeout_error(timeout: Duration) -> Error {
+    let message = format!(
+        "evaluating SCRIPT took longer than --timeout {}s",
+        timeout.as_secs_f64(),
+    );
+    Diagnostic::new(Kind::Eval, message).into()
+}
+
+/// The error for SCRIPT needing more than `max` bytes of memory.
+#[cfg(feature = "lua")]
+pub fn memory_error(max: u64) -> Error {
+    let message = format!("SCRIPT needed more than --max-memory {} MiB", max >> 20);
+    Diagnostic::new(Kind::Eval, message).into()
+}
+
+/// The error for the first of `limits` which has been crossed, if any.
+fn check(limits: Limits) -> Option<Error> {
+    if let Some(timeout) = limits.timeout {
+        let deadlines = DEADLINES.lock().expect("locking evaluations");
+        let now = Instant::now();
+        if deadlines.values().any(|&deadline| now > deadline) {
+            return Some(timeout_error(timeout));
+        }
+    }
+    if let Some(max) = limits.memory
+        && let Some(peak) = profile::peak_memory()
+        && peak > max
+    {
+        let message = format!(
+            "the run used {} MiB of memory, more than --max-memory {} MiB",
+            peak >> 20,
+            max >> 20,
+        );
+        return Some(Diagnostic::new(Kind::Eval, message).into());
+    }
+    None
+}
+
+/// An evaluation of SCRIPT which --timeout applies to, until it's dropped.
+pub struct Evaluation(Option<u64>);
+
+/// Start timing an evaluation of SCRIPT, which the watchdog stops once it takes longer than
+/// --timeout.
+pub fn evaluation() -> Evaluation {
+    start(Duration::ZERO)
+}
+
+/// Start timing an evaluation of SCRIPT which fails by itself once it takes longer than
+/// --timeout, so that the watchdog only steps in if it doesn't.
+#[cfg(feature = "lua")]
+pub fn checked_evaluation() -> Evaluation {
+    start(GRACE)
+}
+
+fn start(grace: Duration) -> Evaluation {
+    let Some(timeout) = limits().timeout else {
+        return Evaluation(None);
+    };
+    let id = NEXT.fetch_add(1, Ordering::Relaxed);
+    DEADLINES
+        .lock()
+        .expect("locking evaluations")
+        .insert(id, Instant::now() + timeout + grace);
+    Evaluation(Some(id))
+}
+
+impl Drop for Evaluation {
+    fn drop(&mut self) {
+        if let Some(id) = self.0 {
+            DEADLINES.lock().expect("locking evaluations").remove(&id);
+        }
+    }
+}
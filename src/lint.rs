@@ -0,0 +1,402 @@
+//! Heuristic checks for common mistakes in scripts. Scripts are tokenized rather than parsed, so
+//! these can be fooled, but they never need to evaluate anything.
+
+/// A likely mistake, found at `line` and `column` (both counting from 1).
+pub struct Warning {
+    pub line: usize,
+    pub column: usize,
+    pub message: &'static str,
+    pub help: &'static str,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Ident,
+    Punct,
+    Literal,
+}
+
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    start: usize,
+}
+
+const PUNCTS: &[&str] = &[
+    ">>>=", "===", "!==", "**=", "...", "<<=", ">>=", ">>>", "&&=", "||=", "??=", "=>", "==", "!=",
+    "<=", ">=", "&&", "||", "??", "?.", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=",
+    "<<", ">>", "**",
+];
+
+/// Keywords after which a `/` starts a regular expression rather than being division.
+const BEFORE_REGEX: &[&str] = &[
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+    "await",
+];
+
+/// Array methods which use the value returned by their callback.
+const CALLBACK_METHODS: &[&str] = &[
+    "map",
+    "flatMap",
+    "filter",
+    "find",
+    "findIndex",
+    "findLast",
+    "findLastIndex",
+    "some",
+    "every",
+    "reduce",
+    "reduceRight",
+    "sort",
+    "toSorted",
+];
+
+/// Array methods whose callback is a predicate, so an assignment in it is probably a comparison.
+const PREDICATE_METHODS: &[&str] = &[
+    "filter",
+    "find",
+    "findIndex",
+    "findLast",
+    "findLastIndex",
+    "some",
+    "every",
+];
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// The length of the string, template, or regular expression at the start of `rest`, which
+/// starts with `quote`, including both quotes.
+fn quoted_len(rest: &str, quote: char) -> usize {
+    let mut chars = rest.char_indices().skip(1);
+    let mut depth = 0;
+    let mut class = false;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\n' if quote != '`' => return i,
+            '[' if quote == '/' => class = true,
+            ']' if quote == '/' => class = false,
+            '{' if quote == '`' && depth > 0 => depth += 1,
+            '{' if quote == '`' && rest[..i].ends_with('$') => depth += 1,
+            '}' if quote == '`' && depth > 0 => depth -= 1,
+            c if c == quote && depth == 0 && !class => return i + 1,
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+fn tokenize(script: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut start = 0;
+    while let Some(c) = script[start..].chars().next() {
+        let rest = &script[start..];
+        let len = if c.is_whitespace() {
+            start += c.len_utf8();
+            continue;
+        } else if rest.starts_with("//") {
+            start += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        } else if rest.starts_with("/*") {
+            start += rest.find("*/").map_or(rest.len(), |i| i + 2);
+            continue;
+        } else if is_ident(c) {
+            let kind = if c.is_ascii_digit() {
+                Kind::Literal
+            } else {
+                Kind::Ident
+            };
+            let len = rest
+                .find(|c: char| !(is_ident(c) || kind == Kind::Literal && c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token {
+                kind,
+                text: &rest[..len],
+                start,
+            });
+            start += len;
+            continue;
+        } else if matches!(c, '"' | '\'' | '`') {
+            quoted_len(rest, c)
+        } else if c == '/'
+            && tokens.last().is_none_or(|t| match t.kind {
+                Kind::Punct => !matches!(t.text, ")" | "]" | "}"),
+                Kind::Ident => BEFORE_REGEX.contains(&t.text),
+                Kind::Literal => false,
+            })
+        {
+            let len = quoted_len(rest, '/');
+            len + rest[len..]
+                .find(|c: char| !is_ident(c))
+                .unwrap_or(rest.len() - len)
+        } else {
+            let len = PUNCTS
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .map_or(c.len_utf8(), |p| p.len());
+            tokens.push(Token {
+                kind: Kind::Punct,
+                text: &rest[..len],
+                start,
+            });
+            start += len;
+            continue;
+        };
+        tokens.push(Token {
+            kind: Kind::Literal,
+            text: &rest[..len],
+            start,
+        });
+        start += len;
+    }
+    tokens
+}
+
+struct Linter<'a> {
+    script: &'a str,
+    tokens: Vec<Token<'a>>,
+    /// The index of the bracket matching each bracket.
+    partner: Vec<Option<usize>>,
+    /// The index of the innermost open bracket enclosing each token.
+    parent: Vec<Option<usize>>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(script: &'a str) -> Self {
+        let tokens = tokenize(script);
+        let mut partner = vec![None; tokens.len()];
+        let mut parent = vec![None; tokens.len()];
+        let mut stack: Vec<usize> = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            match token.text {
+                ")" | "]" | "}" if token.kind == Kind::Punct => {
+                    if let Some(open) = stack.pop() {
+                        partner[open] = Some(i);
+                        partner[i] = Some(open);
+                    }
+                    parent[i] = stack.last().copied();
+                }
+                "(" | "[" | "{" if token.kind == Kind::Punct => {
+                    parent[i] = stack.last().copied();
+                    stack.push(i);
+                }
+                _ => parent[i] = stack.last().copied(),
+            }
+        }
+        Self {
+            script,
+            tokens,
+            partner,
+            parent,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn text(&self, i: usize) -> Option<&str> {
+        self.tokens.get(i).map(|t| t.text)
+    }
+
+    fn warn(&mut self, i: usize, message: &'static str, help: &'static str) {
+        let before = &self.script[..self.tokens[i].start];
+        self.warnings.push(Warning {
+            line: before.matches('\n').count() + 1,
+            column: before.chars().rev().take_while(|&c| c != '\n').count() + 1,
+            message,
+            help,
+        });
+    }
+
+    /// The index of the first token of the parameters of the arrow function whose `=>` is at `i`.
+    fn arrow_start(&self, i: usize) -> Option<usize> {
+        let prev = i.checked_sub(1)?;
+        if self.text(prev) == Some(")") {
+            self.partner[prev]
+        } else {
+            Some(prev)
+        }
+    }
+
+    /// The method called with the arrow function whose `=>` is at `i` as its first argument, if
+    /// any.
+    fn callee(&self, i: usize) -> Option<&str> {
+        let open = self.arrow_start(i)?.checked_sub(1)?;
+        let method = open.checked_sub(1)?;
+        let dot = method.checked_sub(1)?;
+        (self.text(open) == Some("(")
+            && self.tokens[method].kind == Kind::Ident
+            && matches!(self.text(dot), Some("." | "?.")))
+        .then(|| self.tokens[method].text)
+    }
+
+    /// The `{` opening the body of the function whose `=>` or `function` is at `i`, if it has one.
+    fn body(&self, i: usize) -> Option<usize> {
+        if self.text(i) == Some("=>") {
+            return (self.text(i + 1) == Some("{")).then_some(i + 1);
+        }
+        let mut open = i + 1;
+        if self.text(open) == Some("*") {
+            open += 1;
+        }
+        if self.tokens.get(open)?.kind == Kind::Ident {
+            open += 1;
+        }
+        (self.text(open) == Some("("))
+            .then(|| self.partner[open])
+            .flatten()
+            .filter(|&close| self.text(close + 1) == Some("{"))
+            .map(|close| close + 1)
+    }
+
+    /// Whether the function body opened at `open` contains a `return`, not counting those in
+    /// nested functions.
+    fn returns(&self, open: usize) -> bool {
+        let Some(close) = self.partner[open] else {
+            return true;
+        };
+        let mut i = open + 1;
+        while i < close {
+            match self.text(i) {
+                Some("return") => return true,
+                Some("=>" | "function") => {
+                    if let Some(nested) = self.body(i).and_then(|body| self.partner[body]) {
+                        i = nested;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        false
+    }
+
+    fn check_block_arrows(&mut self) {
+        for i in 0..self.tokens.len() {
+            if self.text(i) != Some("=>") {
+                continue;
+            }
+            let Some(open) = self.body(i) else {
+                continue;
+            };
+            let looks_like_object = matches!(
+                self.tokens.get(open + 1).map(|t| t.kind),
+                Some(Kind::Ident | Kind::Literal)
+            ) && self.text(open + 2) == Some(":");
+            if looks_like_object {
+                self.warn(
+                    open,
+                    "arrow function body is a block, not an object, so it returns undefined",
+                    "wrap the object in parentheses, e.g. x => ({ a: x })",
+                );
+            } else if self
+                .callee(i)
+                .is_some_and(|m| CALLBACK_METHODS.contains(&m))
+                && !self.returns(open)
+            {
+                self.warn(
+                    open,
+                    "arrow function body is a block without a return, so it returns undefined",
+                    "add a return, or remove the braces to return an expression",
+                );
+            }
+        }
+    }
+
+    /// Whether the assignment at `i` is in a condition: directly inside the parentheses of an `if`
+    /// or `while`, or in the expression body of an arrow function passed to a predicate method.
+    fn in_condition(&self, i: usize) -> bool {
+        let parent = self.parent[i];
+        if let Some(open) = parent
+            && self.text(open) == Some("(")
+            && open
+                .checked_sub(1)
+                .is_some_and(|keyword| matches!(self.text(keyword), Some("if" | "while")))
+        {
+            return true;
+        }
+        for j in (0..i).rev() {
+            if self.parent[j] != parent {
+                continue;
+            }
+            match self.text(j) {
+                Some("," | ";") => return false,
+                Some("=>") => {
+                    return self
+                        .callee(j)
+                        .is_some_and(|m| PREDICATE_METHODS.contains(&m));
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn check_assignments(&mut self) {
+        for i in 0..self.tokens.len() {
+            if self.tokens[i].kind == Kind::Punct
+                && self.text(i) == Some("=")
+                && self.in_condition(i)
+            {
+                self.warn(i, "assignment in a condition", "use === to compare values");
+            }
+        }
+    }
+
+    fn check_shadowing(&mut self) {
+        for i in 0..self.tokens.len() {
+            if self.tokens[i].kind != Kind::Ident || self.text(i) != Some("$") {
+                continue;
+            }
+            let prev = i.checked_sub(1).and_then(|p| self.text(p));
+            let next = self.text(i + 1);
+            let declared = matches!(prev, Some("let" | "const" | "var"));
+            let param = next == Some("=>")
+                || matches!(prev, Some("(" | "," | "..."))
+                    && matches!(next, Some("," | ")" | "="))
+                    && self.parent[i].is_some_and(|open| {
+                        self.text(open) == Some("(")
+                            && self.partner[open].is_some_and(|close| {
+                                let before = |n| open.checked_sub(n).and_then(|b| self.text(b));
+                                self.text(close + 1) == Some("=>")
+                                    || before(1) == Some("function")
+                                    || before(2) == Some("function")
+                            })
+                    });
+            if declared || param {
+                self.warn(
+                    i,
+                    "$ shadows the input",
+                    "use a different name, so that $ still refers to the input",
+                );
+            }
+        }
+    }
+}
+
+/// Check `script` for common mistakes, returning a warning for each, in order.
+pub fn lint(script: &str) -> Vec<Warning> {
+    let mut linter = Linter::new(script);
+    linter.check_assignments();
+    linter.check_block_arrows();
+    linter.check_shadowing();
+    linter
+        .warnings
+        .sort_by_key(|warning| (warning.line, warning.column));
+    linter.warnings
+}
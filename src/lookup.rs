@@ -0,0 +1,65 @@
+//! Reference data given with --lookup, indexed by --lookup-key, so that SCRIPT can join against it
+//! with `lookup.NAME.get(key)` without searching it for every input.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+
+use crate::{parse, stats};
+
+/// Parse a table of the form `NAME=PATH`, for clap.
+pub fn parse_table(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), path.to_string()))
+        }
+        _ => Err(String::from("expected a table of the form NAME=PATH")),
+    }
+}
+
+/// The records in `path`: the elements of an array, or each of a sequence of values, as in NDJSON.
+/// YAML and TOML are recognised by their extensions.
+fn records(path: &str) -> Result<Vec<Value>> {
+    let s = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let mut values = match extension.as_deref() {
+        Some("yaml" | "yml") => vec![serde_json::from_str(&parse::yaml(&s)?)?],
+        Some("toml") => vec![serde_json::from_str(&parse::toml(&s)?)?],
+        _ => serde_json::Deserializer::from_str(&s)
+            .into_iter()
+            .collect::<serde_json::Result<_>>()?,
+    };
+    Ok(match values.as_mut_slice() {
+        [Value::Array(records)] => std::mem::take(records),
+        _ => values,
+    })
+}
+
+/// The value at `key` in `record`, as a string, or `None` if there isn't a string, number, or
+/// boolean there.
+pub fn key(record: &Value, key: &str) -> Option<String> {
+    match record.get(key)? {
+        Value::String(s) => Some(s.clone()),
+        // As JavaScript would write them, so that `get(1)` finds a record with key 1.0.
+        Value::Number(n) => Some(stats::canonical_number(n).to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Index the records in `path` by the value at `key`, as a JSON object. Keys are compared as
+/// strings, records without `key` are left out, and where two records share a key, the later one
+/// wins.
+pub fn load(name: &str, path: &str, key: &str) -> Result<String> {
+    let mut index = Map::new();
+    for record in records(path).with_context(|| format!("loading --lookup {name}"))? {
+        if let Some(k) = self::key(&record, key) {
+            index.insert(k, record);
+        }
+    }
+    Ok(Value::Object(index).to_string())
+}
@@ -0,0 +1,351 @@
+use std::{cell::Cell, rc::Rc, time::Instant};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use mlua::{HookTriggers, Lua, LuaSerdeExt, Value};
+
+use crate::{
+    edit, limit, parse, print,
+    profile::{self, Stage},
+    safe::{self, Capability},
+};
+
+/// The language SCRIPT is written in.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Lang {
+    Js,
+    Lua,
+}
+
+/// Convert a JSON value to a Lua value. `null` becomes `mlua`'s null, and arrays are marked as
+/// such, so that both survive being converted back.
+fn to_lua<'lua>(lua: &'lua Lua, value: &serde_json::Value) -> mlua::Result<Value<'lua>> {
+    // Numbers can't go through serde, since with arbitrary precision they serialize as structs.
+    Ok(match value {
+        serde_json::Value::Null => lua.null(),
+        serde_json::Value::Bool(b) => Value::Boolean(*b),
+        serde_json::Value::Number(n) => n.as_i64().map_or_else(
+            || Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+            Value::Integer,
+        ),
+        serde_json::Value::String(s) => Value::String(lua.create_string(s)?),
+        serde_json::Value::Array(values) => {
+            let table = lua.create_sequence_from(
+                values
+                    .iter()
+                    .map(|value| to_lua(lua, value))
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?;
+            table.set_metatable(Some(lua.array_metatable()));
+            Value::Table(table)
+        }
+        serde_json::Value::Object(entries) => {
+            let table = lua.create_table()?;
+            for (k, v) in entries {
+                table.set(k.as_str(), to_lua(lua, v)?)?;
+            }
+            Value::Table(table)
+        }
+    })
+}
+
+fn from_json<'lua>(lua: &'lua Lua, json: &str) -> mlua::Result<Value<'lua>> {
+    to_lua(
+        lua,
+        &serde_json::from_str(json).map_err(mlua::Error::external)?,
+    )
+}
+
+fn to_json(lua: &Lua, value: Value) -> mlua::Result<String> {
+    let value: serde_json::Value = lua.from_value(value)?;
+    serde_json::to_string(&value).map_err(mlua::Error::external)
+}
+
+/// Register a table `name` with `parse` and `stringify` functions, like the JavaScript `JSON`.
+fn register_parse_and_stringify(
+    lua: &Lua,
+    name: &str,
+    parse: fn(&str) -> Result<String>,
+    print: fn(&str) -> Result<String>,
+) -> mlua::Result<()> {
+    let table = lua.create_table()?;
+    table.set(
+        "parse",
+        lua.create_function(move |lua, s: String| {
+            let json = parse(&s).map_err(|err| mlua::Error::external(format!("{err:#}")))?;
+            from_json(lua, &json)
+        })?,
+    )?;
+    table.set(
+        "stringify",
+        lua.create_function(move |lua, value: Value| {
+            print(&to_json(lua, value)?).map_err(|err| mlua::Error::external(format!("{err:#}")))
+        })?,
+    )?;
+    lua.globals().set(name, table)
+}
+
+/// A function which fails with `message`, for a helper which has been taken away.
+fn disabled(lua: &Lua, message: String) -> mlua::Result<mlua::Function<'_>> {
+    lua.create_function(move |_, ()| Err::<(), _>(mlua::Error::runtime(&message)))
+}
+
+/// The same helpers as are available to JavaScript, where they make sense in Lua: `read`, `write`,
+/// `print`, `env`, `lookup`, and `JSON`, `YAML`, and `TOML`. Variables given with --arg and
+/// --argjson are the fields of `arg`.
+fn register_helpers(
+    lua: &Lua,
+    env: impl Iterator<Item = (String, String)>,
+    vars: Vec<(String, String)>,
+    lookups: Vec<(String, String)>,
+) -> mlua::Result<()> {
+    let globals = lua.globals();
+
+    if safe::allowed(Capability::Fs) {
+        globals.set(
+            "read",
+            lua.create_function(|_, path: String| {
+                safe::check_read(&path).map_err(|err| mlua::Error::external(format!("{err:#}")))?;
+                std::fs::read_to_string(path).map_err(mlua::Error::external)
+            })?,
+        )?;
+        globals.set(
+            "write",
+            lua.create_function(|_, (path, value): (String, String)| {
+                edit::write(&path, &value).map_err(|err| mlua::Error::external(format!("{err:#}")))
+            })?,
+        )?;
+    } else {
+        for name in ["read", "write"] {
+            globals.set(name, disabled(lua, safe::denied(Capability::Fs, name))?)?;
+        }
+        // The standard library's own ways to touch files, run programs, and tell the time.
+        for name in ["io", "os", "package", "require", "dofile", "loadfile"] {
+            globals.set(name, Value::Nil)?;
+        }
+    }
+    if !safe::can_spawn() {
+        if let Ok(os) = globals.get::<_, mlua::Table>("os") {
+            os.set("execute", Value::Nil)?;
+        }
+        if let Ok(io) = globals.get::<_, mlua::Table>("io") {
+            io.set("popen", Value::Nil)?;
+        }
+    }
+    if !safe::allowed(Capability::Env)
+        && let Ok(os) = globals.get::<_, mlua::Table>("os")
+    {
+        os.set("getenv", Value::Nil)?;
+    }
+    if !safe::allowed(Capability::Env) && safe::allowed(Capability::Fs) {
+        // The standard library's ways to open files by name mustn't open /proc/self/environ.
+        let check = lua.create_function(|_, path: String| {
+            safe::check_read(&path).map_err(|err| mlua::Error::external(format!("{err:#}")))
+        })?;
+        lua.load(
+            "local check = ...
+            local function guard(t, name)
+                local f = t[name]
+                t[name] = function(path, ...)
+                    if type(path) == 'string' then check(path) end
+                    return f(path, ...)
+                end
+            end
+            for _, name in ipairs({ 'open', 'lines', 'input' }) do guard(io, name) end
+            for _, name in ipairs({ 'dofile', 'loadfile' }) do guard(_G, name) end",
+        )
+        .call::<_, ()>(check)?;
+    }
+    if safe::enabled() {
+        let message = String::from("math.random is disabled by --safe");
+        globals
+            .get::<_, mlua::Table>("math")?
+            .set("random", disabled(lua, message)?)?;
+    }
+    globals.set(
+        "print",
+        lua.create_function(|_, value: Value| {
+            print::text(&value.to_string()?)
+                .map_err(|err| mlua::Error::external(format!("{err:#}")))
+        })?,
+    )?;
+
+    register_parse_and_stringify(lua, "JSON", parse::json, |s| Ok(s.to_string()))?;
+    register_parse_and_stringify(lua, "YAML", parse::yaml, print::yaml_to_string)?;
+    register_parse_and_stringify(lua, "TOML", parse::toml, print::toml_to_string)?;
+
+    globals.set("env", lua.create_table_from(env)?)?;
+
+    let arg = lua.create_table()?;
+    for (k, v) in vars {
+        arg.set(k, from_json(lua, &v)?)?;
+    }
+    globals.set("arg", arg)?;
+
+    let lookup = lua.create_table()?;
+    for (name, json) in lookups {
+        let index = Rc::new(lua.create_registry_value(from_json(lua, &json)?)?);
+        let table = lua.create_table()?;
+        for (method, get) in [("get", true), ("has", false)] {
+            let index = Rc::clone(&index);
+            table.set(
+                method,
+                lua.create_function(move |lua, key: Value| {
+                    // Keys are strings, as in JavaScript, so get(1) and get("1") are the same.
+                    let index: mlua::Table = lua.registry_value(&index)?;
+                    let record = index.raw_get::<_, Value>(key.to_string()?)?;
+                    Ok(if get {
+                        record
+                    } else {
+                        Value::Boolean(!record.is_nil())
+                    })
+                })?,
+            )?;
+        }
+        lookup.set(name, table)?;
+    }
+    globals.set("lookup", lookup)?;
+
+    Ok(())
+}
+
+/// The most instructions SCRIPT may run for each input in safe mode, in place of the limit on loop
+/// iterations in JavaScript.
+const MAX_INSTRUCTIONS: u64 = 10 * safe::MAX_LOOP_ITERATIONS;
+
+/// How many instructions are run between counts towards [`MAX_INSTRUCTIONS`], and checks of
+/// --timeout.
+const HOOK_INTERVAL: u32 = 1000;
+
+/// Whether `err` is from Lua running out of the memory allowed by --max-memory, perhaps inside a
+/// helper.
+fn is_memory_error(err: &mlua::Error) -> bool {
+    match err {
+        mlua::Error::MemoryError(_) => true,
+        mlua::Error::CallbackError { cause, .. } => is_memory_error(cause),
+        _ => false,
+    }
+}
+
+/// Evaluates SCRIPT as Lua, rather than JavaScript, for when a small binary matters more than a
+/// familiar language. The input is available as `_`.
+pub struct Runtime {
+    lua: Lua,
+    parse: bool,
+    stringify: bool,
+    /// How many instructions SCRIPT has run for the current input, counted in safe mode.
+    instructions: Rc<Cell<u64>>,
+    /// When SCRIPT started running for the current input, for --timeout.
+    started: Rc<Cell<Instant>>,
+    /// Whether SCRIPT was stopped for the current input by --timeout.
+    timed_out: Rc<Cell<bool>>,
+}
+
+impl Runtime {
+    pub fn new(
+        env: impl Iterator<Item = (String, String)>,
+        vars: Vec<(String, String)>,
+        lookups: Vec<(String, String)>,
+        parse: bool,
+        stringify: bool,
+    ) -> Result<Self> {
+        let lua = Lua::new();
+        register_helpers(&lua, env, vars, lookups)?;
+        if let Some(max) = limit::limits().memory {
+            lua.set_memory_limit(usize::try_from(max).unwrap_or(usize::MAX))?;
+        }
+        let instructions = Rc::new(Cell::new(0));
+        let started = Rc::new(Cell::new(Instant::now()));
+        let timed_out = Rc::new(Cell::new(false));
+        let safe = safe::enabled();
+        let timeout = limit::limits().timeout;
+        if safe || timeout.is_some() {
+            let instructions = Rc::clone(&instructions);
+            let started = Rc::clone(&started);
+            let timed_out = Rc::clone(&timed_out);
+            lua.set_hook(
+                HookTriggers::new().every_nth_instruction(HOOK_INTERVAL),
+                move |_, _| {
+                    instructions.set(instructions.get() + u64::from(HOOK_INTERVAL));
+                    if safe && instructions.get() > MAX_INSTRUCTIONS {
+                        return Err(mlua::Error::runtime(format!(
+                            "SCRIPT ran more than the {MAX_INSTRUCTIONS} instructions allowed by \
+                            --safe"
+                        )));
+                    }
+                    if let Some(timeout) = timeout
+                        && started.get().elapsed() > timeout
+                    {
+                        timed_out.set(true);
+                        return Err(mlua::Error::runtime("--timeout"));
+                    }
+                    Ok(())
+                },
+            );
+        }
+        Ok(Self {
+            lua,
+            parse,
+            stringify,
+            instructions,
+            started,
+            timed_out,
+        })
+    }
+
+    pub fn eval(&mut self, script: &str, input: &str, filename: Option<&str>) -> Result<String> {
+        let lua = &self.lua;
+        self.instructions.set(0);
+
+        let jsq = lua.create_table()?;
+        jsq.set("filename", filename)?;
+        lua.globals().set("jsq", jsq)?;
+
+        let input = if self.parse {
+            profile::time(Stage::Convert, || from_json(lua, input))?
+        } else {
+            Value::String(lua.create_string(input)?)
+        };
+        lua.globals().set("_", input)?;
+
+        // Like the Lua REPL, SCRIPT is evaluated as an expression if it is one, and as a chunk of
+        // statements ending in a return otherwise.
+        let _evaluation = limit::checked_evaluation();
+        self.started.set(Instant::now());
+        self.timed_out.set(false);
+        let res = profile::time(Stage::Script, || {
+            let f = lua
+                .load(format!("return {script}"))
+                .set_name("SCRIPT")
+                .into_function()
+                .or_else(|_| lua.load(script).set_name("SCRIPT").into_function())?;
+            f.call::<_, Value>(())
+        })
+        // Tracebacks only point back in to SCRIPT, which is short enough to not need them.
+        .map_err(|err| {
+            let limits = limit::limits();
+            if let Some(timeout) = limits.timeout
+                && self.timed_out.get()
+            {
+                return limit::timeout_error(timeout);
+            }
+            if let Some(max) = limits.memory
+                && is_memory_error(&err)
+            {
+                return limit::memory_error(max);
+            }
+            let err = err.to_string();
+            anyhow!("{}", err.split("\nstack traceback:").next().unwrap_or(&err))
+        })?;
+
+        profile::time(Stage::Convert, || -> Result<String> {
+            Ok(match res {
+                // Like undefined in JavaScript, which isn't printed as JSON.
+                Value::Nil if self.stringify => String::from("undefined"),
+                Value::Table(_) => to_json(lua, res)?,
+                _ if self.stringify => to_json(lua, res)?,
+                _ => res.to_string()?,
+            })
+        })
+    }
+}
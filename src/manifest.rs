@@ -0,0 +1,43 @@
+//! Manifests of the files written with --to, written with --manifest, so that what a pipeline
+//! produced, and with which SCRIPT, can be checked later.
+
+use std::{fs::File, io};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+/// The SHA-256 of the file at `path`, in hex.
+fn sha256(path: &str) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(
+        &mut File::open(path).with_context(|| format!("reading {path}"))?,
+        &mut hasher,
+    )?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Write a manifest of `files`, which `records` results of `script` have just been written to, to
+/// `path`. Each file is listed with its size and SHA-256, which cover the whole file, even if it
+/// was appended to.
+pub fn write(path: &str, script: &str, files: &[String], records: usize) -> Result<()> {
+    let files = files
+        .iter()
+        .map(|file| {
+            Ok(json!({
+                "path": file,
+                "bytes": std::fs::metadata(file)
+                    .with_context(|| format!("reading {file}"))?
+                    .len(),
+                "sha256": sha256(file)?,
+                "records": records,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let manifest = json!({
+        "script": { "sha256": format!("{:x}", Sha256::digest(script)) },
+        "files": files,
+    });
+    let manifest = serde_json::to_string_pretty(&manifest)? + "\n";
+    std::fs::write(path, manifest).with_context(|| format!("writing {path}"))
+}
@@ -0,0 +1,68 @@
+use serde_json::Value;
+
+/// How to merge two arrays.
+#[derive(Clone)]
+pub enum Arrays {
+    /// The later array replaces the earlier one.
+    Replace,
+    /// The later array is appended to the earlier one.
+    Concat,
+    /// Objects with the same value at a key are merged, and any other elements are appended.
+    ByKey(String),
+}
+
+/// Parse a strategy for merging arrays: `replace`, `concat`, or `by-key:KEY`.
+pub fn parse_arrays(s: &str) -> Result<Arrays, String> {
+    match s {
+        "replace" => Ok(Arrays::Replace),
+        "concat" => Ok(Arrays::Concat),
+        _ => match s.strip_prefix("by-key:") {
+            Some(key) if !key.is_empty() => Ok(Arrays::ByKey(key.to_string())),
+            _ => Err(format!(
+                "unknown strategy {s}, expected replace, concat, or by-key:KEY"
+            )),
+        },
+    }
+}
+
+/// Deep-merge `b` in to `a`. Objects are merged key by key, arrays according to `arrays`, and any
+/// other value in `b` replaces the value in `a`.
+fn merge_into(a: &mut Value, b: Value, arrays: &Arrays) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, b) in b {
+                match a.get_mut(&key) {
+                    Some(a) => merge_into(a, b, arrays),
+                    None => {
+                        a.insert(key, b);
+                    }
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => match arrays {
+            Arrays::Replace => *a = b,
+            Arrays::Concat => a.extend(b),
+            Arrays::ByKey(key) => {
+                for b in b {
+                    let existing = b.get(key).and_then(|id| {
+                        a.iter_mut()
+                            .find(|a| a.is_object() && a.get(key) == Some(id))
+                    });
+                    match existing {
+                        Some(a) => merge_into(a, b, arrays),
+                        None => a.push(b),
+                    }
+                }
+            }
+        },
+        (a, b) => *a = b,
+    }
+}
+
+/// Deep-merge `values` from left to right, so that where they conflict, the rightmost wins.
+pub fn merge(values: impl IntoIterator<Item = Value>, arrays: &Arrays) -> Option<Value> {
+    values.into_iter().reduce(|mut a, b| {
+        merge_into(&mut a, b, arrays);
+        a
+    })
+}
@@ -0,0 +1,27 @@
+//! Large input files are mapped in to memory rather than read through a buffer, so that they're
+//! read straight from the page cache. With --stream they're never copied in full at all, and
+//! otherwise they're parsed straight from the map, unless they need decoding first.
+
+use std::fs::File;
+
+use memmap2::Mmap;
+
+/// Files smaller than this are read as usual, since mapping them saves next to nothing.
+const THRESHOLD: u64 = 16 << 20;
+
+/// Map `file` in to memory, if it's a regular file of at least [`THRESHOLD`] bytes which can be
+/// mapped, or `None` if it should be read as usual.
+pub fn map(file: &File) -> Option<Mmap> {
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() || metadata.len() < THRESHOLD {
+        return None;
+    }
+    // SAFETY: The map is only read. If the file is changed while it's mapped, what's read is
+    // garbled, as it would be reading it any other way, and if it's truncated, reading past the
+    // end is fatal, so --no-mmap is there for files which are being written to.
+    let map = unsafe { Mmap::map(file) }.ok()?;
+    // Input is read from start to end, so the OS may as well read ahead and drop what's behind.
+    #[cfg(unix)]
+    map.advise(memmap2::Advice::Sequential).ok();
+    Some(map)
+}
@@ -0,0 +1,175 @@
+use std::{
+    io::{self, ErrorKind, Write},
+    process::{Child, Command, Stdio},
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long output is held back with nothing more written, as when a stream arrives slowly, before
+/// it's written straight to STDOUT instead.
+const IDLE: Duration = Duration::from_millis(100);
+
+enum State {
+    /// Less than a screenful has been written so far, last at the given instant.
+    Buffering(Vec<u8>, usize, Instant),
+    Paging(Child),
+    /// The pager couldn't be started, so output goes straight to STDOUT.
+    Direct,
+    /// The pager has exited, so output is discarded.
+    Closed,
+}
+
+/// Holds output back until more than a screenful has been written, then starts a pager and writes
+/// to that instead. Output which fits on the screen is written to STDOUT when the pager finishes,
+/// or once nothing more has been written for `IDLE`.
+struct Pager {
+    command: String,
+    height: usize,
+    state: State,
+}
+
+impl Pager {
+    fn page(&mut self) -> io::Result<()> {
+        let State::Buffering(buf, ..) = std::mem::replace(&mut self.state, State::Direct) else {
+            return Ok(());
+        };
+        let mut words = self.command.split_whitespace();
+        let child = words.next().and_then(|program| {
+            Command::new(program)
+                .args(words)
+                .stdin(Stdio::piped())
+                .spawn()
+                .ok()
+        });
+        if let Some(child) = child {
+            self.state = State::Paging(child);
+        }
+        self.write_all(&buf)
+    }
+
+    /// Give up on paging, and write any output held back straight to STDOUT.
+    fn direct(&mut self) -> io::Result<()> {
+        let State::Buffering(buf, ..) = std::mem::replace(&mut self.state, State::Direct) else {
+            return Ok(());
+        };
+        let mut stdout = io::stdout();
+        stdout.write_all(&buf)?;
+        stdout.flush()
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        match &mut self.state {
+            State::Buffering(buf, ..) => io::stdout().write_all(buf),
+            State::Paging(child) => {
+                // Close STDIN so that the pager knows there's no more to come.
+                drop(child.stdin.take());
+                child.wait()?;
+                Ok(())
+            }
+            State::Direct | State::Closed => Ok(()),
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match &mut self.state {
+            State::Buffering(buf, lines, last) => {
+                buf.extend_from_slice(data);
+                *last = Instant::now();
+                #[expect(clippy::naive_bytecount)]
+                let newlines = data.iter().filter(|&&b| b == b'\n').count();
+                *lines += newlines;
+                if *lines >= self.height {
+                    self.page()?;
+                }
+            }
+            State::Paging(child) => {
+                let stdin = child.stdin.as_mut().expect("piped");
+                match stdin.write_all(data) {
+                    Err(err) if err.kind() == ErrorKind::BrokenPipe => self.state = State::Closed,
+                    res => res?,
+                }
+            }
+            State::Direct => io::stdout().write_all(data)?,
+            State::Closed => {}
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.state {
+            State::Paging(child) => match child.stdin.as_mut().expect("piped").flush() {
+                Err(err) if err.kind() == ErrorKind::BrokenPipe => {
+                    self.state = State::Closed;
+                    Ok(())
+                }
+                res => res,
+            },
+            State::Direct => io::stdout().flush(),
+            State::Buffering(..) | State::Closed => Ok(()),
+        }
+    }
+}
+
+static PAGER: Mutex<Option<Pager>> = Mutex::new(None);
+
+fn pager() -> MutexGuard<'static, Option<Pager>> {
+    PAGER.lock().expect("locking pager")
+}
+
+/// Send output through `command` once it's more than `height` lines long.
+pub fn start(command: String, height: usize) {
+    *pager() = Some(Pager {
+        command,
+        height,
+        state: State::Buffering(Vec::new(), 0, Instant::now()),
+    });
+    thread::spawn(|| {
+        loop {
+            thread::sleep(IDLE / 4);
+            let mut pager = pager();
+            let Some(pager) = pager.as_mut() else {
+                return;
+            };
+            match pager.state {
+                State::Buffering(.., last) if last.elapsed() < IDLE => {}
+                // If writing fails here, it fails again on the next write, which reports it.
+                State::Buffering(..) => {
+                    pager.direct().ok();
+                    return;
+                }
+                _ => return,
+            }
+        }
+    });
+}
+
+pub fn is_started() -> bool {
+    pager().is_some()
+}
+
+/// Write any output held back, and wait for the pager to exit if it was started.
+pub fn finish() -> io::Result<()> {
+    pager().take().map_or(Ok(()), Pager::finish)
+}
+
+/// Writes through the pager if it's been started, or straight to STDOUT otherwise.
+pub struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match pager().as_mut() {
+            Some(pager) => pager.write(data),
+            None => io::stdout().write(data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match pager().as_mut() {
+            Some(pager) => pager.flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
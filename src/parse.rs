@@ -1,23 +1,672 @@
-use anyhow::{Context, Result};
-use serde_json::Value;
+use std::{collections::HashSet, io::Cursor};
+
+use anyhow::{bail, Context, Result};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde_json::{Map, Value};
+
+use crate::{
+    diagnostic::{self, Diagnostic, Kind},
+    dupes::{self, Dupes},
+};
+
+const DUPES_HINT: &str = "use --dupes first or --dupes last to keep one of them";
 
 /// Parse JSON in to a JSON string.
 pub fn json(s: &str) -> Result<String> {
-    Ok(serde_json::from_str::<Value>(s)
+    json_with(s, Dupes::Last)
+}
+
+/// Parse JSON in to a JSON string, doing as `dupes` says with keys which appear more than once in
+/// an object.
+pub fn json_with(s: &str, dupes: Dupes) -> Result<String> {
+    #[cfg(feature = "simd")]
+    if dupes == Dupes::Last
+        && let Some(json) = crate::simd::json(s)
+    {
+        return Ok(json);
+    }
+    Ok(dupes::json(s, dupes)
+        .map_err(|err| {
+            // serde_json counts columns in bytes, up to and including the one it stopped at.
+            let offset = diagnostic::offset(s, err.line(), 1) + err.column().saturating_sub(1);
+            let diagnostic = Diagnostic::new(Kind::Parse, &err).excerpt(s, offset..offset + 1);
+            let message = err.to_string();
+            if message.starts_with("trailing characters") {
+                diagnostic.hint("to read more than one value, such as NDJSON, use --stream")
+            } else if message.starts_with("duplicate key") {
+                diagnostic.hint(DUPES_HINT)
+            } else {
+                diagnostic
+            }
+        })
         .context("parsing JSON")?
         .to_string())
 }
 
-/// Parse YAML in to a JSON string.
+/// Parse YAML in to a JSON string. Aliases are replaced by the values they refer to, and merge keys
+/// are merged in to the mappings they're in.
 pub fn yaml(s: &str) -> Result<String> {
-    Ok(serde_yaml::from_str::<Value>(s)
-        .context("parsing YAML")?
-        .to_string())
+    yaml_with(s, Dupes::Last)
+}
+
+/// Parse YAML in to a JSON string, doing as `dupes` says with keys which appear more than once in
+/// a mapping.
+pub fn yaml_with(s: &str, dupes: Dupes) -> Result<String> {
+    let mut value = dupes::yaml(s, dupes)
+        .map_err(|err| {
+            let location = err.location();
+            let message = err.to_string();
+            let mut diagnostic = Diagnostic::new(Kind::Parse, err);
+            if let Some(location) = location {
+                let offset = location.index();
+                diagnostic = diagnostic.excerpt(s, offset..offset + 1);
+            }
+            if message.starts_with("unknown anchor") {
+                diagnostic = diagnostic.hint("an alias *NAME must come after the anchor &NAME");
+            } else if message.contains("duplicate key") {
+                diagnostic = diagnostic.hint(DUPES_HINT);
+            }
+            diagnostic
+        })
+        .context("parsing YAML")?;
+    merge_keys(&mut value).context("parsing YAML")?;
+    Ok(value.to_string())
+}
+
+const MERGE_KEY_ERROR: &str =
+    "the value of a merge key << should be a mapping or a sequence of them";
+
+/// Merge the value of each `<<` key in `value`, a mapping or a sequence of them, in to the mapping
+/// it's in, as in <https://yaml.org/type/merge.html>. Keys the mapping has itself take precedence,
+/// then those of earlier mappings in the sequence. Merged keys take the place of the `<<`.
+fn merge_keys(value: &mut Value) -> Result<()> {
+    match value {
+        Value::Array(arr) => arr.iter_mut().try_for_each(merge_keys),
+        Value::Object(obj) => {
+            obj.values_mut().try_for_each(merge_keys)?;
+            if !obj.contains_key("<<") {
+                return Ok(());
+            }
+            let entries = std::mem::take(obj);
+            let own = entries
+                .keys()
+                .filter(|k| *k != "<<")
+                .cloned()
+                .collect::<HashSet<_>>();
+            for (k, v) in entries {
+                if k != "<<" {
+                    obj.insert(k, v);
+                    continue;
+                }
+                let sources = match v {
+                    Value::Object(source) => vec![source],
+                    Value::Array(sources) => sources
+                        .into_iter()
+                        .map(|source| match source {
+                            Value::Object(source) => Some(source),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>()
+                        .context(MERGE_KEY_ERROR)?,
+                    _ => bail!(MERGE_KEY_ERROR),
+                };
+                for (k, v) in sources.into_iter().flatten() {
+                    if !own.contains(&k) && !obj.contains_key(&k) {
+                        obj.insert(k, v);
+                    }
+                }
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
 }
 
 /// Parse TOML in to a JSON string.
 pub fn toml(s: &str) -> Result<String> {
-    Ok(toml::from_str::<Value>(s)
-        .context("parsing TOML")?
+    Ok(toml::from_str::<toml::Value>(s)
+        .map_err(|err| {
+            // The error's own message already has an excerpt, without the structure to render it.
+            let span = err.span();
+            let diagnostic = Diagnostic::new(Kind::Parse, err.message().replace('\n', ", "));
+            match span {
+                Some(span) => diagnostic.excerpt(s, span),
+                None => diagnostic,
+            }
+        })
+        .context("parsing TOML")
+        .map(from_toml)?
         .to_string())
 }
+
+/// The JSON value of the TOML value `value`. Dates and times become strings, as they're written.
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(n) => Value::from(n),
+        toml::Value::Float(n) => Value::from(n),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(date) => Value::String(date.to_string()),
+        toml::Value::Array(arr) => Value::Array(arr.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, from_toml(v)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+/// The index of the next character after `i` which isn't whitespace or part of a comment.
+fn skip_insignificant(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            (c, _) if c.is_whitespace() => i += 1,
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            _ => break,
+        }
+    }
+    i.min(chars.len())
+}
+
+/// Rewrite relaxed JSON as strict JSON: comments are removed (keeping line breaks, so that line
+/// numbers in errors still match the input), trailing commas are dropped, unquoted keys are quoted,
+/// and single-quoted strings are double-quoted.
+fn relax(s: &str) -> String {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut res = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' | '\'' => {
+                res.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    match chars[i] {
+                        '\\' if chars.get(i + 1) == Some(&'\'') => {
+                            res.push('\'');
+                            i += 1;
+                        }
+                        '\\' => {
+                            res.push('\\');
+                            if let Some(&escaped) = chars.get(i + 1) {
+                                res.push(escaped);
+                                i += 1;
+                            }
+                        }
+                        '"' => res.push_str("\\\""),
+                        other => res.push(other),
+                    }
+                    i += 1;
+                }
+                res.push('"');
+                i += 1;
+            }
+            '/' if matches!(chars.get(i + 1), Some('/' | '*')) => {
+                let end = skip_insignificant(&chars, i);
+                let lines = chars[i..end].iter().filter(|&&c| c == '\n').count();
+                res.push_str(&if lines == 0 {
+                    String::from(" ")
+                } else {
+                    "\n".repeat(lines)
+                });
+                i = end;
+            }
+            ',' if matches!(
+                chars.get(skip_insignificant(&chars, i + 1)),
+                Some(']' | '}')
+            ) =>
+            {
+                i += 1;
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                let word = chars[start..i].iter().collect::<String>();
+                if chars.get(skip_insignificant(&chars, i)) == Some(&':') {
+                    res.push_str(&serde_json::to_string(&word).expect("serializing string"));
+                } else {
+                    res.push_str(&word);
+                }
+            }
+            c => {
+                res.push(c);
+                i += 1;
+            }
+        }
+    }
+    res
+}
+
+/// Parse relaxed JSON, which may contain comments, trailing commas, unquoted keys, and
+/// single-quoted strings, in to a JSON string.
+pub fn lenient(s: &str, dupes: Dupes) -> Result<String> {
+    json_with(&relax(s), dupes)
+}
+
+/// A step along a path in gron, e.g. `.a` or `[0]`.
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Parse a line of gron, e.g. `json.a[0]["b c"] = true;`, in to the steps of the path it assigns
+/// to and the value it assigns, or where the line goes wrong and why.
+fn gron_line(line: &str) -> Result<(Vec<Step>, Value), (usize, &'static str)> {
+    let mut i = line.find(|c| !is_ident(c)).unwrap_or(line.len());
+    if i == 0 {
+        return Err((0, "expected a path such as json.a[0]"));
+    }
+    let mut steps = Vec::new();
+    loop {
+        let rest = &line[i..];
+        if let Some(r) = rest.strip_prefix('.') {
+            let len = r.find(|c| !is_ident(c)).unwrap_or(r.len());
+            if len == 0 {
+                return Err((i + 1, "expected a key"));
+            }
+            steps.push(Step::Key(r[..len].to_string()));
+            i += 1 + len;
+        } else if let Some(r) = rest.strip_prefix('[') {
+            let (step, len) = if r.starts_with('"') {
+                let mut keys = serde_json::Deserializer::from_str(r).into_iter::<String>();
+                let Some(Ok(key)) = keys.next() else {
+                    return Err((i + 1, "expected a key as a JSON string"));
+                };
+                (Step::Key(key), keys.byte_offset())
+            } else {
+                let len = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+                let Ok(index) = r[..len].parse() else {
+                    return Err((i + 1, "expected an index or a key"));
+                };
+                (Step::Index(index), len)
+            };
+            if !r[len..].starts_with(']') {
+                return Err((i + 1 + len, "expected ]"));
+            }
+            steps.push(step);
+            i += len + 2;
+        } else {
+            break;
+        }
+    }
+
+    let rest = line[i..].trim_start();
+    let Some(rest) = rest.strip_prefix('=') else {
+        return Err((line.len() - rest.len(), "expected ="));
+    };
+    let value = rest.trim();
+    let value = value.strip_suffix(';').unwrap_or(value);
+    let value = serde_json::from_str(value).map_err(|_| {
+        (
+            line.len() - rest.trim_start().len(),
+            "expected a JSON value",
+        )
+    })?;
+    Ok((steps, value))
+}
+
+/// The most nulls filled in before an index past the end of an array in gron, so that an index
+/// such as `json[99999999999]` fails rather than exhausting memory.
+const MAX_GRON_GAP: usize = 1 << 20;
+
+/// Assign `value` to the path `steps` into `target`, making objects and arrays along the way as
+/// needed. Gaps in arrays are filled with null, up to [`MAX_GRON_GAP`] of them.
+fn gron_assign(target: &mut Value, steps: &[Step], value: Value) -> Result<(), String> {
+    let Some((step, steps)) = steps.split_first() else {
+        // An empty object or array only declares what's there, which may already be filled in.
+        let declared = match &value {
+            Value::Object(obj) => obj.is_empty() && target.is_object(),
+            Value::Array(arr) => arr.is_empty() && target.is_array(),
+            _ => false,
+        };
+        if !declared {
+            *target = value;
+        }
+        return Ok(());
+    };
+    match step {
+        Step::Key(key) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            let Value::Object(obj) = target else {
+                unreachable!("target is an object by construction");
+            };
+            gron_assign(obj.entry(key).or_insert(Value::Null), steps, value)
+        }
+        Step::Index(index) => {
+            if !target.is_array() {
+                *target = Value::Array(Vec::new());
+            }
+            let Value::Array(arr) = target else {
+                unreachable!("target is an array by construction");
+            };
+            if arr.len() <= *index {
+                if *index - arr.len() > MAX_GRON_GAP {
+                    return Err(format!(
+                        "index {index} is more than {MAX_GRON_GAP} past the end of the array"
+                    ));
+                }
+                arr.resize(index + 1, Value::Null);
+            }
+            gron_assign(&mut arr[*index], steps, value)
+        }
+    }
+}
+
+/// Parse gron, lines of the form `json.a[0] = 1;` as printed by the tool of the same name, in to
+/// a JSON string, so that gron which has been grepped or edited can be turned back in to JSON.
+pub fn gron(s: &str) -> Result<String> {
+    let mut res = Value::Null;
+    let mut offset = 0;
+    for (i, line) in s.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let line = line.trim_end();
+        if line.trim_start().is_empty() {
+            continue;
+        }
+        let (steps, value) = gron_line(line)
+            .map_err(|(at, message)| {
+                Diagnostic::new(Kind::Parse, format!("{message} at line {}", i + 1))
+                    .excerpt(s, start + at..start + at + 1)
+            })
+            .context("parsing gron")?;
+        gron_assign(&mut res, &steps, value)
+            .map_err(|message| {
+                Diagnostic::new(Kind::Parse, format!("{message} at line {}", i + 1))
+                    .excerpt(s, start..start + line.len())
+            })
+            .context("parsing gron")?;
+    }
+    Ok(res.to_string())
+}
+
+/// The path of keys named by the header of an INI section, between the brackets, such as `a.b`,
+/// or `remote "origin"` as git writes it. Names with spaces or dots can be quoted as JSON strings.
+fn ini_section(header: &str) -> Result<Vec<String>, (usize, &'static str)> {
+    let mut keys = Vec::new();
+    let mut i = header.len() - header.trim_start().len();
+    loop {
+        let rest = &header[i..];
+        if rest.starts_with('"') {
+            let mut names = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+            let Some(Ok(name)) = names.next() else {
+                return Err((i, "expected a section name as a JSON string"));
+            };
+            keys.push(name);
+            i += names.byte_offset();
+        } else {
+            let len = rest
+                .find(|c: char| c == '.' || c == '"' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            if len == 0 {
+                return Err((i, "expected a section name"));
+            }
+            keys.push(rest[..len].to_string());
+            i += len;
+        }
+        let rest = header[i..].trim_start();
+        i = header.len() - rest.len();
+        if rest.is_empty() {
+            return Ok(keys);
+        } else if let Some(rest) = rest.strip_prefix('.') {
+            i = header.len() - rest.trim_start().len();
+        }
+    }
+}
+
+/// Parse INI in to a JSON string. Each section is an object, nested by its path, and each value is
+/// a string, since INI has no other types, except that a key with no value is true, as in git
+/// config. A key given more than once in a section is an array of its values.
+pub fn ini(s: &str) -> Result<String> {
+    let mut res = Map::new();
+    let mut section = Vec::new();
+    let mut offset = 0;
+    for (i, line) in s.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        ini_line(&mut res, &mut section, line.trim_end())
+            .map_err(|(at, message)| {
+                Diagnostic::new(Kind::Parse, format!("{message} at line {}", i + 1))
+                    .excerpt(s, start + at..start + at + 1)
+            })
+            .context("parsing INI")?;
+    }
+    Ok(Value::Object(res).to_string())
+}
+
+/// Read a line of INI in to `res`, where `section` is the path of the section it's in, and is
+/// changed if the line starts a new one.
+fn ini_line(
+    res: &mut Map<String, Value>,
+    section: &mut Vec<String>,
+    line: &str,
+) -> Result<(), (usize, &'static str)> {
+    let content = line.trim_start();
+    let indent = line.len() - content.len();
+    if content.is_empty() || content.starts_with(';') || content.starts_with('#') {
+        return Ok(());
+    }
+
+    if let Some(header) = content.strip_prefix('[') {
+        let Some(header) = header.strip_suffix(']') else {
+            return Err((line.len(), "expected ]"));
+        };
+        *section = ini_section(header).map_err(|(at, message)| (indent + 1 + at, message))?;
+        return match ini_table(res, section) {
+            Some(_) => Ok(()),
+            None => Err((
+                indent,
+                "expected a new section, but this one is already a value",
+            )),
+        };
+    }
+
+    let (key, rest) = if content.starts_with('"') {
+        let mut keys = serde_json::Deserializer::from_str(content).into_iter::<String>();
+        let Some(Ok(key)) = keys.next() else {
+            return Err((indent, "expected a key as a JSON string"));
+        };
+        (key, &content[keys.byte_offset()..])
+    } else {
+        let len = content.find(['=', ':']).unwrap_or(content.len());
+        (content[..len].trim_end().to_string(), &content[len..])
+    };
+    if key.is_empty() {
+        return Err((indent, "expected a key"));
+    }
+    let rest = rest.trim_start();
+    let value = if rest.is_empty() {
+        Value::Bool(true)
+    } else if let Some(value) = rest.strip_prefix(['=', ':']) {
+        let value = value.trim();
+        match serde_json::from_str(value) {
+            Ok(Value::String(s)) if value.starts_with('"') => Value::String(s),
+            _ => Value::String(value.to_string()),
+        }
+    } else {
+        return Err((line.len() - rest.len(), "expected ="));
+    };
+
+    let table = ini_table(res, section).expect("section was made when it started");
+    match table.get_mut(&key) {
+        None => {
+            table.insert(key, value);
+        }
+        Some(Value::Array(values)) => values.push(value),
+        Some(Value::Object(_)) => {
+            return Err((indent, "expected a key, but this one is a section"))
+        }
+        Some(first) => *first = Value::Array(vec![first.take(), value]),
+    }
+    Ok(())
+}
+
+/// The object at `path` in `res`, made if it isn't there yet, or `None` if something other than an
+/// object is in the way.
+fn ini_table<'a>(
+    mut res: &'a mut Map<String, Value>,
+    path: &[String],
+) -> Option<&'a mut Map<String, Value>> {
+    for key in path {
+        res = res
+            .entry(key)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()?;
+    }
+    Some(res)
+}
+
+/// Read the variable on the line of a .env file starting at `start`, if there is one, and return
+/// it along with where the next line starts. A quoted value may span lines.
+#[expect(clippy::type_complexity)]
+fn env_line(
+    s: &str,
+    start: usize,
+) -> Result<(Option<(String, String)>, usize), (usize, &'static str)> {
+    let end_of_line = |i: usize| s[i..].find('\n').map_or(s.len(), |n| i + n + 1);
+    let line = &s[start..end_of_line(start)];
+    let content = line.trim();
+    if content.is_empty() || content.starts_with('#') {
+        return Ok((None, start + line.len()));
+    }
+
+    let mut i = start + line.len() - line.trim_start().len();
+    if let Some(rest) = s[i..].strip_prefix("export ") {
+        i = s.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+    let len = s[i..]
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '-')))
+        .unwrap_or(s.len() - i);
+    if len == 0 {
+        return Err((i, "expected a name"));
+    }
+    let key = s[i..i + len].to_string();
+    i = s.len() - s[i + len..].trim_start_matches([' ', '\t']).len();
+    let Some(rest) = s[i..].strip_prefix('=') else {
+        return Err((i, "expected ="));
+    };
+    i = s.len() - rest.trim_start_matches([' ', '\t']).len();
+
+    let mut value = String::new();
+    let quote = s[i..].chars().next().filter(|c| matches!(c, '\'' | '"'));
+    if let Some(quote) = quote {
+        let open = i;
+        let mut chars = s[i + 1..].char_indices();
+        loop {
+            let Some((j, c)) = chars.next() else {
+                return Err((open, "expected a closing quote"));
+            };
+            if c == quote {
+                i += 1 + j + 1;
+                break;
+            }
+            if c == '\\' && quote == '"' {
+                match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c)) => value.push(c),
+                    None => return Err((open, "expected a closing quote")),
+                }
+            } else {
+                value.push(c);
+            }
+        }
+        let next = end_of_line(i);
+        let rest = s[i..next].trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err((i, "expected the end of the line"));
+        }
+        Ok((Some((key, value)), next))
+    } else {
+        let next = end_of_line(i);
+        // An unquoted value ends at a comment, which must follow a space.
+        let raw = &s[i..next];
+        let raw = raw.find(" #").map_or(raw, |n| &raw[..n]);
+        value.push_str(raw.trim());
+        Ok((Some((key, value)), next))
+    }
+}
+
+/// Parse a .env file, `KEY=value` lines such as docker compose reads, in to a JSON string: an
+/// object with a string for each variable. Values may be single quoted, and taken as they are, or
+/// double quoted, with escapes such as `\n`. A variable set more than once keeps its last value.
+pub fn env(s: &str) -> Result<String> {
+    let mut res = Map::new();
+    let mut i = 0;
+    while i < s.len() {
+        let (var, next) = env_line(s, i)
+            .map_err(|(at, message)| {
+                let line = s[..at].matches('\n').count() + 1;
+                Diagnostic::new(Kind::Parse, format!("{message} at line {line}"))
+                    .excerpt(s, at..at + 1)
+            })
+            .context("parsing env")?;
+        if let Some((key, value)) = var {
+            res.insert(key, Value::String(value));
+        }
+        i = next;
+    }
+    Ok(Value::Object(res).to_string())
+}
+
+/// Parse a property list, XML or binary, in to a JSON string. Dates become RFC 3339 strings, and
+/// data becomes base64 strings, since JSON has neither.
+pub fn plist(bytes: &[u8]) -> Result<String> {
+    // Anything which isn't binary is read as XML, so that errors say what's wrong with it as XML.
+    let value = if bytes.starts_with(b"bplist") {
+        plist::Value::from_reader(Cursor::new(bytes))
+    } else {
+        plist::Value::from_reader_xml(bytes)
+    };
+    let value = value
+        .map_err(|err| Diagnostic::new(Kind::Parse, err))
+        .context("parsing plist")?;
+    Ok(from_plist(value).to_string())
+}
+
+/// The JSON value of the property list value `value`.
+fn from_plist(value: plist::Value) -> Value {
+    match value {
+        plist::Value::Array(arr) => Value::Array(arr.into_iter().map(from_plist).collect()),
+        plist::Value::Dictionary(dict) => Value::Object(
+            dict.into_iter()
+                .map(|(k, v)| (k, from_plist(v)))
+                .collect::<Map<_, _>>(),
+        ),
+        plist::Value::Boolean(b) => Value::Bool(b),
+        plist::Value::Data(data) => Value::String(BASE64_STANDARD.encode(data)),
+        plist::Value::Date(date) => Value::String(date.to_xml_format()),
+        plist::Value::Real(n) => Value::from(n),
+        plist::Value::Integer(n) => match n.as_signed() {
+            Some(n) => Value::from(n),
+            None => Value::from(n.as_unsigned()),
+        },
+        plist::Value::String(s) => Value::String(s),
+        plist::Value::Uid(uid) => Value::from(uid.get()),
+        _ => Value::Null,
+    }
+}
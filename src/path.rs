@@ -0,0 +1,85 @@
+//! Shorthand for SCRIPT and FN which just pick out part of a value, e.g. `.items[0].name`, or
+//! `.users[].email` to iterate over an array, in the style of jq.
+
+use std::fmt::Write as _;
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Split a path in to the accessors between each `[]`, translated to JavaScript, or `None` if `s`
+/// isn't a path.
+fn parse(s: &str) -> Option<Vec<String>> {
+    let s = s.trim();
+    if !s.starts_with('.') {
+        return None;
+    }
+    if s == "." {
+        return Some(vec![String::new()]);
+    }
+
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('.') {
+            if r.starts_with(is_ident_start) {
+                let len = r.find(|c| !is_ident(c)).unwrap_or(r.len());
+                part.push('.');
+                part.push_str(&r[..len]);
+                rest = &r[len..];
+            } else if r.starts_with('[') {
+                rest = r;
+            } else {
+                return None;
+            }
+        } else if let Some(r) = rest.strip_prefix("[]") {
+            parts.push(std::mem::take(&mut part));
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']')?;
+            let index = &r[..end];
+            // An index, or a key as a JSON string, which can't contain `]`.
+            if index.parse::<usize>().is_err() && serde_json::from_str::<String>(index).is_err() {
+                return None;
+            }
+            write!(part, "[{index}]").expect("writing to a String");
+            rest = &r[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    parts.push(part);
+    Some(parts)
+}
+
+/// Translate a path to JavaScript which reads it from `$`, or `None` if `s` isn't a path. Each
+/// `[]` maps over the array before it, and the results are flattened so that `.a[].b[]` is a
+/// single array.
+fn translate(s: &str) -> Option<String> {
+    let parts = parse(s)?;
+    let mut res = format!("${}", parts[0]);
+    let last = parts.len() - 1;
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        if i < last {
+            write!(res, ".flatMap(x => x{part})").expect("writing to a String");
+        } else if !part.is_empty() {
+            write!(res, ".map(x => x{part})").expect("writing to a String");
+        }
+    }
+    Some(res)
+}
+
+/// Expand SCRIPT if it's a path, or leave it as it is otherwise.
+pub fn script(s: String) -> String {
+    translate(&s).unwrap_or(s)
+}
+
+/// Expand FN if it's a path, or leave it as it is otherwise.
+pub fn function(s: String) -> String {
+    translate(&s).map_or(s, |body| format!("$ => {body}"))
+}
@@ -0,0 +1,76 @@
+//! Formats implemented by external programs. The plugin for a format `NAME` is an executable on
+//! `$PATH` called `jsq-format-NAME`. Run as `jsq-format-NAME decode`, it reads input in the format
+//! on STDIN and writes it as JSON to STDOUT. Run as `jsq-format-NAME encode`, it reads a result as
+//! JSON on STDIN and writes it in the format to STDOUT.
+
+use std::{
+    io::{ErrorKind, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{parse, safe};
+
+/// Whether `name` can be the name of a plugin format, rather than a path or something else.
+pub fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parse the name of a plugin format, for clap.
+pub fn parse(name: &str) -> Result<String, String> {
+    if is_name(name) {
+        Ok(name.to_string())
+    } else {
+        Err(format!("invalid format name {name}"))
+    }
+}
+
+/// Run the plugin for `format` in `mode`, passing it `input` on STDIN, and return its STDOUT.
+fn run(format: &str, mode: &str, input: Vec<u8>) -> Result<Vec<u8>> {
+    let program = format!("jsq-format-{format}");
+    safe::forbid(&format!("running {program}"))?;
+    let mut child = Command::new(&program)
+        .arg(mode)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running {program}"))?;
+
+    // Input is written from another thread, so that a plugin which writes output before it has
+    // read all its input can't fill its STDOUT and block.
+    let mut stdin = child.stdin.take().context("getting stdin")?;
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    let written = writer.join().expect("joining writer");
+
+    if !output.status.success() {
+        bail!(
+            "{program} {mode} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    // A plugin which succeeds without reading all its input didn't need the rest.
+    if let Err(err) = written
+        && err.kind() != ErrorKind::BrokenPipe
+    {
+        return Err(err).with_context(|| format!("writing to {program}"));
+    }
+    Ok(output.stdout)
+}
+
+/// Convert `input` in `format` to JSON.
+pub fn decode(format: &str, input: Vec<u8>) -> Result<String> {
+    let json = String::from_utf8(run(format, "decode", input)?)?;
+    parse::json(&json).with_context(|| format!("parsing output of jsq-format-{format}"))
+}
+
+/// Convert the JSON `json` to `format`.
+pub fn encode(format: &str, json: &str) -> Result<Vec<u8>> {
+    run(format, "encode", json.as_bytes().to_vec())
+}
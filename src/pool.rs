@@ -0,0 +1,215 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    boa::{Options, Runtime, Step},
+    diagnostic,
+    profile::{self, Stage},
+    safe, stats,
+    undefined::Undefined,
+};
+
+/// What evaluating SCRIPT for a single input produces.
+pub enum Output {
+    /// Results to be printed: one, or any number if SCRIPT spreads its result.
+    Print(Vec<String>),
+    /// Values to be added to the aggregate, each paired with its key.
+    Aggregate(Vec<(String, String)>),
+}
+
+/// The work to be done for each input: evaluate SCRIPT, then optionally apply the aggregation
+/// function to the result.
+#[derive(Clone)]
+pub struct Task {
+    /// SCRIPT, and any steps after it, each evaluated for the result of the one before.
+    pub steps: Vec<Step>,
+    pub aggregate: Option<String>,
+}
+
+impl Task {
+    /// SCRIPT as a whole, with each step on a line of its own.
+    pub fn script(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| step.script.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn eval(
+        &self,
+        runtime: &mut Runtime,
+        filename: Option<&str>,
+        input: &str,
+    ) -> Result<Output> {
+        // A diagnostic is kept whole, so that it can be rendered in full.
+        let res = runtime
+            .reset()
+            .and_then(|()| runtime.eval_steps(&self.steps, input, filename))
+            .map_err(|err| match diagnostic::find(&err) {
+                Some(_) => err,
+                None => anyhow!("{err}"),
+            });
+
+        let output = res.and_then(|results| {
+            let Some(f) = &self.aggregate else {
+                return Ok(Output::Print(results));
+            };
+            profile::time(Stage::Aggregate, || {
+                let mut values = Vec::new();
+                for res in &results {
+                    for value in stats::spread(res)? {
+                        let key = runtime.apply(f, &value).map_err(|err| anyhow!("{err}"))?;
+                        values.push((key, value));
+                    }
+                }
+                Ok(Output::Aggregate(values))
+            })
+        });
+
+        label(filename, output)
+    }
+}
+
+/// Label an error with the name of the file it relates to, if there is one.
+pub fn label<T>(filename: Option<&str>, res: Result<T>) -> Result<T> {
+    match filename {
+        Some(filename) => res.with_context(|| filename.to_owned()),
+        None => res,
+    }
+}
+
+struct Job {
+    index: usize,
+    filename: Option<String>,
+    input: String,
+}
+
+/// A pool of worker threads, each with its own runtime, which evaluate a task for each submitted
+/// input. Outputs are received in the order that inputs were submitted.
+pub struct Pool {
+    jobs: Option<SyncSender<Job>>,
+    results: Receiver<(usize, Result<Output>)>,
+    submitted: usize,
+    received: usize,
+    pending: BTreeMap<usize, Result<Output>>,
+}
+
+impl Pool {
+    #[expect(clippy::too_many_arguments)]
+    pub fn new(
+        threads: usize,
+        task: &Task,
+        vars: &[(String, String)],
+        lookups: &[(String, String)],
+        init: Option<&(String, String)>,
+        parse: bool,
+        stringify: bool,
+        undefined: Undefined,
+    ) -> Self {
+        // Bound the queue so that we don't read input far ahead of the workers.
+        let (jobs, jobs_receiver) = mpsc::sync_channel::<Job>(threads * 2);
+        let jobs_receiver = Arc::new(Mutex::new(jobs_receiver));
+        let (results_sender, results) = mpsc::channel();
+
+        for _ in 0..threads {
+            let jobs = Arc::clone(&jobs_receiver);
+            let results = results_sender.clone();
+            let task = task.clone();
+            let vars = vars.to_vec();
+            let lookups = lookups.to_vec();
+            let init = init.cloned();
+            thread::spawn(move || {
+                let mut runtime = Runtime::new(Options {
+                    env: safe::env(),
+                    vars,
+                    lookups,
+                    init,
+                    parse,
+                    stringify,
+                    undefined,
+                });
+                loop {
+                    let Ok(job) = jobs.lock().expect("locking job queue").recv() else {
+                        break;
+                    };
+                    let output = match &mut runtime {
+                        Ok(runtime) => task.eval(runtime, job.filename.as_deref(), &job.input),
+                        Err(err) => Err(anyhow!("{err:#}")),
+                    };
+                    if results.send((job.index, output)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Self {
+            jobs: Some(jobs),
+            results,
+            submitted: 0,
+            received: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Queue an input for evaluation, blocking if the queue is full.
+    pub fn submit(&mut self, filename: Option<&str>, input: String) -> Result<()> {
+        self.jobs
+            .as_ref()
+            .context("submitting to a closed pool")?
+            .send(Job {
+                index: self.submitted,
+                filename: filename.map(String::from),
+                input,
+            })
+            .map_err(|_| anyhow!("worker threads exited unexpectedly"))?;
+        self.submitted += 1;
+        Ok(())
+    }
+
+    /// Signal that there are no more inputs, so that workers exit once the queue is empty.
+    pub fn close(&mut self) {
+        self.jobs = None;
+    }
+
+    /// The next output, if it's ready.
+    pub fn try_recv(&mut self) -> Option<Result<Output>> {
+        self.next(false)
+    }
+
+    /// The next output, blocking until it's ready, or `None` if every output has been received.
+    pub fn recv(&mut self) -> Option<Result<Output>> {
+        self.next(true)
+    }
+
+    fn next(&mut self, block: bool) -> Option<Result<Output>> {
+        while self.received < self.submitted {
+            if let Some(output) = self.pending.remove(&self.received) {
+                self.received += 1;
+                return Some(output);
+            }
+            let (index, output) = if block {
+                let Ok(result) = self.results.recv() else {
+                    // No output still to come will ever arrive, so report this once and stop.
+                    self.received = self.submitted;
+                    self.pending.clear();
+                    return Some(Err(anyhow!("worker threads exited unexpectedly")));
+                };
+                result
+            } else {
+                self.results.try_recv().ok()?
+            };
+            self.pending.insert(index, output);
+        }
+        None
+    }
+}
@@ -0,0 +1,282 @@
+use std::{collections::HashMap, fmt::Write, ops::Range};
+
+/// Marks a string which stands in for a `BigInt`: either an integer in input which is too large to
+/// be a double exactly, or a `BigInt` in a result.
+pub const BIGINT: &str = "\u{10FFFF}bigint:";
+
+/// Numbers in JavaScript are doubles, so long decimals lose precision when input is evaluated. This
+/// records the original text of each such number in some JSON, so that it can be restored wherever
+/// the number is passed through to the result unchanged. Integers are `BigInt`s instead.
+#[derive(Default)]
+pub struct Numbers {
+    /// The original text of each number by the bits of the double it becomes, or `None` if
+    /// several numbers become the same double.
+    originals: HashMap<u64, Option<String>>,
+    /// The bits of the double and the original text of each number, by its JSON pointer.
+    at: HashMap<String, (u64, String)>,
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+/// The number literals in `s` which aren't part of a string or an identifier, with their positions.
+fn literals(s: &str) -> Vec<(usize, &str)> {
+    let mut res = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    let mut prev = None;
+    while let Some((i, c)) = chars.next() {
+        if c == '"' {
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => break,
+                    _ => {}
+                }
+            }
+        } else if (c == '-' || c.is_ascii_digit()) && !prev.is_some_and(is_ident) {
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, c)) = chars.peek() {
+                let exponent_sign = matches!(c, '+' | '-') && s[..j].ends_with(['e', 'E']);
+                if !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E') || exponent_sign) {
+                    break;
+                }
+                end = j + 1;
+                chars.next();
+            }
+            if s[i..end].ends_with(|c: char| c.is_ascii_digit()) {
+                res.push((i, &s[i..end]));
+            }
+            prev = s[..end].chars().next_back();
+            continue;
+        }
+        prev = Some(c);
+    }
+    res
+}
+
+fn is_integer(text: &str) -> bool {
+    !text.contains(['.', 'e', 'E'])
+}
+
+/// Whether the number `text` can't be represented exactly as the double `n`.
+fn is_lossy(text: &str, n: f64) -> bool {
+    canonical(text) != canonical(&format!("{n:e}"))
+}
+
+/// Replace each integer in `json` which is too large to be a double exactly with a string marked
+/// with [`BIGINT`], or return `None` if there aren't any.
+pub fn mark_bigints(json: &str) -> Option<String> {
+    let mut res = String::new();
+    let mut last = 0;
+    for (i, text) in literals(json) {
+        if is_integer(text) && text.parse().is_ok_and(|n| is_lossy(text, n)) {
+            res.push_str(&json[last..i]);
+            res.push('"');
+            res.push_str(BIGINT);
+            res.push_str(text);
+            res.push('"');
+            last = i + text.len();
+        }
+    }
+    if last == 0 {
+        return None;
+    }
+    res.push_str(&json[last..]);
+    Some(res)
+}
+
+/// Replace each string marked with [`BIGINT`] in `json` with the integer it stands for.
+pub fn unmark_bigints(json: String) -> String {
+    let marker = format!("\"{BIGINT}");
+    if !json.contains(&marker) {
+        return json;
+    }
+    let mut res = String::new();
+    let mut rest = json.as_str();
+    while let Some(i) = rest.find(&marker) {
+        let after = &rest[i + marker.len()..];
+        let len = after
+            .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+            .unwrap_or(after.len());
+        if after[len..].starts_with('"') {
+            res.push_str(&rest[..i]);
+            res.push_str(&after[..len]);
+            rest = &after[len + 1..];
+        } else {
+            res.push_str(&rest[..i + marker.len()]);
+            rest = after;
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// The significant digits of the number `text` and the power of ten they're multiplied by, as in
+/// 0.DIGITS × 10^EXPONENT, so that equal numbers have equal canonical forms.
+fn canonical(text: &str) -> Option<(bool, String, i64)> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{int}{frac}");
+    let leading = digits.len() - digits.trim_start_matches('0').len();
+    let digits = digits.trim_matches('0');
+    if digits.is_empty() {
+        return Some((false, String::new(), 0));
+    }
+    let exponent = exponent + i64::try_from(int.len()).ok()? - i64::try_from(leading).ok()?;
+    Some((negative, digits.to_string(), exponent))
+}
+
+/// Where in some JSON a value is, as a JSON pointer.
+#[derive(Clone, Copy)]
+enum Frame {
+    Array(usize),
+    Object,
+}
+
+/// The numbers in the JSON `json`, each with its JSON pointer and its position.
+fn numbers(json: &str) -> Vec<(String, Range<usize>)> {
+    let mut res = Vec::new();
+    // Each container the current value is in, with the length of the pointer to it.
+    let mut stack: Vec<(Frame, usize)> = Vec::new();
+    let mut pointer = String::new();
+    let bytes = json.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => {
+                stack.push((Frame::Object, pointer.len()));
+                i += 1;
+            }
+            b'[' => {
+                stack.push((Frame::Array(0), pointer.len()));
+                pointer.push_str("/0");
+                i += 1;
+            }
+            b'}' | b']' => {
+                if let Some((_, len)) = stack.pop() {
+                    pointer.truncate(len);
+                }
+                i += 1;
+            }
+            b',' => {
+                if let Some((frame, len)) = stack.last_mut() {
+                    pointer.truncate(*len);
+                    if let Frame::Array(index) = frame {
+                        *index += 1;
+                        write!(pointer, "/{index}").expect("writing to a string");
+                    }
+                }
+                i += 1;
+            }
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+                let end = i.min(bytes.len());
+                let rest = json[end..].trim_start();
+                if rest.starts_with(':')
+                    && let Some((_, len)) = stack.last()
+                {
+                    // A key, rather than a value.
+                    let key = serde_json::from_str::<String>(&json[start..end]).unwrap_or_default();
+                    pointer.truncate(*len);
+                    pointer.push('/');
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = i;
+                while i < bytes.len()
+                    && matches!(bytes[i], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                {
+                    i += 1;
+                }
+                res.push((pointer.clone(), start..i));
+            }
+            _ => i += 1,
+        }
+    }
+    res
+}
+
+impl Numbers {
+    /// Record the numbers in `json` which lose precision as doubles.
+    pub fn new(json: &str) -> Self {
+        let mut originals = HashMap::new();
+        let mut at = HashMap::new();
+        let mut exact = Vec::new();
+        for (pointer, range) in numbers(json) {
+            let text = &json[range];
+            let Ok(n) = text.parse::<f64>() else {
+                continue;
+            };
+            if !is_lossy(text, n) {
+                exact.push(n);
+                continue;
+            }
+            // Integers which lose precision are BigInts instead.
+            if is_integer(text) {
+                continue;
+            }
+            at.insert(pointer, (n.to_bits(), text.to_string()));
+            originals
+                .entry(n.to_bits())
+                .and_modify(|original: &mut Option<String>| {
+                    if original.as_deref() != Some(text) {
+                        *original = None;
+                    }
+                })
+                .or_insert_with(|| Some(text.to_string()));
+        }
+        // A number which doesn't lose precision can't be told apart from one which does and
+        // becomes the same double, unless it's where the original was.
+        if !originals.is_empty() {
+            for n in exact {
+                if let Some(original) = originals.get_mut(&n.to_bits()) {
+                    *original = None;
+                }
+            }
+        }
+        Self { originals, at }
+    }
+
+    /// Replace each number in the JSON `json` which became one of those recorded with its
+    /// original text: the one from the same place in the input if there was one, or otherwise the
+    /// only one which became that double.
+    pub fn restore(&self, json: String) -> String {
+        if self.originals.is_empty() {
+            return json;
+        }
+        let mut res = String::new();
+        let mut last = 0;
+        for (pointer, range) in numbers(&json) {
+            let Ok(n) = json[range.clone()].parse::<f64>() else {
+                continue;
+            };
+            let original = match self.at.get(&pointer) {
+                Some((bits, text)) if *bits == n.to_bits() => Some(text.as_str()),
+                _ => self.originals.get(&n.to_bits()).and_then(Option::as_deref),
+            };
+            if let Some(original) = original {
+                res.push_str(&json[last..range.start]);
+                res.push_str(original);
+                last = range.end;
+            }
+        }
+        res.push_str(&json[last..]);
+        res
+    }
+}
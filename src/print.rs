@@ -1,10 +1,206 @@
-use std::{io::IsTerminal, sync::LazyLock};
+use std::{
+    collections::HashMap,
+    fmt::{self, Write as _},
+    io::{self, IsTerminal, Write as _},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
 
-use anyhow::{bail, Error, Result};
-use serde_json::Value;
-use termcolor::{Color, ColorChoice, ColorSpec, NoColor, StandardStream, WriteColor};
+use anyhow::{bail, ensure, Context, Error, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use serde_json::{Map, Value};
+use similar::{ChangeTag, TextDiff};
+use termcolor::{
+    Ansi, Buffer, BufferedStandardStream, Color, ColorChoice, ColorSpec, NoColor, StandardStream,
+    WriteColor,
+};
 
-const TAB_WIDTH: usize = 2;
+use crate::{
+    diagnostic::{self, Diagnostic, Kind},
+    diff, lint, pager, plugin, profile, stats, suite,
+};
+
+/// How to print the value of a field in YAML and TOML, for parsers which expect a particular type.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TypeHint {
+    /// A string which is a date or time, written unquoted so that it's read as one.
+    Date,
+    /// A number which is written without a fraction, and mustn't have one.
+    Integer,
+    /// An array or object which is written on one line, rather than as a block or a table.
+    Inline,
+}
+
+impl TypeHint {
+    /// Parse a hint: `date`, `integer`, or `inline`.
+    ///
+    /// # Errors
+    ///
+    /// If `s` isn't a hint.
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "date" => Self::Date,
+            "integer" => Self::Integer,
+            "inline" => Self::Inline,
+            _ => bail!("unknown type hint {s}, expected date, integer, or inline"),
+        })
+    }
+}
+
+/// Format specific options which control how values are printed.
+#[expect(clippy::struct_excessive_bools)]
+#[derive(Clone)]
+pub struct Options {
+    pub json_indent: usize,
+    pub json_tabs: bool,
+    pub yaml_indent: usize,
+    pub toml_inline_threshold: usize,
+    /// Whether to print strings which are RFC 3339 dates as TOML dates, rather than as strings.
+    pub toml_datetimes: bool,
+    /// What to join the keys of nested objects with when flattening them to env variable names.
+    pub env_separator: String,
+    /// Whether to print property lists in Apple's binary format, rather than as XML.
+    pub plist_binary: bool,
+    /// Whether to print strings which are RFC 3339 dates as property list dates.
+    pub plist_dates: bool,
+    pub types: bool,
+    /// The width of the terminal, to right-align a count of the children of each array and object
+    /// to, or `None` to not count them.
+    pub counts: Option<usize>,
+    /// How deep to print arrays and objects as JSON or YAML before collapsing them to `[…]` and
+    /// `{…}`.
+    pub depth: Option<usize>,
+    /// How many characters of each string to print before cutting it short with `…`.
+    pub max_string: Option<usize>,
+    /// How many elements to print from the start of a result which is an array.
+    pub head: Option<usize>,
+    /// How many elements to print from the end of a result which is an array.
+    pub tail: Option<usize>,
+    /// Highlight keys and values which match, when printing as JSON or YAML.
+    pub grep: Option<Regex>,
+    pub sort_keys: bool,
+    pub ascii: bool,
+    /// How to print values in YAML, TOML, and property lists, by the name of their field, or by a
+    /// JSON Pointer to them, starting with `/`, in which `*` stands for any key or index.
+    pub type_hints: HashMap<String, TypeHint>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            json_indent: 2,
+            json_tabs: false,
+            yaml_indent: 2,
+            toml_inline_threshold: 1,
+            toml_datetimes: false,
+            env_separator: String::from("_"),
+            plist_binary: false,
+            plist_dates: false,
+            types: false,
+            counts: None,
+            depth: None,
+            max_string: None,
+            head: None,
+            tail: None,
+            grep: None,
+            sort_keys: false,
+            ascii: false,
+            type_hints: HashMap::new(),
+        }
+    }
+}
+
+impl Options {
+    /// These options without those which cut results short or annotate them for reading, for
+    /// writing results to files in full.
+    #[must_use]
+    pub fn for_files(&self) -> Self {
+        Self {
+            types: false,
+            counts: None,
+            grep: None,
+            depth: None,
+            max_string: None,
+            head: None,
+            tail: None,
+            ..self.clone()
+        }
+    }
+
+    /// Set an option from a string of the form `FORMAT.KEY=VALUE`.
+    ///
+    /// # Errors
+    ///
+    /// If `opt` isn't of that form, or isn't an option.
+    pub fn set(&mut self, opt: &str) -> Result<()> {
+        let (key, value) = opt
+            .split_once('=')
+            .context("expected an option of the form FORMAT.KEY=VALUE")?;
+        match key {
+            "json.indent" => self.json_indent = value.parse()?,
+            "json.tabs" => self.json_tabs = value.parse()?,
+            "yaml.indent" => {
+                let indent = value.parse()?;
+                // Sequence entries need room for "- ", and block scalar indentation indicators are
+                // a single digit.
+                ensure!(
+                    (2..=9).contains(&indent),
+                    "yaml.indent must be between 2 and 9"
+                );
+                self.yaml_indent = indent;
+            }
+            "toml.inline-threshold" => self.toml_inline_threshold = value.parse()?,
+            "toml.datetimes" => self.toml_datetimes = value.parse()?,
+            "env.separator" => self.env_separator = value.to_string(),
+            "plist.binary" => self.plist_binary = value.parse()?,
+            "plist.dates" => self.plist_dates = value.parse()?,
+            _ => bail!("unknown option {key}"),
+        }
+        Ok(())
+    }
+
+    /// The hint for the value at `pointer`, which is the value of the field `key` if it's in an
+    /// object: the hint for a pattern which matches `pointer`, or failing that for `key`.
+    fn type_hint(&self, pointer: &str, key: Option<&str>) -> Option<TypeHint> {
+        if self.type_hints.is_empty() {
+            return None;
+        }
+        // The most specific pattern wins, with the fewest wildcards.
+        self.type_hints
+            .iter()
+            .filter(|(pattern, _)| pattern.starts_with('/') && matches_pointer(pattern, pointer))
+            .min_by_key(|(pattern, _)| (pattern.matches("/*").count(), *pattern))
+            .map(|(_, hint)| *hint)
+            .or_else(|| key.and_then(|key| self.type_hints.get(key).copied()))
+    }
+
+    /// The JSON Pointer to the value at `segment`, a key or index, of the value at `pointer`, if
+    /// there are any type hints to look up by it.
+    fn child(&self, pointer: &str, segment: impl fmt::Display) -> String {
+        if self.type_hints.is_empty() {
+            String::new()
+        } else {
+            let segment = segment.to_string().replace('~', "~0").replace('/', "~1");
+            format!("{pointer}/{segment}")
+        }
+    }
+}
+
+/// Whether the JSON Pointer `pointer` matches `pattern`, in which `*` stands for any key or index.
+fn matches_pointer(pattern: &str, pointer: &str) -> bool {
+    let (mut pattern, mut pointer) = (pattern.split('/'), pointer.split('/'));
+    loop {
+        match (pattern.next(), pointer.next()) {
+            (None, None) => return true,
+            (Some(p), Some(s)) if p == s || p == "*" => {}
+            _ => return false,
+        }
+    }
+}
 
 fn normal(color: Color) -> ColorSpec {
     let mut spec = ColorSpec::new();
@@ -18,10 +214,96 @@ fn bold(color: Color) -> ColorSpec {
     spec
 }
 
-static KEY: LazyLock<ColorSpec> = LazyLock::new(|| normal(Color::Blue));
-static STR: LazyLock<ColorSpec> = LazyLock::new(|| normal(Color::Green));
-static HEADER: LazyLock<ColorSpec> = LazyLock::new(|| bold(Color::Blue));
-static ERR: LazyLock<ColorSpec> = LazyLock::new(|| bold(Color::Red));
+/// The colors used for each kind of output.
+pub struct Theme {
+    pub key: ColorSpec,
+    pub string: ColorSpec,
+    pub number: ColorSpec,
+    pub boolean: ColorSpec,
+    pub null: ColorSpec,
+    pub header: ColorSpec,
+    pub annotation: ColorSpec,
+    pub error: ColorSpec,
+    pub warning: ColorSpec,
+    pub added: ColorSpec,
+    pub removed: ColorSpec,
+    pub highlight: ColorSpec,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let mut annotation = ColorSpec::new();
+        annotation.set_dimmed(true);
+        let mut highlight = bold(Color::Magenta);
+        highlight.set_underline(true);
+        Self {
+            key: normal(Color::Blue),
+            string: normal(Color::Green),
+            number: ColorSpec::new(),
+            boolean: ColorSpec::new(),
+            null: ColorSpec::new(),
+            header: bold(Color::Blue),
+            annotation,
+            error: bold(Color::Red),
+            warning: bold(Color::Yellow),
+            added: normal(Color::Green),
+            removed: normal(Color::Red),
+            highlight,
+        }
+    }
+}
+
+impl Theme {
+    /// Set colors from a string of the form `KIND=STYLE:KIND=STYLE...`, where each `STYLE` is a
+    /// space separated list of a color and any of bold, dimmed, italic, or underline.
+    fn set(&mut self, colors: &str) -> Result<()> {
+        for entry in colors.split(':').filter(|entry| !entry.is_empty()) {
+            let (kind, style) = entry
+                .split_once('=')
+                .context("expected an entry of the form KIND=STYLE")?;
+            let spec = match kind {
+                "key" => &mut self.key,
+                "string" => &mut self.string,
+                "number" => &mut self.number,
+                "boolean" => &mut self.boolean,
+                "null" => &mut self.null,
+                "header" => &mut self.header,
+                "annotation" => &mut self.annotation,
+                "error" => &mut self.error,
+                "warning" => &mut self.warning,
+                "added" => &mut self.added,
+                "removed" => &mut self.removed,
+                "highlight" => &mut self.highlight,
+                _ => bail!("unknown kind {kind}"),
+            };
+            *spec = ColorSpec::new();
+            for word in style.split_whitespace() {
+                match word {
+                    "bold" => spec.set_bold(true),
+                    "dimmed" => spec.set_dimmed(true),
+                    "italic" => spec.set_italic(true),
+                    "underline" => spec.set_underline(true),
+                    _ => spec.set_fg(Some(word.parse().with_context(|| format!("in {entry}"))?)),
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Override the default colors from now on. See [`Theme::set`] for the format of `colors`.
+pub fn set_theme(colors: &str) -> Result<()> {
+    let mut theme = Theme::default();
+    theme.set(colors)?;
+    THEME.set(theme).ok();
+    Ok(())
+}
+
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
 
 macro_rules! write_with_color {
     ($dst:expr, $color:expr, $($arg:tt)*) => {
@@ -31,46 +313,373 @@ macro_rules! write_with_color {
     };
 }
 
+pub(crate) use write_with_color;
+
+/// Write a number, boolean, or null in its color, if it has one.
+pub fn write_scalar(w: &mut impl WriteColor, value: &Value) -> io::Result<()> {
+    let spec = match value {
+        Value::Number(_) => &theme().number,
+        Value::Bool(_) => &theme().boolean,
+        _ => &theme().null,
+    };
+    if spec.is_none() {
+        write!(w, "{value}")
+    } else {
+        write_with_color!(w, spec, "{value}")
+    }
+}
+
+/// A format to print results to a file in.
+#[derive(Clone)]
+pub enum Format {
+    Json,
+    /// JSON on a single line, so that results accumulate as NDJSON.
+    Ndjson,
+    /// JSON on a single line after an RS, so that results accumulate as an RFC 7464 JSON text
+    /// sequence.
+    JsonSeq,
+    Yaml,
+    /// A YAML document for each element of an array, each starting with `---`.
+    YamlDocs,
+    /// RFC 8785 canonical JSON, on a single line.
+    Jcs,
+    Toml,
+    Gron,
+    Ini,
+    Env,
+    Plist,
+    /// A format implemented by a plugin.
+    Plugin(String),
+}
+
+/// Each built-in format, by name.
+pub const FORMATS: [(&str, Format); 11] = [
+    ("json", Format::Json),
+    ("ndjson", Format::Ndjson),
+    ("json-seq", Format::JsonSeq),
+    ("yaml", Format::Yaml),
+    ("yaml-docs", Format::YamlDocs),
+    ("jcs", Format::Jcs),
+    ("toml", Format::Toml),
+    ("gron", Format::Gron),
+    ("ini", Format::Ini),
+    ("env", Format::Env),
+    ("plist", Format::Plist),
+];
+
+/// Parse an output of the form `FORMAT=FILE`, for clap.
+pub fn parse_output(s: &str) -> Result<(Format, String), String> {
+    let (format, file) = s
+        .split_once('=')
+        .ok_or_else(|| String::from("expected an output of the form FORMAT=FILE"))?;
+    let format = match FORMATS.iter().find(|(name, _)| *name == format) {
+        Some((_, format)) => format.clone(),
+        None if plugin::is_name(format) => Format::Plugin(format.to_string()),
+        None => return Err(format!("unknown format {format}")),
+    };
+    Ok((format, file.to_string()))
+}
+
+/// Print a result in `format`.
+pub fn format(w: &mut impl WriteColor, opts: &Options, format: &Format, s: &str) -> Result<()> {
+    match format {
+        Format::Json => json(w, opts, s).context("printing JSON"),
+        Format::Ndjson => json_line(w, opts, s).context("printing NDJSON"),
+        Format::JsonSeq => json_seq(w, opts, s).context("printing JSON text sequence"),
+        Format::Yaml => yaml(w, opts, s).context("printing YAML"),
+        Format::YamlDocs => yaml_docs(w, opts, s).context("printing YAML documents"),
+        Format::Jcs => jcs(w, opts, s).context("printing canonical JSON"),
+        Format::Toml => toml(w, opts, s).context("printing TOML"),
+        Format::Gron => gron(w, opts, s).context("printing gron"),
+        Format::Ini => ini(w, opts, s).context("printing INI"),
+        Format::Env => env(w, opts, s).context("printing env"),
+        Format::Plist => plist(w, opts, s).context("printing plist"),
+        Format::Plugin(name) => Ok(w.write_all(&plugin::encode(name, s)?)?),
+    }
+}
+
+/// When to print in color.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ColorWhen {
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR: OnceLock<ColorWhen> = OnceLock::new();
+
+/// Print in color according to `when` from now on, rather than only to terminals.
+pub fn set_color(when: ColorWhen) {
+    COLOR.set(when).ok();
+}
+
 fn color_choice(t: &impl IsTerminal) -> ColorChoice {
-    if t.is_terminal() {
-        ColorChoice::Auto
+    match COLOR.get().copied().unwrap_or(ColorWhen::Auto) {
+        ColorWhen::Always => ColorChoice::Always,
+        ColorWhen::Never => ColorChoice::Never,
+        ColorWhen::Auto => {
+            if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                ColorChoice::Never
+            } else if std::env::var_os("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0")
+            {
+                ColorChoice::Always
+            } else if t.is_terminal() {
+                ColorChoice::Auto
+            } else {
+                ColorChoice::Never
+            }
+        }
+    }
+}
+
+pub fn type_name(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("null"),
+        Value::Bool(_) => String::from("boolean"),
+        Value::Number(_) => String::from("number"),
+        Value::String(_) => String::from("string"),
+        Value::Array(arr) => {
+            let mut types = arr.iter().map(type_name);
+            let elem = match types.next() {
+                Some(t) if types.all(|u| u == t) => t,
+                _ => String::from("any"),
+            };
+            format!("{elem}[{}]", arr.len())
+        }
+        Value::Object(_) => String::from("object"),
+    }
+}
+
+/// Whether a value is printed over multiple lines, in which case its type annotation goes on the
+/// first line.
+fn is_branch(value: &Value) -> bool {
+    match value {
+        Value::Array(arr) => !arr.is_empty(),
+        Value::Object(obj) => !obj.is_empty(),
+        _ => false,
+    }
+}
+
+/// The highlight color if `s` matches --grep, or `spec` otherwise.
+fn highlight<'a>(opts: &Options, spec: &'a ColorSpec, s: &str) -> &'a ColorSpec {
+    if opts.grep.as_ref().is_some_and(|re| re.is_match(s)) {
+        &theme().highlight
+    } else {
+        spec
+    }
+}
+
+/// Write a number, boolean, or null, highlighted if it matches --grep.
+fn write_leaf(w: &mut impl WriteColor, opts: &Options, value: &Value) -> Result<()> {
+    let s = value.to_string();
+    if opts.grep.as_ref().is_some_and(|re| re.is_match(&s)) {
+        write_with_color!(w, theme().highlight, "{s}")?;
+    } else {
+        write_scalar(w, value)?;
+    }
+    Ok(())
+}
+
+/// Whether a value is deeper than --depth, and so is printed as `[…]` or `{…}` rather than in full.
+fn is_collapsed(opts: &Options, depth: usize, value: &Value) -> bool {
+    is_branch(value) && opts.depth.is_some_and(|max| depth >= max)
+}
+
+/// Write a collapsed array or object.
+fn write_collapsed(w: &mut impl WriteColor, value: &Value) -> Result<()> {
+    write!(w, "{}", if value.is_array() { "[…]" } else { "{…}" })?;
+    Ok(())
+}
+
+fn write_type(w: &mut impl WriteColor, opts: &Options, comment: &str, value: &Value) -> Result<()> {
+    if opts.types {
+        write_with_color!(w, theme().annotation, "  {comment} {}", type_name(value))?;
+    }
+    Ok(())
+}
+
+/// Write the number of children of an array or object at the end of the line, aligned to the right
+/// edge of the terminal.
+fn write_count<W: WriteColor>(
+    w: &mut Columns<W>,
+    opts: &Options,
+    comment: &str,
+    value: &Value,
+) -> Result<()> {
+    let Some(width) = opts.counts else {
+        return Ok(());
+    };
+    let (n, noun) = match value {
+        Value::Array(arr) => (arr.len(), "item"),
+        Value::Object(obj) => (obj.len(), "key"),
+        _ => return Ok(()),
+    };
+    let count = format!("{comment} {n} {noun}{}", if n == 1 { "" } else { "s" });
+    let padding = width
+        .saturating_sub(w.column + count.chars().count())
+        .max(2);
+    write!(w, "{:padding$}", "")?;
+    write_with_color!(w, theme().annotation, "{count}")?;
+    Ok(())
+}
+
+/// A writer which keeps track of how many characters are on the current line, so that counts can
+/// be aligned to the right edge of the terminal. Colors don't count, since they're set on the
+/// underlying writer.
+struct Columns<W> {
+    w: W,
+    column: usize,
+}
+
+impl<W> Columns<W> {
+    fn new(w: W) -> Self {
+        Self { w, column: 0 }
+    }
+}
+
+impl<W: io::Write> io::Write for Columns<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.w.write(buf)?;
+        let written = String::from_utf8_lossy(&buf[..n]);
+        match written.rsplit_once('\n') {
+            Some((_, line)) => self.column = line.chars().count(),
+            None => self.column += written.chars().count(),
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for Columns<W> {
+    fn supports_color(&self) -> bool {
+        self.w.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.w.set_color(spec)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.w.reset()
+    }
+}
+
+fn json_indentation(opts: &Options, depth: usize) -> String {
+    if opts.json_tabs {
+        "\t".repeat(depth)
     } else {
-        ColorChoice::Never
+        " ".repeat(depth * opts.json_indent)
     }
 }
 
-fn write_json(w: &mut impl WriteColor, depth: usize, value: &Value) -> Result<()> {
+fn write_json<W: WriteColor>(
+    w: &mut Columns<W>,
+    opts: &Options,
+    depth: usize,
+    value: &Value,
+) -> Result<()> {
     match value {
+        _ if is_collapsed(opts, depth, value) => write_collapsed(w, value)?,
         Value::Array(arr) => {
             write!(w, "[")?;
+            if is_branch(value) {
+                write_type(w, opts, "//", value)?;
+                write_count(w, opts, "//", value)?;
+            }
             for (i, e) in arr.iter().enumerate() {
-                write!(w, "\n{}", " ".repeat((depth + 1) * TAB_WIDTH))?;
-                write_json(w, depth + 1, e)?;
-                if i == arr.len() - 1 {
-                    write!(w, "\n{}", " ".repeat(depth * TAB_WIDTH))?;
-                } else {
+                write!(w, "\n{}", json_indentation(opts, depth + 1))?;
+                write_json(w, opts, depth + 1, e)?;
+                if i != arr.len() - 1 {
                     write!(w, ",")?;
                 }
+                if !is_branch(e) || is_collapsed(opts, depth + 1, e) {
+                    write_type(w, opts, "//", e)?;
+                }
+                if i == arr.len() - 1 {
+                    write!(w, "\n{}", json_indentation(opts, depth))?;
+                }
             }
             write!(w, "]")?;
         }
         Value::Object(obj) => {
             write!(w, "{{")?;
+            if is_branch(value) {
+                write_type(w, opts, "//", value)?;
+                write_count(w, opts, "//", value)?;
+            }
             for (i, (k, v)) in obj.iter().enumerate() {
-                write!(w, "\n{}", " ".repeat((depth + 1) * TAB_WIDTH))?;
-                write_with_color!(w, KEY, "{}", Value::String(k.clone()))?;
+                write!(w, "\n{}", json_indentation(opts, depth + 1))?;
+                write_with_color!(
+                    w,
+                    highlight(opts, &theme().key, k),
+                    "{}",
+                    json_string(opts, k)
+                )?;
                 write!(w, ": ")?;
-                write_json(w, depth + 1, v)?;
+                write_json(w, opts, depth + 1, v)?;
+                if i != obj.len() - 1 {
+                    write!(w, ",")?;
+                }
+                if !is_branch(v) || is_collapsed(opts, depth + 1, v) {
+                    write_type(w, opts, "//", v)?;
+                }
                 if i == obj.len() - 1 {
-                    write!(w, "\n{}", " ".repeat(depth * TAB_WIDTH))?;
-                } else {
+                    write!(w, "\n{}", json_indentation(opts, depth))?;
+                }
+            }
+            write!(w, "}}")?;
+        }
+        Value::String(s) => write_with_color!(
+            w,
+            highlight(opts, &theme().string, s),
+            "{}",
+            json_string(opts, s)
+        )?,
+        _ => write_leaf(w, opts, value)?,
+    }
+    Ok(())
+}
+
+/// Write a JSON value without any whitespace.
+fn write_json_line(w: &mut impl WriteColor, opts: &Options, value: &Value) -> Result<()> {
+    match value {
+        Value::Array(arr) => {
+            write!(w, "[")?;
+            for (i, e) in arr.iter().enumerate() {
+                if i != 0 {
+                    write!(w, ",")?;
+                }
+                write_json_line(w, opts, e)?;
+            }
+            write!(w, "]")?;
+        }
+        Value::Object(obj) => {
+            write!(w, "{{")?;
+            for (i, (k, v)) in obj.iter().enumerate() {
+                if i != 0 {
                     write!(w, ",")?;
                 }
+                write_with_color!(
+                    w,
+                    highlight(opts, &theme().key, k),
+                    "{}",
+                    json_string(opts, k)
+                )?;
+                write!(w, ":")?;
+                write_json_line(w, opts, v)?;
             }
             write!(w, "}}")?;
         }
-        Value::String(_) => write_with_color!(w, STR, "{value}")?,
-        _ => write!(w, "{value}")?,
+        Value::String(s) => write_with_color!(
+            w,
+            highlight(opts, &theme().string, s),
+            "{}",
+            json_string(opts, s)
+        )?,
+        _ => write_leaf(w, opts, value)?,
     }
     Ok(())
 }
@@ -79,8 +688,36 @@ fn quote(s: &str) -> String {
     Value::String(s.to_string()).to_string()
 }
 
-fn yaml_flow_string(s: &str) -> String {
-    if s.starts_with(char::is_whitespace)
+/// Quote `s` with each non-ASCII character escaped as `\uXXXX`, or if it doesn't fit, as a
+/// surrogate pair for JSON or as `\UXXXXXXXX` for YAML and TOML.
+fn ascii_quote(s: &str, json: bool) -> String {
+    let mut res = String::new();
+    for c in quote(s).chars() {
+        if c.is_ascii() {
+            res.push(c);
+        } else if json || c.len_utf16() == 1 {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                write!(res, "\\u{unit:04x}").expect("writing to a String");
+            }
+        } else {
+            write!(res, "\\U{:08x}", u32::from(c)).expect("writing to a String");
+        }
+    }
+    res
+}
+
+fn json_string(opts: &Options, s: &str) -> String {
+    if opts.ascii {
+        ascii_quote(s, true)
+    } else {
+        quote(s)
+    }
+}
+
+fn yaml_flow_string(opts: &Options, s: &str) -> String {
+    if opts.ascii && !s.is_ascii() {
+        ascii_quote(s, false)
+    } else if s.starts_with(char::is_whitespace)
         || s.ends_with(char::is_whitespace)
         // Indicator characters
         || s.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
@@ -98,40 +735,187 @@ fn yaml_flow_string(s: &str) -> String {
     }
 }
 
-fn yaml_block_string(depth: usize, s: &str) -> String {
+fn yaml_block_string(opts: &Options, depth: usize, s: &str) -> String {
     let mut res = String::from("|");
     if s.starts_with(char::is_whitespace) {
-        res.push_str(&format!("{TAB_WIDTH}"));
+        write!(res, "{}", opts.yaml_indent).expect("writing to a String");
     }
     for line in s.lines() {
-        res.push_str(&format!("\n{}{}", " ".repeat(depth * TAB_WIDTH), line));
+        write!(res, "\n{}{}", " ".repeat(depth * opts.yaml_indent), line)
+            .expect("writing to a String");
     }
     res
 }
 
-fn yaml_string(depth: usize, s: &str) -> String {
-    if s.contains('\n') && !s.contains(|c: char| c.is_control() && c != '\n') {
-        yaml_block_string(depth, s)
+fn yaml_string(opts: &Options, depth: usize, s: &str) -> String {
+    if s.contains('\n')
+        && !s.contains(|c: char| c.is_control() && c != '\n')
+        && (s.is_ascii() || !opts.ascii)
+    {
+        yaml_block_string(opts, depth, s)
+    } else {
+        yaml_flow_string(opts, s)
+    }
+}
+
+/// The date `s`, if it's hinted to be one, or an error if it isn't a date.
+fn hinted_date(hint: Option<TypeHint>, s: &str) -> Result<Option<toml::value::Datetime>> {
+    if hint != Some(TypeHint::Date) {
+        return Ok(None);
+    }
+    let date = s.parse().map_err(|_| {
+        Diagnostic::new(Kind::Convert, format!("can't print {} as a date", quote(s)))
+            .hint("dates are written as in RFC 3339, e.g. 1979-05-27T07:32:00Z")
+    })?;
+    Ok(Some(date))
+}
+
+/// The integer `n`, if it's hinted to be one, or an error if it has a fraction.
+fn hinted_integer(hint: Option<TypeHint>, n: &serde_json::Number) -> Result<Option<String>> {
+    if hint != Some(TypeHint::Integer) {
+        return Ok(None);
+    }
+    let integer = stats::canonical_number(n).to_string();
+    if !integer.contains(['.', 'e', 'E']) {
+        return Ok(Some(integer));
+    }
+    // Floats too large to be integers exactly still have no fraction, and are printed in full.
+    match n.as_f64() {
+        Some(f) if f.is_finite() && f.fract() == 0.0 => Ok(Some(format!("{f:.0}"))),
+        _ => Err(Diagnostic::new(Kind::Convert, format!("can't print {n} as an integer")).into()),
+    }
+}
+
+/// Write a value on one line, in YAML's flow style.
+fn write_yaml_flow(
+    w: &mut impl WriteColor,
+    opts: &Options,
+    pointer: &str,
+    hint: Option<TypeHint>,
+    value: &Value,
+) -> Result<()> {
+    // Within a flow collection, these characters would end a plain string.
+    let string = |s: &str| {
+        let flow = yaml_flow_string(opts, s);
+        if flow == s && s.contains([',', '[', ']', '{', '}']) {
+            quote(s)
+        } else {
+            flow
+        }
+    };
+    match value {
+        Value::Array(arr) => {
+            write!(w, "[")?;
+            for (i, e) in arr.iter().enumerate() {
+                if i > 0 {
+                    write!(w, ", ")?;
+                }
+                let pointer = opts.child(pointer, i);
+                write_yaml_flow(w, opts, &pointer, opts.type_hint(&pointer, None), e)?;
+            }
+            write!(w, "]")?;
+        }
+        Value::Object(obj) => {
+            write!(w, "{{")?;
+            for (i, (k, v)) in obj.iter().enumerate() {
+                if i > 0 {
+                    write!(w, ", ")?;
+                }
+                write_with_color!(w, highlight(opts, &theme().key, k), "{}", string(k))?;
+                write!(w, ": ")?;
+                let pointer = opts.child(pointer, k);
+                write_yaml_flow(w, opts, &pointer, opts.type_hint(&pointer, Some(k)), v)?;
+            }
+            write!(w, "}}")?;
+        }
+        Value::String(s) => {
+            let spec = highlight(opts, &theme().string, s);
+            match hinted_date(hint, s)? {
+                Some(date) if date.date.is_some() => write_with_color!(w, spec, "{date}")?,
+                _ => write_with_color!(w, spec, "{}", string(s))?,
+            }
+        }
+        Value::Number(n) if let Some(n) = hinted_integer(hint, n)? => {
+            write_with_color!(w, highlight(opts, &theme().number, &n), "{n}")?;
+        }
+        _ => write_leaf(w, opts, value)?,
+    }
+    Ok(())
+}
+
+fn write_yaml_string(
+    w: &mut impl WriteColor,
+    opts: &Options,
+    depth: usize,
+    hint: Option<TypeHint>,
+    value: &Value,
+    s: &str,
+) -> Result<()> {
+    // YAML reads a time without a date as a number, so only dates are left unquoted.
+    if let Some(date) = hinted_date(hint, s)?
+        && date.date.is_some()
+    {
+        write_with_color!(w, highlight(opts, &theme().string, s), "{date}")?;
+        write_type(w, opts, "#", value)?;
+        return Ok(());
+    }
+    let spec = highlight(opts, &theme().string, s);
+    let s = yaml_string(opts, depth, s);
+    // The type annotation of a block string goes after the header, not the last line.
+    if let Some((header, lines)) = s.split_once('\n') {
+        write_with_color!(w, spec, "{header}")?;
+        write_type(w, opts, "#", value)?;
+        write_with_color!(w, spec, "\n{lines}")?;
     } else {
-        yaml_flow_string(s)
+        write_with_color!(w, spec, "{s}")?;
+        write_type(w, opts, "#", value)?;
     }
+    Ok(())
 }
 
-fn write_yaml(w: &mut impl WriteColor, depth: usize, obj_value: bool, value: &Value) -> Result<()> {
+fn write_yaml<W: WriteColor>(
+    w: &mut Columns<W>,
+    opts: &Options,
+    depth: usize,
+    obj_value: bool,
+    pointer: &str,
+    key: Option<&str>,
+    value: &Value,
+) -> Result<()> {
+    let hint = opts.type_hint(pointer, key);
     match value {
+        Value::Array(_) | Value::Object(_) if hint == Some(TypeHint::Inline) => {
+            if obj_value {
+                write!(w, " ")?;
+            }
+            write_yaml_flow(w, opts, pointer, hint, value)?;
+            write_type(w, opts, "#", value)?;
+        }
+        _ if is_collapsed(opts, depth, value) => {
+            if obj_value {
+                write!(w, " ")?;
+            }
+            write_collapsed(w, value)?;
+            write_type(w, opts, "#", value)?;
+        }
         Value::Array(arr) => {
             if arr.is_empty() {
                 if obj_value {
                     write!(w, " ")?;
                 }
                 write!(w, "[]")?;
+                write_type(w, opts, "#", value)?;
             } else {
+                if obj_value {
+                    write_type(w, opts, "#", value)?;
+                    write_count(w, opts, "#", value)?;
+                }
                 for (i, e) in arr.iter().enumerate() {
                     if i > 0 || obj_value {
-                        write!(w, "\n{}", " ".repeat(depth * TAB_WIDTH))?;
+                        write!(w, "\n{}", " ".repeat(depth * opts.yaml_indent))?;
                     }
-                    write!(w, "- ")?;
-                    write_yaml(w, depth + 1, false, e)?;
+                    write!(w, "{:<1$}", "-", opts.yaml_indent)?;
+                    write_yaml(w, opts, depth + 1, false, &opts.child(pointer, i), None, e)?;
                 }
             }
         }
@@ -141,14 +925,32 @@ fn write_yaml(w: &mut impl WriteColor, depth: usize, obj_value: bool, value: &Va
                     write!(w, " ")?;
                 }
                 write!(w, "{{}}")?;
+                write_type(w, opts, "#", value)?;
             } else {
+                if obj_value {
+                    write_type(w, opts, "#", value)?;
+                    write_count(w, opts, "#", value)?;
+                }
                 for (i, (k, v)) in obj.iter().enumerate() {
                     if i > 0 || obj_value {
-                        write!(w, "\n{}", " ".repeat(depth * TAB_WIDTH))?;
+                        write!(w, "\n{}", " ".repeat(depth * opts.yaml_indent))?;
                     }
-                    write_with_color!(w, KEY, "{}", yaml_flow_string(k))?;
+                    write_with_color!(
+                        w,
+                        highlight(opts, &theme().key, k),
+                        "{}",
+                        yaml_flow_string(opts, k)
+                    )?;
                     write!(w, ":")?;
-                    write_yaml(w, depth + 1, true, v)?;
+                    write_yaml(
+                        w,
+                        opts,
+                        depth + 1,
+                        true,
+                        &opts.child(pointer, k),
+                        Some(k),
+                        v,
+                    )?;
                 }
             }
         }
@@ -156,19 +958,25 @@ fn write_yaml(w: &mut impl WriteColor, depth: usize, obj_value: bool, value: &Va
             if obj_value {
                 write!(w, " ")?;
             }
-            write_with_color!(w, STR, "{}", yaml_string(depth, s))?;
+            write_yaml_string(w, opts, depth, hint, value, s)?;
         }
         _ => {
             if obj_value {
                 write!(w, " ")?;
             }
-            write!(w, "{value}")?;
+            match value {
+                Value::Number(n) if let Some(n) = hinted_integer(hint, n)? => {
+                    write_with_color!(w, highlight(opts, &theme().number, &n), "{n}")?;
+                }
+                _ => write_leaf(w, opts, value)?,
+            }
+            write_type(w, opts, "#", value)?;
         }
     }
     Ok(())
 }
 
-fn toml_key(s: &str) -> String {
+fn toml_key(opts: &Options, s: &str) -> String {
     // https://toml.io/en/v1.0.0#keys
     // A bare key must be non-empty.
     // Bare keys may only contain ASCII letters, ASCII digits, underscores, and dashes.
@@ -177,13 +985,19 @@ fn toml_key(s: &str) -> String {
             .all(|c| c.is_ascii_alphabetic() || c.is_ascii_digit() || c == '_' || c == '-')
     {
         s.to_string()
+    } else if opts.ascii {
+        ascii_quote(s, false)
     } else {
         quote(s)
     }
 }
 
-fn toml_string(s: &str) -> String {
-    if s.contains('\n') && !s.contains(|c: char| c.is_control() && c != '\n') && !s.contains("'''")
+fn toml_string(opts: &Options, s: &str) -> String {
+    if opts.ascii && !s.is_ascii() {
+        ascii_quote(s, false)
+    } else if s.contains('\n')
+        && !s.contains(|c: char| c.is_control() && c != '\n')
+        && !s.contains("'''")
     {
         format!("'''\n{s}'''")
     } else {
@@ -191,13 +1005,41 @@ fn toml_string(s: &str) -> String {
     }
 }
 
-fn write_toml_inline(w: &mut impl WriteColor, value: &Value) -> Result<()> {
+/// The date `s`, if it's hinted to be one, or if it is one and `opts.toml_datetimes` is set. Times
+/// without a date aren't detected, since they're too easily mistaken for other strings.
+fn toml_datetime(
+    opts: &Options,
+    hint: Option<TypeHint>,
+    s: &str,
+) -> Result<Option<toml::value::Datetime>> {
+    Ok(match hinted_date(hint, s)? {
+        Some(date) => Some(date),
+        None if opts.toml_datetimes => s
+            .parse::<toml::value::Datetime>()
+            .ok()
+            .filter(|date| date.date.is_some()),
+        None => None,
+    })
+}
+
+fn write_toml_inline(
+    w: &mut impl WriteColor,
+    opts: &Options,
+    pointer: &str,
+    hint: Option<TypeHint>,
+    value: &Value,
+) -> Result<()> {
     match value {
         Value::Array(arr) => {
-            let arr = arr.iter().filter(|v| !v.is_null()).collect::<Vec<_>>();
+            let arr = arr
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| !v.is_null())
+                .collect::<Vec<_>>();
             write!(w, "[")?;
-            for (i, e) in arr.iter().enumerate() {
-                write_toml_inline(w, e)?;
+            for (i, &(index, e)) in arr.iter().enumerate() {
+                let pointer = opts.child(pointer, index);
+                write_toml_inline(w, opts, &pointer, opts.type_hint(&pointer, None), e)?;
                 if i != arr.len() - 1 {
                     write!(w, ", ")?;
                 }
@@ -208,9 +1050,10 @@ fn write_toml_inline(w: &mut impl WriteColor, value: &Value) -> Result<()> {
             let obj = obj.iter().filter(|(_, v)| !v.is_null()).collect::<Vec<_>>();
             write!(w, "{{")?;
             for (i, (k, v)) in obj.iter().enumerate() {
-                write_with_color!(w, KEY, " {}", toml_key(k))?;
+                write_with_color!(w, theme().key, " {}", toml_key(opts, k))?;
                 write!(w, " = ")?;
-                write_toml_inline(w, v)?;
+                let pointer = opts.child(pointer, k);
+                write_toml_inline(w, opts, &pointer, opts.type_hint(&pointer, Some(k)), v)?;
                 if i == obj.len() - 1 {
                     write!(w, " ")?;
                 } else {
@@ -219,69 +1062,100 @@ fn write_toml_inline(w: &mut impl WriteColor, value: &Value) -> Result<()> {
             }
             write!(w, "}}")?;
         }
-        _ => write_toml(w, "", value)?,
+        Value::String(s) if let Some(date) = toml_datetime(opts, hint, s)? => {
+            write_with_color!(w, theme().string, "{date}")?;
+        }
+        Value::Number(n) if let Some(n) = hinted_integer(hint, n)? => {
+            write_with_color!(w, theme().number, "{n}")?;
+        }
+        _ => write_toml(w, opts, "", pointer, value)?,
     }
     Ok(())
 }
 
-fn write_toml(w: &mut impl WriteColor, context: &str, value: &Value) -> Result<()> {
-    fn should_nest(value: &Value) -> bool {
-        if let Value::Object(obj) = value {
-            let values = obj.values().filter(|v| !v.is_null()).collect::<Vec<_>>();
-            values.len() > 1 || (values.len() == 1 && should_nest(values[0]))
+fn write_toml(
+    w: &mut impl WriteColor,
+    opts: &Options,
+    context: &str,
+    pointer: &str,
+    value: &Value,
+) -> Result<()> {
+    // Objects with more than opts.toml_inline_threshold entries are written as tables. Smaller
+    // objects are written on one line, either as dotted keys or as inline tables.
+    // Fields hinted to be inline never are.
+    fn should_nest(opts: &Options, pointer: &str, key: &str, value: &Value) -> bool {
+        if opts.type_hint(pointer, Some(key)) == Some(TypeHint::Inline) {
+            false
+        } else if let Value::Object(obj) = value {
+            let obj = obj.iter().filter(|(_, v)| !v.is_null()).collect::<Vec<_>>();
+            obj.len() > opts.toml_inline_threshold
+                || obj
+                    .iter()
+                    .any(|(k, v)| should_nest(opts, &opts.child(pointer, k), k, v))
         } else if let Value::Array(arr) = value {
-            arr.iter().all(Value::is_object)
+            !arr.is_empty() && arr.iter().all(Value::is_object)
         } else {
             false
         }
     }
 
-    fn toml_key_value<'a>(k: &'a str, v: &'a Value) -> (String, &'a Value) {
-        let k = toml_key(k);
-        if let Value::Object(obj) = v {
+    // The pointer and hint are those of the innermost key, whose value is written.
+    fn toml_key_value<'a>(
+        opts: &Options,
+        pointer: &str,
+        k: &'a str,
+        v: &'a Value,
+    ) -> (String, String, Option<TypeHint>, &'a Value) {
+        let pointer = opts.child(pointer, k);
+        let hint = opts.type_hint(&pointer, Some(k));
+        let key = toml_key(opts, k);
+        if let Value::Object(obj) = v
+            && hint != Some(TypeHint::Inline)
+        {
             let obj = obj.iter().filter(|(_, v)| !v.is_null()).collect::<Vec<_>>();
             if obj.len() == 1 {
-                let (inner_k, v) = toml_key_value(obj[0].0, obj[0].1);
-                return (format!("{k}.{inner_k}"), v);
+                let (inner_k, pointer, hint, v) =
+                    toml_key_value(opts, &pointer, obj[0].0, obj[0].1);
+                return (format!("{key}.{inner_k}"), pointer, hint, v);
             }
         }
-        (k, v)
+        (key, pointer, hint, v)
     }
 
     match value {
-        Value::Array(_) => write_toml_inline(w, value)?,
+        Value::Array(_) => write_toml_inline(w, opts, pointer, None, value)?,
         Value::Object(obj) => {
             let obj = obj.iter().filter(|(_, v)| !v.is_null()).collect::<Vec<_>>();
-            let flat = obj
-                .iter()
-                .filter(|(_, v)| !should_nest(v))
-                .collect::<Vec<_>>();
-            let nested = obj
-                .iter()
-                .filter(|(_, v)| should_nest(v))
-                .collect::<Vec<_>>();
+            let nest = |k: &str, v: &Value| should_nest(opts, &opts.child(pointer, k), k, v);
+            let flat = obj.iter().filter(|(k, v)| !nest(k, v)).collect::<Vec<_>>();
+            let nested = obj.iter().filter(|(k, v)| nest(k, v)).collect::<Vec<_>>();
 
             for (i, &(k, v)) in flat.iter().enumerate() {
-                let (k, v) = toml_key_value(k, v);
-                write_with_color!(w, KEY, "{k}")?;
+                let (k, pointer, hint, v) = toml_key_value(opts, pointer, k, v);
+                write_with_color!(w, theme().key, "{k}")?;
                 write!(w, " = ")?;
-                write_toml_inline(w, v)?;
+                write_toml_inline(w, opts, &pointer, hint, v)?;
+                write_type(w, opts, "#", v)?;
                 if i != flat.len() - 1 {
                     writeln!(w)?;
                 }
             }
 
             for (i, &(k, v)) in nested.iter().enumerate() {
-                let k = format!("{}{}", context, toml_key(k));
+                let pointer = opts.child(pointer, k);
+                let k = format!("{}{}", context, toml_key(opts, k));
                 if !flat.is_empty() || i > 0 {
                     write!(w, "\n\n")?;
                 }
                 match v {
                     Value::Object(obj) => {
-                        if obj.iter().any(|(_, v)| !should_nest(v)) {
-                            write_with_color!(w, HEADER, "[{k}]\n")?;
+                        if obj
+                            .iter()
+                            .any(|(k, v)| !should_nest(opts, &opts.child(&pointer, k), k, v))
+                        {
+                            write_with_color!(w, theme().header, "[{k}]\n")?;
                         }
-                        write_toml(w, &format!("{k}."), v)?;
+                        write_toml(w, opts, &format!("{k}."), &pointer, v)?;
                     }
                     Value::Array(arr) => {
                         for (i, e) in arr.iter().enumerate() {
@@ -291,50 +1165,1068 @@ fn write_toml(w: &mut impl WriteColor, context: &str, value: &Value) -> Result<(
                             let Value::Object(obj) = e else {
                                 unreachable!("arr only contains objects by construction");
                             };
-                            write_with_color!(w, HEADER, "[[{k}]]")?;
+                            write_with_color!(w, theme().header, "[[{k}]]")?;
                             if !obj.is_empty() {
                                 writeln!(w)?;
                             }
-                            write_toml(w, &format!("{k}."), e)?;
+                            write_toml(w, opts, &format!("{k}."), &opts.child(&pointer, i), e)?;
                         }
                     }
                     _ => unreachable!("nested contains objects and arrays by construction"),
                 }
             }
         }
-        Value::String(s) => write_with_color!(w, STR, "{}", toml_string(s))?,
-        Value::Null => bail!("can't convert null to TOML"),
-        _ => write!(w, "{value}")?,
+        Value::String(s) => write_with_color!(w, theme().string, "{}", toml_string(opts, s))?,
+        Value::Null => {
+            return Err(Diagnostic::new(Kind::Convert, "can't convert null to TOML")
+                .hint("TOML has no null, so print it as JSON or YAML instead")
+                .into());
+        }
+        _ => write_scalar(w, value)?,
     }
     Ok(())
 }
 
-pub fn json(w: &mut impl WriteColor, s: &str) -> Result<()> {
-    write_json(w, 0, &s.parse()?)?;
-    writeln!(w)?;
-    Ok(())
+/// A buffer to write to before `w`, in color if `w` is, so that nothing is written if a type hint
+/// fails partway through.
+fn buffer(w: &impl WriteColor) -> Buffer {
+    if w.supports_color() {
+        Buffer::ansi()
+    } else {
+        Buffer::no_color()
+    }
 }
 
-pub fn yaml(w: &mut impl WriteColor, s: &str) -> Result<()> {
-    write_yaml(w, 0, false, &s.parse()?)?;
-    writeln!(w)?;
-    Ok(())
+/// Cut each string in `value` short after `max` characters.
+fn truncate_strings(value: &mut Value, max: usize) {
+    match value {
+        Value::Array(arr) => arr.iter_mut().for_each(|e| truncate_strings(e, max)),
+        Value::Object(obj) => obj.values_mut().for_each(|v| truncate_strings(v, max)),
+        Value::String(s) => {
+            if let Some((i, _)) = s.char_indices().nth(max) {
+                s.truncate(i);
+                s.push('…');
+            }
+        }
+        _ => {}
+    }
 }
 
-pub fn toml(w: &mut impl WriteColor, s: &str) -> Result<()> {
-    write_toml(w, "", &s.parse()?)?;
-    writeln!(w)?;
-    Ok(())
+fn parse(opts: &Options, s: &str) -> Result<Value> {
+    let mut value: Value = s.parse()?;
+    if opts.sort_keys {
+        value.sort_all_objects();
+    }
+    if let Some(max) = opts.max_string {
+        truncate_strings(&mut value, max);
+    }
+    slice(opts, &mut value);
+    Ok(value)
 }
 
+/// Take only the elements --head or --tail ask for from `value`, if it's an array.
+fn slice(opts: &Options, value: &mut Value) {
+    if let Value::Array(arr) = value {
+        if let Some(head) = opts.head {
+            arr.truncate(head);
+        }
+        if let Some(tail) = opts.tail {
+            arr.drain(..arr.len().saturating_sub(tail));
+        }
+    }
+}
+
+/// Print a result as indented JSON.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, or writing fails.
+pub fn json(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    let value = parse(opts, s)?;
+    let w = &mut Columns::new(w);
+    write_json(w, opts, 0, &value)?;
+    if !is_branch(&value) || is_collapsed(opts, 0, &value) {
+        write_type(w, opts, "//", &value)?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Print a result as JSON on a single line.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, or writing fails.
+pub fn json_line(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    write_json_line(w, opts, &parse(opts, s)?)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Print a result as a record of a JSON text sequence: an RS, then JSON on a single line.
+pub fn json_seq(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    w.write_all(b"\x1e")?;
+    json_line(w, opts, s)
+}
+
+/// Write `n` as JavaScript would, as RFC 8785 asks: the shortest digits which read back as the same
+/// double, with an exponent only if it's below -6 or above 20.
+fn write_jcs_number(w: &mut impl WriteColor, n: &serde_json::Number) -> Result<()> {
+    let f = n
+        .as_f64()
+        .filter(|f| f.is_finite())
+        .with_context(|| format!("{n} is too large to be a double"))?;
+    if f == 0.0 {
+        write!(w, "0")?;
+        return Ok(());
+    }
+    // e.g. -1.25e-7, as the sign, digits, and exponent of the first digit.
+    let sci = format!("{:e}", f.abs());
+    let (mantissa, exponent) = sci.split_once('e').context("formatting number")?;
+    let digits = mantissa.replace('.', "");
+    let exponent = exponent.parse::<i32>()?;
+    let len = i32::try_from(digits.len())?;
+    if f < 0.0 {
+        write!(w, "-")?;
+    }
+    // Where the decimal point goes, counting digits from the left.
+    let point = exponent + 1;
+    if (len..=21).contains(&point) {
+        write!(w, "{digits}{}", "0".repeat(usize::try_from(point - len)?))?;
+    } else if (1..=21).contains(&point) {
+        let (int, frac) = digits.split_at(usize::try_from(point)?);
+        write!(w, "{int}.{frac}")?;
+    } else if (-5..=0).contains(&point) {
+        write!(w, "0.{}{digits}", "0".repeat(usize::try_from(-point)?))?;
+    } else {
+        let (first, rest) = digits.split_at(1);
+        write!(w, "{first}")?;
+        if !rest.is_empty() {
+            write!(w, ".{rest}")?;
+        }
+        write!(w, "e{}{exponent}", if exponent > 0 { "+" } else { "" })?;
+    }
+    Ok(())
+}
+
+fn write_jcs(w: &mut impl WriteColor, value: &Value) -> Result<()> {
+    match value {
+        Value::Array(arr) => {
+            write!(w, "[")?;
+            for (i, e) in arr.iter().enumerate() {
+                if i != 0 {
+                    write!(w, ",")?;
+                }
+                write_jcs(w, e)?;
+            }
+            write!(w, "]")?;
+        }
+        Value::Object(obj) => {
+            // Keys are sorted by their UTF-16 code units, as JavaScript would sort them.
+            let mut entries = obj.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            write!(w, "{{")?;
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i != 0 {
+                    write!(w, ",")?;
+                }
+                write!(w, "{}:", serde_json::to_string(k)?)?;
+                write_jcs(w, v)?;
+            }
+            write!(w, "}}")?;
+        }
+        // serde_json escapes strings just as RFC 8785 asks.
+        Value::String(s) => write!(w, "{}", serde_json::to_string(s)?)?,
+        Value::Number(n) => write_jcs_number(w, n)?,
+        Value::Bool(_) | Value::Null => write!(w, "{value}")?,
+    }
+    Ok(())
+}
+
+/// Print a result as RFC 8785 canonical JSON, for hashing or signing: keys sorted, numbers written
+/// as JavaScript would write them, and no whitespace, on a line of its own. The newline isn't part
+/// of the canonical form.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, has a number too large to be a double, or writing fails.
+pub fn jcs(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    write_jcs(w, &parse(opts, s)?)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Print a result as YAML.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, or writing fails.
+pub fn yaml(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    let mut buf = buffer(w);
+    write_yaml(
+        &mut Columns::new(&mut buf),
+        opts,
+        0,
+        false,
+        "",
+        None,
+        &parse(opts, s)?,
+    )?;
+    writeln!(buf)?;
+    w.write_all(buf.as_slice())?;
+    Ok(())
+}
+
+/// Print a result as a stream of YAML documents, each starting with `---`: one for each element
+/// if it's a non-empty array, or one for the whole result otherwise, as `kubectl apply -f -`
+/// expects.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, or writing fails.
+pub fn yaml_docs(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    let mut buf = buffer(w);
+    let value = parse(opts, s)?;
+    // An empty array is a document of its own, rather than none at all, so that it isn't lost.
+    let docs = match &value {
+        Value::Array(arr) if !arr.is_empty() => arr.iter().collect(),
+        _ => vec![&value],
+    };
+    for doc in docs {
+        writeln!(buf, "---")?;
+        write_yaml(&mut Columns::new(&mut buf), opts, 0, false, "", None, doc)?;
+        writeln!(buf)?;
+    }
+    w.write_all(buf.as_slice())?;
+    Ok(())
+}
+
+/// Print a result as TOML.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, is null, or writing fails.
+pub fn toml(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    let mut buf = buffer(w);
+    write_toml(&mut buf, opts, "", "", &parse(opts, s)?)?;
+    writeln!(buf)?;
+    w.write_all(buf.as_slice())?;
+    Ok(())
+}
+
+const INI_HINT: &str =
+    "INI only has sections of keys and values, so print it as JSON or YAML instead";
+
+/// A key or a section name as it's written in INI: bare if it can be read back as it is, or quoted
+/// as a JSON string otherwise.
+fn ini_key(opts: &Options, s: &str, section: bool) -> String {
+    let bare = if section {
+        s.chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    } else {
+        !s.contains(['=', ':']) && !s.starts_with(['[', ';', '#', '"']) && s == s.trim()
+    };
+    if bare && !s.is_empty() && !s.contains(char::is_control) {
+        s.to_string()
+    } else {
+        json_string(opts, s)
+    }
+}
+
+fn write_ini_value(w: &mut impl WriteColor, opts: &Options, k: &str, v: &Value) -> Result<()> {
+    match v {
+        Value::Null => return Ok(()),
+        Value::Array(arr) => {
+            // Each element is written as the same key again, as INI has no arrays.
+            for e in arr {
+                if e.is_array() || e.is_object() {
+                    return Err(Diagnostic::new(
+                        Kind::Convert,
+                        format!("can't convert {} to INI", type_name(v)),
+                    )
+                    .hint(INI_HINT)
+                    .into());
+                }
+                write_ini_value(w, opts, k, e)?;
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    write_with_color!(w, theme().key, "{}", ini_key(opts, k, false))?;
+    write!(w, " = ")?;
+    match v {
+        Value::String(s) => {
+            let s = if s.is_empty()
+                || s != s.trim()
+                || s.starts_with('"')
+                || s.contains(char::is_control)
+            {
+                json_string(opts, s)
+            } else {
+                s.clone()
+            };
+            write_with_color!(w, theme().string, "{s}")?;
+        }
+        _ => write_scalar(w, v)?,
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Write the keys and values of `obj` under the header `path`, if it needs one, followed by a
+/// section for each object in it. `written` is whether anything has been written yet, to separate
+/// sections with blank lines.
+fn write_ini(
+    w: &mut impl WriteColor,
+    opts: &Options,
+    path: &mut Vec<String>,
+    obj: &Map<String, Value>,
+    written: &mut bool,
+) -> Result<()> {
+    if !path.is_empty() && (obj.is_empty() || obj.values().any(|v| !v.is_object())) {
+        if *written {
+            writeln!(w)?;
+        }
+        write_with_color!(w, theme().header, "[{}]", path.join("."))?;
+        writeln!(w)?;
+        *written = true;
+    }
+    for (k, v) in obj.iter().filter(|(_, v)| !v.is_object()) {
+        write_ini_value(w, opts, k, v)?;
+        *written = true;
+    }
+    for (k, v) in obj {
+        if let Value::Object(obj) = v {
+            path.push(ini_key(opts, k, true));
+            write_ini(w, opts, path, obj, written)?;
+            path.pop();
+        }
+    }
+    Ok(())
+}
+
+/// Print a result as INI: the keys of an object whose values aren't objects, and then a section
+/// for each object, named by its path, e.g. `[a.b]`. An array is written as the same key for each
+/// of its elements, and null is left out, as INI has neither.
+///
+/// # Errors
+///
+/// If `s` isn't a JSON object, contains arrays of arrays or objects, or writing fails.
+pub fn ini(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    let mut buf = buffer(w);
+    let value = parse(opts, s)?;
+    let Value::Object(obj) = &value else {
+        return Err(Diagnostic::new(
+            Kind::Convert,
+            format!("can't convert {} to INI", type_name(&value)),
+        )
+        .hint(INI_HINT)
+        .into());
+    };
+    write_ini(&mut buf, opts, &mut Vec::new(), obj, &mut false)?;
+    w.write_all(buf.as_slice())?;
+    Ok(())
+}
+
+/// A value as it's written in a .env file: bare if it's safe to, in single quotes if nothing in it
+/// would be read differently there, and in double quotes, escaped, otherwise.
+fn env_value(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.,/:@+%".contains(c))
+    {
+        s.to_string()
+    } else if !s.contains('\'') && !s.contains(char::is_control) {
+        format!("'{s}'")
+    } else {
+        let mut res = String::from('"');
+        for c in s.chars() {
+            match c {
+                '"' | '\\' | '$' | '`' => {
+                    res.push('\\');
+                    res.push(c);
+                }
+                '\n' => res.push_str("\\n"),
+                '\r' => res.push_str("\\r"),
+                '\t' => res.push_str("\\t"),
+                _ => res.push(c),
+            }
+        }
+        res.push('"');
+        res
+    }
+}
+
+/// Write each value in `value` which isn't an array or object as a variable named by its path,
+/// with `key` so far.
+fn write_env(w: &mut impl WriteColor, opts: &Options, key: &str, value: &Value) -> Result<()> {
+    let join = |k: &str| {
+        if key.is_empty() {
+            k.to_string()
+        } else {
+            format!("{key}{}{k}", opts.env_separator)
+        }
+    };
+    match value {
+        Value::Array(arr) => {
+            for (i, e) in arr.iter().enumerate() {
+                write_env(w, opts, &join(&i.to_string()), e)?;
+            }
+        }
+        Value::Object(obj) => {
+            for (k, v) in obj {
+                write_env(w, opts, &join(k), v)?;
+            }
+        }
+        // As in TOML, there's no null, and an empty value would be an empty string.
+        Value::Null => {}
+        _ => {
+            if key.is_empty()
+                || key.starts_with('#')
+                || key.contains(|c: char| c == '=' || c.is_whitespace() || c.is_control())
+            {
+                return Err(Diagnostic::new(
+                    Kind::Convert,
+                    format!("can't convert the key {} to env", quote(key)),
+                )
+                .hint("rename it with a name of letters, digits, and underscores")
+                .into());
+            }
+            write_with_color!(w, theme().key, "{key}")?;
+            write!(w, "=")?;
+            match value {
+                Value::String(s) => write_with_color!(w, theme().string, "{}", env_value(s))?,
+                _ => write_scalar(w, value)?,
+            }
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print a result as a .env file, as docker compose reads: a `KEY=value` line for each value in
+/// it, named by its path, with the keys of nested objects and the indices of arrays joined by
+/// `opts.env_separator`. null is left out.
+///
+/// # Errors
+///
+/// If `s` isn't a JSON object, has a key which can't be a name, or writing fails.
+pub fn env(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    let mut buf = buffer(w);
+    let value = parse(opts, s)?;
+    if !value.is_object() {
+        return Err(Diagnostic::new(
+            Kind::Convert,
+            format!("can't convert {} to env", type_name(&value)),
+        )
+        .hint("env is a list of names and values, so print an object")
+        .into());
+    }
+    write_env(&mut buf, opts, "", &value)?;
+    w.write_all(buf.as_slice())?;
+    Ok(())
+}
+
+/// The property list value of `value`, or `None` if it's null, which is left out, as in TOML.
+fn to_plist(
+    opts: &Options,
+    pointer: &str,
+    hint: Option<TypeHint>,
+    value: &Value,
+) -> Result<Option<plist::Value>> {
+    Ok(Some(match value {
+        Value::Null => return Ok(None),
+        Value::Bool(b) => plist::Value::Boolean(*b),
+        Value::Number(n) => {
+            if let Some(n) = n.as_i64() {
+                plist::Value::Integer(n.into())
+            } else if let Some(n) = n.as_u64() {
+                plist::Value::Integer(n.into())
+            } else {
+                plist::Value::Real(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        Value::String(s) => {
+            let date = if hint == Some(TypeHint::Date) {
+                let date = plist::Date::from_xml_format(s).map_err(|_| {
+                    Diagnostic::new(Kind::Convert, format!("can't print {} as a date", quote(s)))
+                        .hint("dates are written as in RFC 3339, e.g. 1979-05-27T07:32:00Z")
+                })?;
+                Some(date)
+            } else if opts.plist_dates {
+                plist::Date::from_xml_format(s).ok()
+            } else {
+                None
+            };
+            date.map_or_else(|| plist::Value::String(s.clone()), plist::Value::Date)
+        }
+        Value::Array(arr) => plist::Value::Array(
+            arr.iter()
+                .enumerate()
+                .filter_map(|(i, e)| {
+                    let pointer = opts.child(pointer, i);
+                    to_plist(opts, &pointer, opts.type_hint(&pointer, None), e).transpose()
+                })
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(obj) => plist::Value::Dictionary(
+            obj.iter()
+                .filter_map(|(k, v)| {
+                    let pointer = opts.child(pointer, k);
+                    let v = to_plist(opts, &pointer, opts.type_hint(&pointer, Some(k)), v)
+                        .transpose()?;
+                    Some(v.map(|v| (k.clone(), v)))
+                })
+                .collect::<Result<_>>()?,
+        ),
+    }))
+}
+
+/// Print a result as an XML property list, or as a binary one if `opts.plist_binary` is set.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, is null, or writing fails.
+pub fn plist(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    let Some(value) = to_plist(opts, "", None, &parse(opts, s)?)? else {
+        return Err(
+            Diagnostic::new(Kind::Convert, "can't convert null to plist")
+                .hint("property lists have no null, so print it as JSON or YAML instead")
+                .into(),
+        );
+    };
+    let mut buf = Vec::new();
+    if opts.plist_binary {
+        value.to_writer_binary(&mut buf)?;
+    } else {
+        value.to_writer_xml(&mut buf)?;
+        buf.push(b'\n');
+    }
+    w.write_all(&buf)?;
+    Ok(())
+}
+
+fn write_gron(w: &mut impl WriteColor, opts: &Options, path: &str, value: &Value) -> Result<()> {
+    write_with_color!(w, theme().key, "{path}")?;
+    write!(w, " = ")?;
+    match value {
+        Value::Array(arr) => {
+            writeln!(w, "[];")?;
+            for (i, e) in arr.iter().enumerate() {
+                write_gron(w, opts, &format!("{path}[{i}]"), e)?;
+            }
+        }
+        Value::Object(obj) => {
+            writeln!(w, "{{}};")?;
+            for (k, v) in obj {
+                write_gron(w, opts, &diff::key_path(path, k), v)?;
+            }
+        }
+        Value::String(s) => {
+            write_with_color!(w, theme().string, "{}", json_string(opts, s))?;
+            writeln!(w, ";")?;
+        }
+        _ => {
+            write_scalar(w, value)?;
+            writeln!(w, ";")?;
+        }
+    }
+    Ok(())
+}
+
+/// Print a result as gron: an assignment of each value to its path from `json`, one to a line, so
+/// that it can be grepped, and read back in with -g.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, or writing fails.
+pub fn gron(w: &mut impl WriteColor, opts: &Options, s: &str) -> Result<()> {
+    write_gron(w, opts, "json", &parse(opts, s)?)
+}
+
+/// Write a JSON value used as a key, highlighting strings.
+fn write_key(w: &mut impl WriteColor, key: &str) -> Result<()> {
+    if key.starts_with('"') {
+        write_with_color!(w, theme().string, "{key}")?;
+    } else {
+        write!(w, "{key}")?;
+    }
+    Ok(())
+}
+
+/// Print a table of values and their counts, with the percentage of the total each makes up.
+#[expect(clippy::cast_precision_loss)]
+pub fn frequencies(w: &mut impl WriteColor, rows: &[(&str, usize)]) -> Result<()> {
+    let total = rows.iter().map(|(_, count)| count).sum::<usize>();
+    let width = rows.first().map_or(0, |(_, count)| count.to_string().len());
+    for (value, count) in rows {
+        let percent = *count as f64 / total as f64 * 100.0;
+        write!(w, "{count:>width$} {percent:>6.2}%  ")?;
+        write_key(w, value)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Print a table of keys shared by more than one value, with counts and a representative value.
+pub fn duplicates(w: &mut impl WriteColor, rows: &[(&str, usize, &str)]) -> Result<()> {
+    let width = rows
+        .first()
+        .map_or(0, |(_, count, _)| count.to_string().len());
+    for (key, count, value) in rows {
+        write!(w, "{count:>width$}  ")?;
+        write_key(w, key)?;
+        writeln!(w, "  {value}")?;
+    }
+    Ok(())
+}
+
+/// Print each deviation of each value from the majority shape, with the value's position.
+pub fn drift(w: &mut impl WriteColor, rows: &[(usize, Vec<(String, String)>)]) -> Result<()> {
+    let width = rows.last().map_or(0, |(i, _)| i.to_string().len());
+    for (i, deviations) in rows {
+        for (path, description) in deviations {
+            write!(w, "{i:>width$}  ")?;
+            write_with_color!(w, theme().key, "{path}")?;
+            writeln!(w, " {description}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Print each change between two documents on a line of its own: `+` for an added value, `-` for a
+/// removed one, and `~` for one which was changed.
+pub fn diff(w: &mut impl WriteColor, changes: &[(String, diff::Change)]) -> Result<()> {
+    for (path, change) in changes {
+        match change {
+            diff::Change::Added(b) => {
+                write_with_color!(w, theme().added, "+ {path}: {b}")?;
+            }
+            diff::Change::Removed(a) => {
+                write_with_color!(w, theme().removed, "- {path}: {a}")?;
+            }
+            diff::Change::Changed(a, b) => {
+                write!(w, "~ ")?;
+                write_with_color!(w, theme().key, "{path}")?;
+                write!(w, ": ")?;
+                write_with_color!(w, theme().removed, "{a}")?;
+                write!(w, " → ")?;
+                write_with_color!(w, theme().added, "{b}")?;
+            }
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Print a unified diff, as `diff -u` would, of how the file at `path` would change from `old`, or
+/// from nothing if it doesn't exist, to `new`. Nothing is printed if they're the same.
+///
+/// # Errors
+///
+/// If writing fails.
+pub fn unified_diff(
+    w: &mut impl WriteColor,
+    path: &str,
+    old: Option<&str>,
+    new: &str,
+) -> Result<()> {
+    if old == Some(new) {
+        return Ok(());
+    }
+    let diff = TextDiff::from_lines(old.unwrap_or(""), new);
+    let from = if old.is_some() { path } else { "/dev/null" };
+    write_with_color!(w, theme().removed, "--- {from}")?;
+    writeln!(w)?;
+    write_with_color!(w, theme().added, "+++ {path}")?;
+    writeln!(w)?;
+    for hunk in diff.unified_diff().iter_hunks() {
+        write_with_color!(w, theme().header, "{}", hunk.header())?;
+        writeln!(w)?;
+        for change in hunk.iter_changes() {
+            let line = change.value();
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            match change.tag() {
+                ChangeTag::Delete => write_with_color!(w, theme().removed, "-{line}")?,
+                ChangeTag::Insert => write_with_color!(w, theme().added, "+{line}")?,
+                ChangeTag::Equal => write!(w, " {line}")?,
+            }
+            writeln!(w)?;
+            if change.missing_newline() {
+                writeln!(w, "\\ No newline at end of file")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print whether the test `name` passed, and if it failed, why: how the result differed from what
+/// was expected, or the error it failed with.
+pub fn outcome(w: &mut impl WriteColor, name: &str, outcome: &suite::Outcome) -> Result<()> {
+    match outcome {
+        suite::Outcome::Pass => {
+            write_with_color!(w, theme().added, "pass")?;
+            writeln!(w, " {name}")?;
+        }
+        suite::Outcome::Fail(changes) => {
+            write_with_color!(w, theme().removed, "FAIL")?;
+            writeln!(w, " {name}")?;
+            diff(w, changes)?;
+        }
+        suite::Outcome::Error(err) => {
+            write_with_color!(w, theme().removed, "FAIL")?;
+            writeln!(w, " {name}")?;
+            error(w, err)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print a histogram of (lower bound, upper bound, count) buckets as horizontal bars.
+#[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn histogram(w: &mut impl WriteColor, buckets: &[(f64, f64, usize)]) -> Result<()> {
+    const BAR_WIDTH: usize = 50;
+    const EIGHTHS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+    // Show enough decimal places to tell the bounds of adjacent buckets apart.
+    let width = buckets.first().map_or(0.0, |(lo, hi, _)| hi - lo);
+    let precision = if width == 0.0 {
+        buckets
+            .first()
+            .map_or(0, |(lo, _, _)| if lo.fract() == 0.0 { 0 } else { 2 })
+    } else {
+        (1.0 - width.log10().floor()).clamp(0.0, 6.0) as usize
+    };
+
+    let labels = buckets
+        .iter()
+        .map(|(lo, hi, _)| (format!("{lo:.precision$}"), format!("{hi:.precision$}")))
+        .collect::<Vec<_>>();
+    let lo_width = labels.iter().map(|(lo, _)| lo.len()).max().unwrap_or(0);
+    let hi_width = labels.iter().map(|(_, hi)| hi.len()).max().unwrap_or(0);
+    let max = buckets
+        .iter()
+        .map(|(_, _, count)| *count)
+        .max()
+        .unwrap_or(0);
+
+    for ((lo, hi), (_, _, count)) in labels.iter().zip(buckets) {
+        let eighths = (count * BAR_WIDTH * 8).checked_div(max).unwrap_or(0);
+        write_with_color!(w, theme().key, "{lo:>lo_width$} – {hi:>hi_width$}")?;
+        writeln!(
+            w,
+            " │{}{} {count}",
+            "█".repeat(eighths / 8),
+            EIGHTHS[eighths % 8]
+        )?;
+    }
+    Ok(())
+}
+
+/// Draw numbers as a sparkline, with a bar for each, scaled from the least of them to the greatest.
+/// If they're all the same, the bars are drawn half height.
+#[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn sparkline(ns: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = ns.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = ns.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    ns.iter()
+        .map(|n| {
+            if max > min {
+                BARS[((n - min) / (max - min) * 7.0).round() as usize]
+            } else {
+                BARS[3]
+            }
+        })
+        .collect()
+}
+
+/// A value as a cell of a table, and whether it's aligned to the right: strings as they are, with
+/// any control characters escaped, numbers aligned to the right, non-empty arrays of numbers as
+/// sparklines, and anything else as JSON.
+fn cell(value: &Value) -> (String, bool) {
+    match value {
+        Value::String(s) => {
+            let s = s
+                .chars()
+                .map(|c| {
+                    if c.is_control() {
+                        c.escape_default().to_string()
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect();
+            (s, false)
+        }
+        Value::Number(n) => (n.to_string(), true),
+        Value::Array(values) if !values.is_empty() => values
+            .iter()
+            .map(Value::as_f64)
+            .collect::<Option<Vec<_>>>()
+            .map_or_else(|| (value.to_string(), false), |ns| (sparkline(&ns), false)),
+        _ => (value.to_string(), false),
+    }
+}
+
+/// Print rows of values in columns under a header, as drawn by `cell`. Missing values are left
+/// blank.
+pub fn table(
+    w: &mut impl WriteColor,
+    columns: &[&str],
+    rows: &[Vec<Option<&Value>>],
+) -> Result<()> {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| value.map_or((String::new(), false), cell))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let widths = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].0.chars().count())
+                .chain([column.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    for (i, (column, width)) in columns.iter().zip(&widths).enumerate() {
+        if i > 0 {
+            write!(w, "  ")?;
+        }
+        // Don't pad the last column, so that lines don't end in spaces.
+        let width = if i + 1 == columns.len() { 0 } else { *width };
+        write_with_color!(w, theme().key, "{column:<width$}")?;
+    }
+    writeln!(w)?;
+    for row in &rows {
+        let mut line = String::new();
+        for (i, ((cell, right), width)) in row.iter().zip(&widths).enumerate() {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            if *right {
+                write!(line, "{cell:>width$}")?;
+            } else {
+                write!(line, "{cell:<width$}")?;
+            }
+        }
+        // Blank cells at the end are left off, so that lines don't end in spaces.
+        writeln!(w, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Print the time spent in each stage, with its share of the total.
+pub fn profile(w: &mut impl WriteColor, profile: &profile::Profile) -> Result<()> {
+    let total = profile.stages.iter().map(|(_, d)| *d).sum::<Duration>();
+    let rows = profile
+        .stages
+        .iter()
+        .copied()
+        .chain([("total", total)])
+        .map(|(name, d)| (name, format!("{d:.1?}"), d))
+        .collect::<Vec<_>>();
+    let name_width = rows.iter().map(|(name, ..)| name.len()).max().unwrap_or(0);
+    let time_width = rows.iter().map(|(_, t, _)| t.len()).max().unwrap_or(0);
+    for (name, t, d) in rows {
+        write_with_color!(w, theme().key, "{name:<name_width$}")?;
+        write!(w, "  {t:>time_width$}")?;
+        if !total.is_zero() {
+            write!(
+                w,
+                " {:>5.1}%",
+                d.as_secs_f64() / total.as_secs_f64() * 100.0
+            )?;
+        }
+        writeln!(w)?;
+    }
+    if profile.evaluations > 0 {
+        writeln!(
+            w,
+            "SCRIPT was evaluated {} times, taking {:.1?} each on average",
+            profile.evaluations, profile.per_evaluation
+        )?;
+    }
+    if let Some(bytes) = profile.peak_memory {
+        #[expect(clippy::cast_precision_loss)]
+        let mib = bytes as f64 / f64::from(1 << 20);
+        writeln!(w, "Peak memory use was {mib:.1} MiB")?;
+    }
+    Ok(())
+}
+
+/// Print an error, with the kind of error, an excerpt of where it was found underlined, and a hint
+/// at how to fix it, if it has a diagnostic attached.
 pub fn error(w: &mut impl WriteColor, err: &Error) -> Result<()> {
-    write_with_color!(w, ERR, "error")?;
+    let Some(diagnostic) = diagnostic::find(err) else {
+        write_with_color!(w, theme().error, "error")?;
+        writeln!(w, ": {err:#}")?;
+        return Ok(());
+    };
+    write_with_color!(w, theme().error, "error[{}]", diagnostic.kind)?;
+    writeln!(w, ": {err:#}")?;
+    if let Some(excerpt) = &diagnostic.excerpt {
+        let line = excerpt.line.to_string();
+        let gutter = " ".repeat(line.len());
+        if let Some(path) = &diagnostic.path {
+            write_with_color!(w, theme().annotation, "{gutter}-->")?;
+            writeln!(w, " {path}:{line}:{}", excerpt.column)?;
+        }
+        write_with_color!(w, theme().annotation, "{gutter} |")?;
+        writeln!(w)?;
+        for (n, text) in &excerpt.before {
+            write_with_color!(w, theme().annotation, "{n:>width$} |", width = line.len())?;
+            writeln!(w, " {text}")?;
+        }
+        write_with_color!(w, theme().annotation, "{line} |")?;
+        writeln!(w, " {}", excerpt.text)?;
+        write_with_color!(w, theme().annotation, "{gutter} |")?;
+        write!(w, " {}", " ".repeat(excerpt.span.start))?;
+        write_with_color!(w, theme().error, "{}", "^".repeat(excerpt.span.len()))?;
+        writeln!(w)?;
+    }
+    if let Some(hint) = &diagnostic.hint {
+        writeln!(w, "  hint: {hint}")?;
+    }
+    Ok(())
+}
+
+/// Print an error which was recovered from.
+pub fn warn(w: &mut impl WriteColor, err: &Error) -> Result<()> {
+    write_with_color!(w, theme().warning, "warning")?;
     writeln!(w, ": {err:#}")?;
     Ok(())
 }
 
-pub fn stdout() -> StandardStream {
-    StandardStream::stdout(color_choice(&std::io::stdout()))
+pub fn warning(w: &mut impl WriteColor, warning: &lint::Warning) -> Result<()> {
+    write_with_color!(w, theme().warning, "warning")?;
+    writeln!(
+        w,
+        ": {} at {}:{}",
+        warning.message, warning.line, warning.column
+    )?;
+    writeln!(w, "  help: {}", warning.help)?;
+    Ok(())
+}
+
+static CRLF: AtomicBool = AtomicBool::new(false);
+
+/// End lines written to STDOUT with CRLF rather than LF from now on.
+pub fn use_crlf() {
+    CRLF.store(true, Ordering::Relaxed);
+}
+
+/// Writes each LF not already preceded by a CR as CRLF.
+struct Crlf<W> {
+    inner: W,
+    cr: bool,
+}
+
+impl<W: WriteColor> io::Write for Crlf<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            match line.split_last() {
+                Some((b'\n', rest)) if !rest.last().map_or(self.cr, |&b| b == b'\r') => {
+                    self.inner.write_all(rest)?;
+                    self.inner.write_all(b"\r\n")?;
+                }
+                _ => self.inner.write_all(line)?,
+            }
+            self.cr = line.last() == Some(&b'\r');
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for Crlf<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.inner.reset()
+    }
+}
+
+/// STDOUT, or the pager if it's started. Output to STDOUT is buffered, and written as the buffer
+/// fills, so flush once done.
+pub fn stdout() -> Box<dyn WriteColor> {
+    let stream = BufferedStandardStream::stdout(color_choice(&std::io::stdout()));
+    let w: Box<dyn WriteColor> = if !pager::is_started() {
+        Box::new(stream)
+    } else if stream.supports_color() {
+        Box::new(Ansi::new(pager::Stdout))
+    } else {
+        Box::new(NoColor::new(pager::Stdout))
+    };
+    if CRLF.load(Ordering::Relaxed) {
+        Box::new(Crlf {
+            inner: w,
+            cr: false,
+        })
+    } else {
+        w
+    }
+}
+
+/// Print a result which was stringified only for --to, --head, or --tail, as text, as it would
+/// have been printed otherwise.
+///
+/// # Errors
+///
+/// If `s` isn't JSON, or writing fails.
+pub fn json_as_text(opts: &Options, s: &str) -> Result<()> {
+    let mut value = s.parse()?;
+    slice(opts, &mut value);
+    text(&js_string(&value))
+}
+
+/// `value` as a string, as JavaScript's `String` would convert it.
+fn js_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(arr) => arr
+            .iter()
+            .map(|v| {
+                if v.is_null() {
+                    String::new()
+                } else {
+                    js_string(v)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::Object(_) => String::from("[object Object]"),
+        _ => value.to_string(),
+    }
+}
+
+/// Print a string as is, followed by a newline unless it already ends with one.
+pub fn text(s: &str) -> Result<()> {
+    let mut w = stdout();
+    if s.ends_with('\n') {
+        write!(w, "{s}")?;
+    } else {
+        writeln!(w, "{s}")?;
+    }
+    w.flush()?;
+    Ok(())
 }
 
 pub fn stderr() -> StandardStream {
@@ -343,12 +2235,12 @@ pub fn stderr() -> StandardStream {
 
 pub fn yaml_to_string(s: &str) -> Result<String> {
     let mut buf = Vec::new();
-    yaml(&mut NoColor::new(&mut buf), s)?;
+    yaml(&mut NoColor::new(&mut buf), &Options::default(), s)?;
     Ok(String::from_utf8(buf)?)
 }
 
 pub fn toml_to_string(s: &str) -> Result<String> {
     let mut buf = Vec::new();
-    toml(&mut NoColor::new(&mut buf), s)?;
+    toml(&mut NoColor::new(&mut buf), &Options::default(), s)?;
     Ok(String::from_utf8(buf)?)
 }
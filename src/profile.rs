@@ -0,0 +1,101 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// A stage of processing that time is spent in.
+#[derive(Clone, Copy)]
+pub enum Stage {
+    /// Reading and parsing input.
+    Read,
+    /// Converting input to and results from JavaScript values.
+    Convert,
+    /// Evaluating SCRIPT itself.
+    Script,
+    /// Applying aggregation functions and adding to the aggregate.
+    Aggregate,
+    /// Printing results.
+    Print,
+}
+
+const STAGES: [(Stage, &str); 5] = [
+    (Stage::Read, "reading input"),
+    (Stage::Convert, "converting values"),
+    (Stage::Script, "evaluating SCRIPT"),
+    (Stage::Aggregate, "aggregating"),
+    (Stage::Print, "printing"),
+];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NANOS: [AtomicU64; STAGES.len()] = [const { AtomicU64::new(0) }; STAGES.len()];
+static EVALUATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Start timing stages. Until this is called, [`time`] just calls its argument.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Call `f`, adding the time it takes to the total for `stage`. Time spent on other threads is
+/// added to the same totals, so with several threads the totals can add up to more than the time
+/// elapsed.
+pub fn time<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+    let start = Instant::now();
+    let res = f();
+    let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+    NANOS[stage as usize].fetch_add(nanos, Ordering::Relaxed);
+    if matches!(stage, Stage::Script) {
+        EVALUATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+    res
+}
+
+/// The time spent in each stage since the last reset.
+pub struct Profile {
+    pub stages: Vec<(&'static str, Duration)>,
+    /// The number of times SCRIPT was evaluated.
+    pub evaluations: u32,
+    /// The average time taken to evaluate SCRIPT once.
+    pub per_evaluation: Duration,
+    /// The most memory the process has used at once, in bytes, where the OS makes it easy to find.
+    pub peak_memory: Option<u64>,
+}
+
+/// The peak resident set size of the process, which Linux reports in /proc/self/status.
+pub fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+pub fn profile() -> Profile {
+    let nanos = |stage: Stage| Duration::from_nanos(NANOS[stage as usize].load(Ordering::Relaxed));
+    let evaluations = u32::try_from(EVALUATIONS.load(Ordering::Relaxed)).unwrap_or(u32::MAX);
+    Profile {
+        stages: STAGES
+            .iter()
+            .map(|&(stage, name)| (name, nanos(stage)))
+            .collect(),
+        evaluations,
+        per_evaluation: nanos(Stage::Script)
+            .checked_div(evaluations)
+            .unwrap_or_default(),
+        peak_memory: peak_memory(),
+    }
+}
+
+pub fn reset() {
+    for nanos in &NANOS {
+        nanos.store(0, Ordering::Relaxed);
+    }
+    EVALUATIONS.store(0, Ordering::Relaxed);
+}
@@ -0,0 +1,149 @@
+//! Safe mode, turned on with --safe or `JSQ_SAFE=1`, for evaluating scripts which aren't trusted,
+//! such as those pasted from a runbook. SCRIPT can't read or write files, make requests, or run
+//! programs, and can't see the environment. Input and loops are limited in size, and anything which
+//! would give a different result from one run to the next fails instead.
+//!
+//! Each of the first three can also be taken away on its own, with --no-fs, --no-net, and
+//! --no-env, for scripts which need the rest.
+
+use std::{
+    io::{Read, Take},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{ensure, Result};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The most bytes of input read at once: the whole of STDIN or a file, or a single streamed value.
+const MAX_INPUT: usize = 64 << 20;
+
+/// The most iterations of any one loop in SCRIPT.
+pub const MAX_LOOP_ITERATIONS: u64 = 10_000_000;
+
+/// Something SCRIPT can do which reaches outside of jsq, and can be taken away.
+#[derive(Clone, Copy)]
+pub enum Capability {
+    /// Reading and writing files, with `read`, `write`, `require`, and `import`.
+    Fs,
+    /// Making requests, with `fetch`.
+    Net,
+    /// Seeing environment variables.
+    Env,
+}
+
+impl Capability {
+    fn flag(self) -> &'static str {
+        match self {
+            Capability::Fs => "--no-fs",
+            Capability::Net => "--no-net",
+            Capability::Env => "--no-env",
+        }
+    }
+}
+
+static DENIED: [AtomicBool; 3] = [const { AtomicBool::new(false) }; 3];
+
+/// JavaScript evaluated before anything else in safe mode, so that `Math.random`, `Date.now`, and
+/// `new Date()` without arguments fail, since they'd give a different result from one run to the
+/// next. `Date` is replaced wherever it can be reached, including as the constructor of a date, so
+/// that the original can't be used instead.
+pub const PRELUDE: &str = "(() => {
+    const disabled = (name) => () => {
+        throw new TypeError(`${name} is disabled by --safe`);
+    };
+    Math.random = disabled('Math.random');
+    Date.now = disabled('Date.now');
+    const SafeDate = new Proxy(Date, {
+        apply: disabled('Date()'),
+        construct: (target, args, newTarget) => args.length === 0
+            ? disabled('new Date()')()
+            : Reflect.construct(target, args, newTarget),
+    });
+    Date.prototype.constructor = SafeDate;
+    globalThis.Date = SafeDate;
+})()";
+
+/// Turn on safe mode for the rest of the run.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Fail if `what` is used in safe mode.
+pub fn forbid(what: &str) -> Result<()> {
+    ensure!(!enabled(), "{what} is disabled by --safe");
+    Ok(())
+}
+
+/// Take `capability` away from SCRIPT for the rest of the run.
+pub fn deny(capability: Capability) {
+    DENIED[capability as usize].store(true, Ordering::Relaxed);
+}
+
+/// Whether SCRIPT may use `capability`: not in safe mode, nor once it's been denied.
+pub fn allowed(capability: Capability) -> bool {
+    !enabled() && !DENIED[capability as usize].load(Ordering::Relaxed)
+}
+
+/// The error for using `what` without `capability`, naming the flag which took it away.
+pub fn denied(capability: Capability, what: &str) -> String {
+    let flag = if enabled() {
+        "--safe"
+    } else {
+        capability.flag()
+    };
+    format!("{what} is disabled by {flag}")
+}
+
+/// The environment, as SCRIPT sees it: empty in safe mode or with --no-env, so that secrets can't
+/// leak in to results.
+pub fn env() -> impl Iterator<Item = (String, String)> {
+    std::env::vars().filter(|_| allowed(Capability::Env))
+}
+
+/// Whether SCRIPT may run other programs: only once nothing has been taken away, since a program
+/// could do whatever SCRIPT can't.
+#[cfg(feature = "lua")]
+pub fn can_spawn() -> bool {
+    [Capability::Fs, Capability::Net, Capability::Env]
+        .into_iter()
+        .all(allowed)
+}
+
+/// Fail if reading `path` would reveal the environment after it's been hidden, as reading
+/// `/proc/self/environ` would.
+pub fn check_read(path: impl AsRef<Path>) -> Result<()> {
+    if allowed(Capability::Env) {
+        return Ok(());
+    }
+    let environ = path.as_ref().canonicalize().is_ok_and(|path| {
+        path.starts_with("/proc") && path.file_name().is_some_and(|name| name == "environ")
+    });
+    ensure!(!environ, denied(Capability::Env, "reading the environment"));
+    Ok(())
+}
+
+/// Stop reading from `reader` just past the limit in safe mode, so that [`check_input`] can tell
+/// that input was too large without reading all of it.
+pub fn limit<R: Read>(reader: R) -> Take<R> {
+    reader.take(if enabled() {
+        MAX_INPUT as u64 + 1
+    } else {
+        u64::MAX
+    })
+}
+
+/// Fail if `len` bytes of input are more than safe mode allows.
+pub fn check_input(len: usize) -> Result<()> {
+    ensure!(
+        !enabled() || len <= MAX_INPUT,
+        "input is larger than the {} MiB allowed by --safe",
+        MAX_INPUT >> 20
+    );
+    Ok(())
+}
@@ -0,0 +1,601 @@
+//! Validation against a JSON Schema, given with --schema. The keywords for types, values, objects,
+//! arrays, numbers, strings, and combining schemas are checked, as are `$ref`s within the schema.
+//! Of the formats, `date-time`, `date`, `time`, `email`, `ipv4`, `ipv6`, `uri`, and `uuid` are
+//! checked, and any others are ignored, as the specification allows.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{parse, print::TypeHint, stats};
+
+/// The most `$ref`s to follow within a single path, so that a schema which refers to itself
+/// without ever reaching a value doesn't recurse forever.
+const MAX_DEPTH: usize = 256;
+
+/// Where a value breaks a schema, and how.
+pub struct Violation {
+    /// A JSON Pointer to the value in the document.
+    pub pointer: String,
+    pub message: String,
+}
+
+pub struct Schema {
+    path: String,
+    root: Value,
+    /// Each `pattern` and key of `patternProperties` compiled so far, or why it couldn't be.
+    patterns: RefCell<HashMap<String, Result<Regex, String>>>,
+}
+
+impl Schema {
+    /// Read the schema at `path`, which is parsed as YAML if its extension says so, and as JSON
+    /// otherwise.
+    pub fn load(path: &str) -> Result<Self> {
+        let s = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+        let yaml = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("yaml") || e.eq_ignore_ascii_case("yml"));
+        let json = if yaml {
+            parse::yaml(&s)
+        } else {
+            parse::json(&s)
+        }
+        .with_context(|| format!("parsing {path}"))?;
+        Ok(Self {
+            path: path.to_string(),
+            root: serde_json::from_str(&json)?,
+            patterns: RefCell::new(HashMap::new()),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// How to print fields in YAML and TOML, given with --type-hints. A file which is just an object
+    /// from field names or JSON Pointers to hints is the hints themselves. Otherwise, it's a schema,
+    /// in which values with a date or time `format` are dates, and those of type `integer` are
+    /// integers, by the pointers to them.
+    pub fn type_hints(&self) -> Result<HashMap<String, TypeHint>> {
+        if let Value::Object(hints) = &self.root
+            && hints.values().all(Value::is_string)
+        {
+            return hints
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), TypeHint::parse(v.as_str().unwrap_or_default())?)))
+                .collect::<Result<_>>()
+                .with_context(|| format!("reading type hints from {}", self.path));
+        }
+        let mut hints = HashMap::new();
+        collect_type_hints(&self.root, &self.root, "", &mut Vec::new(), &mut hints);
+        Ok(hints)
+    }
+
+    /// Every way in which `value` breaks the schema, in document order.
+    pub fn validate(&self, value: &Value) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        Validator {
+            root: &self.root,
+            patterns: &self.patterns,
+            violations: &mut violations,
+        }
+        .check(&self.root, value, "", 0);
+        violations
+    }
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    patterns: &'a RefCell<HashMap<String, Result<Regex, String>>>,
+    violations: &'a mut Vec<Violation>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, pointer: &str, message: String) {
+        self.violations.push(Violation {
+            pointer: pointer.to_string(),
+            message,
+        });
+    }
+
+    /// Whether `value` matches `schema`, without reporting anything, for `anyOf`, `oneOf`, `not`,
+    /// and `if`.
+    fn matches(&self, schema: &Value, value: &Value, depth: usize) -> bool {
+        let mut violations = Vec::new();
+        Validator {
+            root: self.root,
+            patterns: self.patterns,
+            violations: &mut violations,
+        }
+        .check(schema, value, "", depth);
+        violations.is_empty()
+    }
+
+    /// Whether `s` matches the regular expression `pattern`, reporting a pattern which isn't one.
+    fn is_match(&mut self, pointer: &str, pattern: &str, s: &str) -> bool {
+        let mut patterns = self.patterns.borrow_mut();
+        let re = patterns
+            .entry(pattern.to_string())
+            .or_insert_with(|| Regex::new(pattern).map_err(|err| err.to_string()));
+        match re {
+            Ok(re) => re.is_match(s),
+            Err(err) => {
+                let message = format!("can't check pattern {pattern}: {err}");
+                drop(patterns);
+                self.report(pointer, message);
+                false
+            }
+        }
+    }
+
+    fn check(&mut self, schema: &Value, value: &Value, pointer: &str, depth: usize) {
+        let schema = match schema {
+            Value::Bool(false) => return self.report(pointer, String::from("no value is allowed")),
+            Value::Object(schema) => schema,
+            // true, which allows anything.
+            _ => return,
+        };
+
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            match resolve(self.root, reference) {
+                _ if depth >= MAX_DEPTH => {
+                    return self.report(pointer, format!("{reference} refers to itself"));
+                }
+                Some(target) => self.check(target, value, pointer, depth + 1),
+                None => self.report(pointer, format!("can't resolve {reference}")),
+            }
+        }
+
+        self.check_any(schema, value, pointer, depth);
+        match value {
+            Value::Object(object) => self.check_object(schema, object, pointer, depth),
+            Value::Array(array) => self.check_array(schema, array, pointer, depth),
+            Value::Number(n) => self.check_number(schema, n, pointer),
+            Value::String(s) => self.check_string(schema, s, pointer),
+            Value::Null | Value::Bool(_) => {}
+        }
+    }
+
+    /// The keywords which apply to values of any type.
+    fn check_any(
+        &mut self,
+        schema: &Map<String, Value>,
+        value: &Value,
+        pointer: &str,
+        depth: usize,
+    ) {
+        let types = match schema.get("type") {
+            Some(Value::String(t)) => vec![t.as_str()],
+            Some(Value::Array(ts)) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| is_type(value, t)) {
+            self.report(
+                pointer,
+                format!("expected {}, found {}", types.join(" or "), type_of(value)),
+            );
+        }
+
+        if let Some(Value::Array(values)) = schema.get("enum")
+            && !values.iter().any(|v| equal(v, value))
+        {
+            let values = values.iter().map(Value::to_string).collect::<Vec<_>>();
+            self.report(pointer, format!("expected one of {}", values.join(", ")));
+        }
+        if let Some(constant) = schema.get("const")
+            && !equal(constant, value)
+        {
+            self.report(pointer, format!("expected {constant}"));
+        }
+
+        if let Some(Value::Array(schemas)) = schema.get("allOf") {
+            for schema in schemas {
+                self.check(schema, value, pointer, depth + 1);
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("anyOf")
+            && !schemas.iter().any(|s| self.matches(s, value, depth + 1))
+        {
+            self.report(pointer, String::from("doesn't match any schema in anyOf"));
+        }
+        if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+            let n = schemas
+                .iter()
+                .filter(|s| self.matches(s, value, depth + 1))
+                .count();
+            if n != 1 {
+                self.report(pointer, format!("matches {n} schemas in oneOf, not 1"));
+            }
+        }
+        if let Some(not) = schema.get("not")
+            && self.matches(not, value, depth + 1)
+        {
+            self.report(pointer, String::from("matches the schema in not"));
+        }
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.matches(condition, value, depth + 1) {
+                schema.get("then")
+            } else {
+                schema.get("else")
+            };
+            if let Some(branch) = branch {
+                self.check(branch, value, pointer, depth + 1);
+            }
+        }
+    }
+
+    fn check_object(
+        &mut self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        pointer: &str,
+        depth: usize,
+    ) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    self.report(pointer, format!("missing required property {key}"));
+                }
+            }
+        }
+        self.check_count(
+            pointer,
+            object.len(),
+            schema.get("minProperties"),
+            schema.get("maxProperties"),
+            ("property", "properties"),
+        );
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let patterns = schema.get("patternProperties").and_then(Value::as_object);
+        for (key, value) in object {
+            let pointer = format!("{pointer}/{}", escape(key));
+            let mut matched = false;
+            if let Some(property) = properties.and_then(|p| p.get(key)) {
+                self.check(property, value, &pointer, depth + 1);
+                matched = true;
+            }
+            for (pattern, property) in patterns.into_iter().flatten() {
+                if self.is_match(&pointer, pattern, key) {
+                    self.check(property, value, &pointer, depth + 1);
+                    matched = true;
+                }
+            }
+            if matched {
+                continue;
+            }
+            match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    self.report(&pointer, String::from("property isn't allowed"));
+                }
+                Some(additional) => self.check(additional, value, &pointer, depth + 1),
+                None => {}
+            }
+        }
+    }
+
+    fn check_array(
+        &mut self,
+        schema: &Map<String, Value>,
+        array: &[Value],
+        pointer: &str,
+        depth: usize,
+    ) {
+        self.check_count(
+            pointer,
+            array.len(),
+            schema.get("minItems"),
+            schema.get("maxItems"),
+            ("item", "items"),
+        );
+
+        // Before 2020-12, an array of schemas in items did what prefixItems does now.
+        let (prefix, rest) = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(Value::Array(prefix)), rest) => (prefix.as_slice(), rest),
+            (None, Some(Value::Array(prefix))) => {
+                (prefix.as_slice(), schema.get("additionalItems"))
+            }
+            (_, rest) => (&[][..], rest),
+        };
+        for (i, value) in array.iter().enumerate() {
+            let pointer = format!("{pointer}/{i}");
+            match prefix.get(i).or(rest) {
+                Some(Value::Bool(false)) => {
+                    self.report(&pointer, String::from("item isn't allowed"));
+                }
+                Some(item) => self.check(item, value, &pointer, depth + 1),
+                None => {}
+            }
+        }
+
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            for (i, value) in array.iter().enumerate() {
+                if let Some(j) = array[..i].iter().position(|v| equal(v, value)) {
+                    self.report(&format!("{pointer}/{i}"), format!("duplicates item {j}"));
+                }
+            }
+        }
+    }
+
+    fn check_number(&mut self, schema: &Map<String, Value>, n: &serde_json::Number, pointer: &str) {
+        let Some(x) = n.as_f64() else {
+            return;
+        };
+        let bound = |key| schema.get(key).and_then(Value::as_f64);
+        if let Some(min) = bound("minimum")
+            && x < min
+        {
+            self.report(pointer, format!("expected at least {min}, found {n}"));
+        }
+        if let Some(max) = bound("maximum")
+            && x > max
+        {
+            self.report(pointer, format!("expected at most {max}, found {n}"));
+        }
+        if let Some(min) = bound("exclusiveMinimum")
+            && x <= min
+        {
+            self.report(pointer, format!("expected more than {min}, found {n}"));
+        }
+        if let Some(max) = bound("exclusiveMaximum")
+            && x >= max
+        {
+            self.report(pointer, format!("expected less than {max}, found {n}"));
+        }
+        // Steps such as 0.1 can't be represented exactly, so the quotient need only be close to a
+        // whole number.
+        if let Some(step) = bound("multipleOf")
+            && step > 0.0
+            && ((x / step) - (x / step).round()).abs() > 1e-9 * (x / step).abs().max(1.0)
+        {
+            self.report(pointer, format!("expected a multiple of {step}, found {n}"));
+        }
+    }
+
+    fn check_string(&mut self, schema: &Map<String, Value>, s: &str, pointer: &str) {
+        self.check_count(
+            pointer,
+            s.chars().count(),
+            schema.get("minLength"),
+            schema.get("maxLength"),
+            ("character", "characters"),
+        );
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str)
+            && !self.is_match(pointer, pattern, s)
+        {
+            self.report(pointer, format!("expected to match {pattern}"));
+        }
+        if let Some(format) = schema.get("format").and_then(Value::as_str)
+            && !is_format(format, s)
+        {
+            self.report(pointer, format!("expected a {format}, found {s}"));
+        }
+    }
+
+    /// Check a count of properties, items, or characters against a minimum and maximum.
+    fn check_count(
+        &mut self,
+        pointer: &str,
+        n: usize,
+        min: Option<&Value>,
+        max: Option<&Value>,
+        (one, many): (&str, &str),
+    ) {
+        let as_usize =
+            |v: Option<&Value>| v.and_then(Value::as_u64).and_then(|n| n.try_into().ok());
+        let what = |n: usize| if n == 1 { one } else { many };
+        if let Some(min) = as_usize(min)
+            && n < min
+        {
+            self.report(
+                pointer,
+                format!("expected at least {min} {}, found {n}", what(min)),
+            );
+        }
+        if let Some(max) = as_usize(max)
+            && n > max
+        {
+            self.report(
+                pointer,
+                format!("expected at most {max} {}, found {n}", what(max)),
+            );
+        }
+    }
+}
+
+/// The schema `reference` points at within `root`, which must be the root itself or a JSON Pointer
+/// in to it, e.g. `#/$defs/port`.
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+        return Some(root);
+    }
+    // Within a URI fragment, the pointer may be percent-encoded.
+    root.pointer(&percent_decode(pointer)?)
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+    while let Some((&b, r)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(r.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &r[2..];
+        } else {
+            bytes.push(b);
+            rest = r;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn type_hint(schema: &Map<String, Value>) -> Option<TypeHint> {
+    let format = schema.get("format").and_then(Value::as_str);
+    let is_integer = match schema.get("type") {
+        Some(Value::String(t)) => t == "integer",
+        Some(Value::Array(ts)) => {
+            ts.iter().any(|t| t == "integer") && !ts.iter().any(|t| t == "number")
+        }
+        _ => false,
+    };
+    if matches!(format, Some("date-time" | "date" | "time")) {
+        Some(TypeHint::Date)
+    } else if is_integer {
+        Some(TypeHint::Integer)
+    } else {
+        None
+    }
+}
+
+/// Collect the type hints of the value `schema` describes, which is at `pointer`, and of the values
+/// within it, by JSON Pointers in which `*` stands for any index or key. Each `$ref` is followed at
+/// most once on the way to a value, so that recursive schemas come to an end.
+fn collect_type_hints<'a>(
+    root: &'a Value,
+    schema: &'a Value,
+    pointer: &str,
+    refs: &mut Vec<&'a str>,
+    hints: &mut HashMap<String, TypeHint>,
+) {
+    let Value::Object(schema) = schema else {
+        return;
+    };
+    if let Some(Value::String(reference)) = schema.get("$ref")
+        && !refs.contains(&reference.as_str())
+        && let Some(target) = resolve(root, reference)
+    {
+        refs.push(reference);
+        collect_type_hints(root, target, pointer, refs, hints);
+        refs.pop();
+    }
+    if !pointer.is_empty()
+        && let Some(hint) = type_hint(schema)
+    {
+        hints.insert(pointer.to_string(), hint);
+    }
+    // Subschemas which describe the same value.
+    let same = ["allOf", "anyOf", "oneOf"]
+        .iter()
+        .filter_map(|key| schema.get(*key).and_then(Value::as_array))
+        .flatten()
+        .chain(
+            ["if", "then", "else"]
+                .iter()
+                .filter_map(|key| schema.get(*key)),
+        );
+    for subschema in same {
+        collect_type_hints(root, subschema, pointer, refs, hints);
+    }
+    if let Some(Value::Object(properties)) = schema.get("properties") {
+        for (key, property) in properties {
+            let pointer = format!("{pointer}/{}", escape(key));
+            collect_type_hints(root, property, &pointer, refs, hints);
+        }
+    }
+    let tuple = match schema.get("prefixItems").or_else(|| schema.get("items")) {
+        Some(Value::Array(items)) => items.as_slice(),
+        _ => &[],
+    };
+    for (i, item) in tuple.iter().enumerate() {
+        collect_type_hints(root, item, &format!("{pointer}/{i}"), refs, hints);
+    }
+    let any = ["items", "additionalProperties"]
+        .iter()
+        .filter_map(|key| schema.get(*key))
+        .filter(|s| s.is_object())
+        .chain(
+            schema
+                .get("patternProperties")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flat_map(Map::values),
+        );
+    for subschema in any {
+        collect_type_hints(root, subschema, &format!("{pointer}/*"), refs, hints);
+    }
+}
+
+/// Escape a key for a JSON Pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn is_integer(n: &serde_json::Number) -> bool {
+    !stats::canonical_number(n)
+        .to_string()
+        .contains(['.', 'e', 'E'])
+}
+
+fn is_type(value: &Value, t: &str) -> bool {
+    match (t, value) {
+        ("integer", Value::Number(n)) => is_integer(n),
+        _ => type_of(value) == t || t == "number" && value.is_number(),
+    }
+}
+
+/// Whether `s` is of the named `format`, or `format` isn't one which is checked.
+fn is_format(format: &str, s: &str) -> bool {
+    let datetime = || s.parse::<toml::value::Datetime>().ok();
+    match format {
+        "date-time" => datetime().is_some_and(|d| d.date.is_some() && d.offset.is_some()),
+        "date" => datetime().is_some_and(|d| d.date.is_some() && d.time.is_none()),
+        "time" => datetime().is_some_and(|d| d.date.is_none() && d.time.is_some()),
+        "email" => s
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.')),
+        "ipv4" => s.parse::<Ipv4Addr>().is_ok(),
+        "ipv6" => s.parse::<Ipv6Addr>().is_ok(),
+        "uri" => s.split_once(':').is_some_and(|(scheme, _)| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }),
+        "uuid" => {
+            let groups = s.split('-').map(str::len).collect::<Vec<_>>();
+            groups == [8, 4, 4, 4, 12] && s.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+        }
+        _ => true,
+    }
+}
+
+/// The name of the type of `value`, as JSON Schema has it.
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if is_integer(n) => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Whether two values are equal, comparing numbers by value, so that 1 and 1.0 are the same.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            stats::canonical_number(x) == stats::canonical_number(y)
+        }
+        (Value::Array(xs), Value::Array(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| equal(x, y))
+        }
+        (Value::Object(xs), Value::Object(ys)) => {
+            xs.len() == ys.len()
+                && xs
+                    .iter()
+                    .all(|(k, x)| ys.get(k).is_some_and(|y| equal(x, y)))
+        }
+        _ => a == b,
+    }
+}
@@ -0,0 +1,159 @@
+//! A faster path for parsing large JSON inputs, with simd-json, turned on by the `simd` feature.
+//! The tape simd-json parses in to is written straight back out as a JSON string, without building
+//! a `serde_json::Value` in between, which is where most of the time would otherwise go.
+//!
+//! simd-json reads numbers as 64-bit integers or doubles, which can't be written back exactly as
+//! they were, so each number is copied from the input instead, found by reading the input alongside
+//! the tape. Any input with a key which appears more than once in an object is left to
+//! `serde_json`, as is any input with an error, so that the error is reported as usual.
+
+use std::{collections::HashSet, fmt::Write};
+
+use simd_json::{tape::Node, StaticNode};
+
+/// Nesting any deeper than this is left to `serde_json`, which refuses it.
+const MAX_DEPTH: usize = 128;
+
+/// Objects with up to this many keys are checked for duplicates one key against another, rather
+/// than with a set.
+const FEW_KEYS: usize = 16;
+
+/// Parse JSON in to a JSON string, or `None` if it should be parsed by `serde_json` instead.
+pub fn json(s: &str) -> Option<String> {
+    let mut bytes = s.as_bytes().to_vec();
+    let tape = simd_json::to_tape(&mut bytes).ok()?;
+    let mut nodes = tape.0.into_iter();
+    let mut numbers = Numbers { s, pos: 0 };
+    let mut json = String::with_capacity(s.len());
+    write_value(&mut json, &mut nodes, &mut numbers, 0)?;
+    Some(json)
+}
+
+/// The numbers in a JSON input which is known to be valid, as they were written, in order.
+struct Numbers<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Numbers<'a> {
+    /// The next number, skipping over strings, which are the only other place digits can be.
+    fn next(&mut self) -> Option<&'a str> {
+        let bytes = self.s.as_bytes();
+        while let Some(&b) = bytes.get(self.pos) {
+            match b {
+                b'"' => {
+                    self.pos += 1;
+                    loop {
+                        let i = bytes[self.pos..]
+                            .iter()
+                            .position(|&b| b == b'"' || b == b'\\')?;
+                        self.pos += i + 1;
+                        if bytes[self.pos - 1] == b'"' {
+                            break;
+                        }
+                        // Skip whatever was escaped, which might be a quote.
+                        self.pos += 1;
+                    }
+                }
+                b'-' | b'0'..=b'9' => {
+                    let start = self.pos;
+                    self.pos += bytes[start..]
+                        .iter()
+                        .position(|b| !matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+                        .unwrap_or(bytes.len() - start);
+                    return Some(&self.s[start..self.pos]);
+                }
+                _ => self.pos += 1,
+            }
+        }
+        None
+    }
+}
+
+/// Write the value starting at the next of `nodes`, which are in document order, with each array
+/// or object followed by its elements or its keys and values.
+fn write_value<'a>(
+    json: &mut String,
+    nodes: &mut impl Iterator<Item = Node<'a>>,
+    numbers: &mut Numbers,
+    depth: usize,
+) -> Option<()> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    match nodes.next()? {
+        Node::String(s) => write_str(json, s),
+        Node::Static(StaticNode::Null) => json.push_str("null"),
+        Node::Static(StaticNode::Bool(b)) => json.push_str(if b { "true" } else { "false" }),
+        // serde_json keeps numbers as they were written, e.g. 1.50, 1e3, or -0.
+        Node::Static(StaticNode::I64(_) | StaticNode::U64(_) | StaticNode::F64(_)) => {
+            json.push_str(numbers.next()?);
+        }
+        Node::Array { len, .. } => {
+            json.push('[');
+            for i in 0..len {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_value(json, nodes, numbers, depth + 1)?;
+            }
+            json.push(']');
+        }
+        Node::Object { len, .. } => {
+            let mut keys = Vec::with_capacity(len.min(FEW_KEYS));
+            let mut set = HashSet::new();
+            json.push('{');
+            for i in 0..len {
+                let Node::String(key) = nodes.next()? else {
+                    return None;
+                };
+                let dupe = if len <= FEW_KEYS {
+                    let dupe = keys.contains(&key);
+                    keys.push(key);
+                    dupe
+                } else {
+                    !set.insert(key)
+                };
+                if dupe {
+                    return None;
+                }
+                if i > 0 {
+                    json.push(',');
+                }
+                write_str(json, key);
+                json.push(':');
+                write_value(json, nodes, numbers, depth + 1)?;
+            }
+            json.push('}');
+        }
+    }
+    Some(())
+}
+
+/// Write `s` as a JSON string, escaped as `serde_json` escapes it.
+fn write_str(json: &mut String, s: &str) {
+    json.push('"');
+    let mut start = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let escape = match b {
+            b'"' => "\\\"",
+            b'\\' => "\\\\",
+            b'\n' => "\\n",
+            b'\r' => "\\r",
+            b'\t' => "\\t",
+            0x08 => "\\b",
+            0x0c => "\\f",
+            0..0x20 => "",
+            _ => continue,
+        };
+        json.push_str(&s[start..i]);
+        if escape.is_empty() {
+            write!(json, "\\u{b:04x}").ok();
+        } else {
+            json.push_str(escape);
+        }
+        start = i + 1;
+    }
+    json.push_str(&s[start..]);
+    json.push('"');
+}
@@ -0,0 +1,98 @@
+//! Soft limits on what a run uses, set with --warn-memory, --warn-output, and --warn-record-time.
+//! Unlike --max-requests, crossing one doesn't fail the run. Instead a warning is printed to STDERR
+//! as a JSON object on a line of its own, with the record being evaluated and what was measured, so
+//! that limits can be tuned before jobs start failing.
+
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+
+use crate::{print, profile};
+
+/// The most memory, in bytes, output, in bytes, and time evaluating a single record, that a run
+/// may use before a warning is printed.
+#[derive(Clone, Copy, Default)]
+pub struct Limits {
+    pub memory: Option<u64>,
+    pub output: Option<u64>,
+    pub record_time: Option<Duration>,
+}
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+static RECORD: AtomicUsize = AtomicUsize::new(0);
+static OUTPUT: AtomicU64 = AtomicU64::new(0);
+// Memory and output only grow, so each is warned about once.
+static WARNED_MEMORY: AtomicBool = AtomicBool::new(false);
+static WARNED_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Warn when a run crosses `limits` from now on.
+pub fn set_limits(limits: Limits) {
+    LIMITS.set(limits).ok();
+}
+
+fn limits() -> Limits {
+    LIMITS.get().copied().unwrap_or_default()
+}
+
+/// Start afresh, from the first record, with no output.
+pub fn reset() {
+    RECORD.store(0, Ordering::Relaxed);
+    OUTPUT.store(0, Ordering::Relaxed);
+    WARNED_MEMORY.store(false, Ordering::Relaxed);
+    WARNED_OUTPUT.store(false, Ordering::Relaxed);
+}
+
+fn warn(limit: &str, value: &serde_json::Value, max: &serde_json::Value) -> Result<()> {
+    let warning = json!({
+        "warning": limit,
+        "record": RECORD.load(Ordering::Relaxed),
+        "value": value,
+        "limit": max,
+    });
+    writeln!(print::stderr(), "{warning}").context("printing warning")
+}
+
+/// Count `bytes` of output from the record being evaluated.
+pub fn output(bytes: usize) -> Result<()> {
+    let bytes = bytes as u64;
+    let total = OUTPUT.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    if let Some(max) = limits().output
+        && total > max
+        && !WARNED_OUTPUT.swap(true, Ordering::Relaxed)
+    {
+        warn("output", &json!(total), &json!(max))?;
+    }
+    Ok(())
+}
+
+/// Check memory, and the time the record just evaluated took, if it's known, and move on to the
+/// next record.
+pub fn record(elapsed: Option<Duration>) -> Result<()> {
+    let limits = limits();
+    if let (Some(max), Some(elapsed)) = (limits.record_time, elapsed)
+        && elapsed > max
+    {
+        warn(
+            "record-time",
+            &json!(elapsed.as_secs_f64()),
+            &json!(max.as_secs_f64()),
+        )?;
+    }
+    if let Some(max) = limits.memory
+        && let Some(peak) = profile::peak_memory()
+        && peak > max
+        && !WARNED_MEMORY.swap(true, Ordering::Relaxed)
+    {
+        warn("memory", &json!(peak), &json!(max))?;
+    }
+    RECORD.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
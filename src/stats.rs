@@ -0,0 +1,392 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    num::NonZeroUsize,
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+/// Split a JSON result in to the values to be aggregated: the elements of an array, a single
+/// value otherwise, or nothing if the result is undefined.
+pub fn spread(res: &str) -> Result<Vec<String>> {
+    if res == "undefined" {
+        return Ok(Vec::new());
+    }
+    Ok(match serde_json::from_str(res)? {
+        Value::Array(arr) => arr.iter().map(Value::to_string).collect(),
+        value => vec![value.to_string()],
+    })
+}
+
+/// Counts how many times each distinct value occurs.
+#[derive(Default)]
+pub struct Frequencies {
+    counts: Vec<(String, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl Frequencies {
+    pub fn add(&mut self, value: String) {
+        if let Some(&i) = self.index.get(&value) {
+            self.counts[i].1 += 1;
+        } else {
+            self.index.insert(value.clone(), self.counts.len());
+            self.counts.push((value, 1));
+        }
+    }
+
+    /// Each distinct value and its count, most frequent first. Ties are broken by the order in
+    /// which values were first seen.
+    pub fn sorted(&self) -> Vec<(&str, usize)> {
+        let mut res = self
+            .counts
+            .iter()
+            .map(|(value, count)| (value.as_str(), *count))
+            .collect::<Vec<_>>();
+        res.sort_by_key(|&(_, count)| Reverse(count));
+        res
+    }
+}
+
+/// Groups values by key, remembering the first value seen with each key.
+#[derive(Default)]
+pub struct Duplicates {
+    frequencies: Frequencies,
+    representatives: Vec<String>,
+}
+
+impl Duplicates {
+    pub fn add(&mut self, key: String, value: String) {
+        if !self.frequencies.index.contains_key(&key) {
+            self.representatives.push(value);
+        }
+        self.frequencies.add(key);
+    }
+
+    /// Each key shared by more than one value, with its count and the first value seen with it,
+    /// most frequent first.
+    pub fn sorted(&self) -> Vec<(&str, usize, &str)> {
+        let mut res = self
+            .frequencies
+            .counts
+            .iter()
+            .zip(&self.representatives)
+            .filter(|((_, count), _)| *count > 1)
+            .map(|((key, count), value)| (key.as_str(), *count, value.as_str()))
+            .collect::<Vec<_>>();
+        res.sort_by_key(|&(_, count, _)| Reverse(count));
+        res
+    }
+}
+
+/// Collects numeric values to be bucketed in to a histogram.
+#[derive(Default)]
+pub struct Histogram {
+    values: Vec<f64>,
+}
+
+impl Histogram {
+    /// Add a JSON number. Nulls (and undefined) are skipped as missing values.
+    pub fn add(&mut self, value: &str) -> Result<()> {
+        if value == "undefined" {
+            return Ok(());
+        }
+        match serde_json::from_str(value)? {
+            Value::Null => {}
+            Value::Number(n) => self.values.push(n.as_f64().context("converting to f64")?),
+            _ => bail!("expected a number, got {value}"),
+        }
+        Ok(())
+    }
+
+    /// Split the range of values in to `n` buckets of equal width, returning the lower bound,
+    /// upper bound, and count of each.
+    #[expect(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn buckets(&self, n: NonZeroUsize) -> Vec<(f64, f64, usize)> {
+        let (Some(min), Some(max)) = (
+            self.values.iter().copied().reduce(f64::min),
+            self.values.iter().copied().reduce(f64::max),
+        ) else {
+            return Vec::new();
+        };
+        let n = n.get();
+        let width = (max - min) / n as f64;
+        if width == 0.0 {
+            return vec![(min, max, self.values.len())];
+        }
+        let mut counts = vec![0; n];
+        for v in &self.values {
+            counts[(((v - min) / width) as usize).min(n - 1)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| (min + width * i as f64, min + width * (i + 1) as f64, count))
+            .collect()
+    }
+}
+
+/// The path and type of everything in a value, with the elements of arrays sharing a path, whose
+/// type is `mixed` if they don't all have the same one.
+type Shape = BTreeMap<String, &'static str>;
+
+fn shape(path: &mut String, value: &Value, res: &mut Shape) {
+    let kind = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(arr) => {
+            let len = path.len();
+            path.push_str("[]");
+            for v in arr {
+                shape(path, v, res);
+            }
+            path.truncate(len);
+            "array"
+        }
+        Value::Object(obj) => {
+            let len = path.len();
+            for (k, v) in obj {
+                path.push('.');
+                path.push_str(k);
+                shape(path, v, res);
+                path.truncate(len);
+            }
+            "object"
+        }
+    };
+    res.entry(path.clone())
+        .and_modify(|k| {
+            if *k != kind {
+                *k = "mixed";
+            }
+        })
+        .or_insert(kind);
+}
+
+/// Tracks the shape of each value, to report those which deviate from the shape of the majority.
+#[derive(Default)]
+pub struct Shapes {
+    /// Each distinct shape, and the (1 based) positions of the values with it.
+    distinct: Vec<(Shape, Vec<usize>)>,
+    index: HashMap<Shape, usize>,
+    count: usize,
+}
+
+impl Shapes {
+    pub fn add(&mut self, value: &str) -> Result<()> {
+        let mut res = Shape::new();
+        shape(
+            &mut String::from("$"),
+            &serde_json::from_str(value)?,
+            &mut res,
+        );
+        self.count += 1;
+        if let Some(&i) = self.index.get(&res) {
+            self.distinct[i].1.push(self.count);
+        } else {
+            self.index.insert(res.clone(), self.distinct.len());
+            self.distinct.push((res, vec![self.count]));
+        }
+        Ok(())
+    }
+
+    /// The paths present in most values, with the type they most often have.
+    fn majority(&self) -> Shape {
+        let mut types = BTreeMap::<&str, HashMap<&'static str, usize>>::new();
+        for (shape, positions) in &self.distinct {
+            for (path, kind) in shape {
+                *types.entry(path).or_default().entry(kind).or_default() += positions.len();
+            }
+        }
+        types
+            .into_iter()
+            .filter(|(_, kinds)| kinds.values().sum::<usize>() * 2 > self.count)
+            .filter_map(|(path, kinds)| {
+                let kind = kinds.into_iter().max_by_key(|&(_, count)| count)?.0;
+                Some((path.to_owned(), kind))
+            })
+            .collect()
+    }
+
+    /// Each value which deviates from the majority shape, by position, with the path and a
+    /// description of each deviation. Deviations inside a path which itself deviates are left
+    /// out.
+    pub fn drift(&self) -> Vec<(usize, Vec<(String, String)>)> {
+        let majority = self.majority();
+        let mut res = Vec::new();
+        for (shape, positions) in &self.distinct {
+            let mut deviations = Vec::<(String, String)>::new();
+            let paths = majority
+                .keys()
+                .chain(shape.keys().filter(|p| !majority.contains_key(*p)));
+            let mut paths = paths.collect::<Vec<_>>();
+            paths.sort();
+            for path in paths {
+                let in_empty_array = |path: &str| {
+                    path.match_indices("[]").any(|(i, _)| {
+                        shape.get(&path[..i]) == Some(&"array")
+                            && !shape.contains_key(&path[..i + 2])
+                    })
+                };
+                let within = |parent: &str| {
+                    path.strip_prefix(parent)
+                        .is_some_and(|rest| rest.starts_with('.') || rest.starts_with('['))
+                };
+                if deviations.iter().any(|(parent, _)| within(parent)) {
+                    continue;
+                }
+                let description = match (majority.get(path), shape.get(path)) {
+                    // Elements of empty arrays aren't missing.
+                    (Some(_), None) if in_empty_array(path) => continue,
+                    (Some(_), None) => String::from("missing"),
+                    (None, Some(kind)) => format!("new {kind}"),
+                    (Some(expected), Some(kind)) if expected != kind => {
+                        format!("{kind}, expected {expected}")
+                    }
+                    _ => continue,
+                };
+                deviations.push((path.clone(), description));
+            }
+            if !deviations.is_empty() {
+                res.extend(positions.iter().map(|&i| (i, deviations.clone())));
+            }
+        }
+        res.sort_by_key(|&(i, _)| i);
+        res
+    }
+}
+
+/// Collects values as the rows of a table, with a column for each key of the objects among them.
+/// Values which aren't objects go in a column of their own, `$`.
+#[derive(Default)]
+pub struct Table {
+    /// The columns, in the order their keys were first seen.
+    columns: Vec<String>,
+    index: HashMap<String, usize>,
+    rows: Vec<Value>,
+}
+
+impl Table {
+    pub fn add(&mut self, value: &str) -> Result<()> {
+        let value = serde_json::from_str(value)?;
+        let keys = match &value {
+            Value::Object(map) => map.keys().map(String::as_str).collect(),
+            _ => vec!["$"],
+        };
+        for key in keys {
+            if !self.index.contains_key(key) {
+                self.index.insert(key.to_owned(), self.columns.len());
+                self.columns.push(key.to_owned());
+            }
+        }
+        self.rows.push(value);
+        Ok(())
+    }
+
+    /// The columns, and each row as its value in each column, if it has one.
+    pub fn rows(&self) -> (Vec<&str>, Vec<Vec<Option<&Value>>>) {
+        let columns = self.columns.iter().map(String::as_str).collect();
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                self.columns
+                    .iter()
+                    .map(|column| match row {
+                        Value::Object(map) => map.get(column),
+                        _ => (column == "$").then_some(row),
+                    })
+                    .collect()
+            })
+            .collect();
+        (columns, rows)
+    }
+}
+
+/// Rewrite a JSON number canonically, so that numbers which are equal are written the same way:
+/// integers without a fraction or exponent, and anything else in the shortest form which reads
+/// back as the same float. Integers too large for a float to hold exactly are left as they are.
+pub fn canonical_number(n: &serde_json::Number) -> serde_json::Number {
+    let s = n.to_string();
+    if !s.contains(['.', 'e', 'E']) {
+        return if s == "-0" { 0.into() } else { n.clone() };
+    }
+    let Some(f) = n.as_f64() else {
+        return n.clone();
+    };
+    // 2^53, the largest integer below which every integer is exactly representable as a float.
+    if f.fract() == 0.0 && f.abs() < 9_007_199_254_740_992.0 {
+        #[expect(clippy::cast_possible_truncation)]
+        return (f as i64).into();
+    }
+    serde_json::Number::from_f64(f).unwrap_or_else(|| n.clone())
+}
+
+/// Rewrite a JSON value canonically: objects with their keys sorted, and numbers as by
+/// `canonical_number`.
+fn canonical(value: &mut Value) {
+    match value {
+        Value::Number(n) => *n = canonical_number(n),
+        Value::Array(values) => values.iter_mut().for_each(canonical),
+        Value::Object(map) => {
+            map.values_mut().for_each(canonical);
+            map.sort_keys();
+        }
+        _ => {}
+    }
+}
+
+/// Collects values canonically, to be printed in a stable order.
+#[derive(Default)]
+pub struct Normalized {
+    records: Vec<(String, String)>,
+}
+
+impl Normalized {
+    pub fn add(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut key = serde_json::from_str(key)?;
+        let mut value = serde_json::from_str(value)?;
+        canonical(&mut key);
+        canonical(&mut value);
+        self.records.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    /// Each value, ordered by its key, and then by the value itself to settle ties.
+    pub fn sorted(&self) -> Vec<&str> {
+        let mut res = self.records.iter().collect::<Vec<_>>();
+        res.sort_unstable();
+        res.into_iter().map(|(_, value)| value.as_str()).collect()
+    }
+}
+
+pub enum Aggregate {
+    Frequencies(Frequencies),
+    Histogram(Histogram),
+    Duplicates(Duplicates),
+    Shapes(Shapes),
+    Table(Table),
+    Normalized(Normalized),
+}
+
+impl Aggregate {
+    /// Add `value`, which the aggregation function mapped to `key`.
+    pub fn add(&mut self, key: String, value: String) -> Result<()> {
+        match self {
+            Aggregate::Frequencies(frequencies) => frequencies.add(key),
+            Aggregate::Histogram(histogram) => histogram.add(&key)?,
+            Aggregate::Duplicates(duplicates) => duplicates.add(key, value),
+            Aggregate::Shapes(shapes) => shapes.add(&value)?,
+            Aggregate::Table(table) => table.add(&value)?,
+            Aggregate::Normalized(normalized) => normalized.add(&key, &value)?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::{print, safe};
+
+/// The record separator which starts each JSON text in an RFC 7464 JSON text sequence.
+const RS: u8 = 0x1E;
+
+#[derive(PartialEq)]
+enum State {
+    /// Between top-level values.
+    TopLevel,
+    /// Just inside the opening bracket of a top-level array.
+    ArrayStart,
+    /// After an element of a top-level array.
+    ArrayElement,
+    /// After a comma in a top-level array.
+    ArrayComma,
+}
+
+/// Reads a sequence of whitespace separated JSON values (e.g. NDJSON) one value at a time, without
+/// holding more than one value in memory. The array at a JSON Pointer is streamed element by
+/// element, see [`Stream::at`].
+///
+/// Records of an RFC 7464 JSON text sequence, each starting with RS, are read whole, and a record
+/// which doesn't parse, as happens when a writer is interrupted part way through one, is skipped
+/// with a warning rather than ending the stream.
+pub struct Stream<R> {
+    reader: R,
+    state: State,
+    /// Values from the last record of a JSON text sequence which haven't been returned yet.
+    record: VecDeque<Value>,
+    /// The number of line breaks read so far.
+    breaks: usize,
+    /// The line that the last value returned started on.
+    line: usize,
+    /// Stop after a single top-level value, because the stream starts part way through a document.
+    /// If that value is an array, its elements are streamed one at a time.
+    single: bool,
+    done: bool,
+}
+
+#[expect(clippy::naive_bytecount)]
+fn breaks(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b == b'\n').count()
+}
+
+/// Split a JSON Pointer (RFC 6901) in to its reference tokens.
+fn tokens(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(pointer) = pointer.strip_prefix('/') else {
+        bail!("JSON pointer must be empty or start with `/`");
+    };
+    Ok(pointer
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+impl<R: BufRead> Stream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: State::TopLevel,
+            record: VecDeque::new(),
+            breaks: 0,
+            line: 1,
+            single: false,
+            done: false,
+        }
+    }
+
+    /// Stream the value at `pointer` in the first value read, or its elements if it's an array,
+    /// skipping everything before it without parsing it, and ignoring everything after it.
+    pub fn at(reader: R, pointer: &str) -> Result<Self> {
+        let mut stream = Self::new(reader);
+        stream.single = true;
+        for token in tokens(pointer)? {
+            if !stream.seek(&token)? {
+                bail!("no value at {pointer}");
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Read just the value at `pointer` in the first value read.
+    pub fn extract(reader: R, pointer: &str) -> Result<Value> {
+        let mut stream = Self::at(reader, pointer)?;
+        stream.skip_whitespace()?;
+        serde_json::from_slice(&stream.read_value(true)?).context("parsing JSON")
+    }
+
+    /// The line that the last value returned started on. Values from the same record of a JSON text
+    /// sequence share the line the record started on.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn skip_whitespace(&mut self) -> Result<()> {
+        while let Some(b) = self.peek()? {
+            if !b.is_ascii_whitespace() {
+                break;
+            }
+            if b == b'\n' {
+                self.breaks += 1;
+            }
+            self.reader.consume(1);
+        }
+        Ok(())
+    }
+
+    /// Consume the next non-whitespace byte if it's `b`.
+    fn eat(&mut self, b: u8) -> Result<bool> {
+        self.skip_whitespace()?;
+        if self.peek()? == Some(b) {
+            self.reader.consume(1);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Advance to the member of the next value named by `token`, returning false if there isn't
+    /// one.
+    fn seek(&mut self, token: &str) -> Result<bool> {
+        if self.eat(b'{')? {
+            if self.eat(b'}')? {
+                return Ok(false);
+            }
+            loop {
+                self.skip_whitespace()?;
+                let key: String =
+                    serde_json::from_slice(&self.read_value(true)?).context("parsing JSON")?;
+                if !self.eat(b':')? {
+                    bail!("expected `:` after object key");
+                }
+                if key == token {
+                    return Ok(true);
+                }
+                self.skip_whitespace()?;
+                self.read_value(false)?;
+                if !self.eat(b',')? {
+                    return Ok(false);
+                }
+            }
+        } else if self.eat(b'[')? {
+            let Ok(index) = token.parse::<usize>() else {
+                return Ok(false);
+            };
+            if self.eat(b']')? {
+                return Ok(false);
+            }
+            for _ in 0..index {
+                self.skip_whitespace()?;
+                self.read_value(false)?;
+                if !self.eat(b',')? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Read the raw bytes of the next value, stopping just after its last byte. If `keep` is
+    /// false then the bytes are skipped rather than returned.
+    fn read_value(&mut self, keep: bool) -> Result<Vec<u8>> {
+        let mut value = Vec::new();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(value);
+            }
+            let mut end = None;
+            for (i, &b) in buf.iter().enumerate() {
+                if in_string {
+                    if escaped {
+                        escaped = false;
+                    } else if b == b'\\' {
+                        escaped = true;
+                    } else if b == b'"' {
+                        in_string = false;
+                        if depth == 0 {
+                            end = Some(i + 1);
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                match b {
+                    // Delimiters end a scalar value.
+                    _ if depth == 0 && (b.is_ascii_whitespace() || b"]},".contains(&b)) => {
+                        end = Some(i);
+                        break;
+                    }
+                    b'"' => in_string = true,
+                    b'[' | b'{' => depth += 1,
+                    b']' | b'}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = Some(i + 1);
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let n = end.unwrap_or(buf.len());
+            if keep {
+                value.extend_from_slice(&buf[..n]);
+                safe::check_input(value.len())?;
+            }
+            self.breaks += breaks(&buf[..n]);
+            self.reader.consume(n);
+            if end.is_some() {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Read the bytes up to the next RS, or the end of input, without consuming the RS.
+    fn read_record(&mut self) -> Result<Vec<u8>> {
+        let mut record = Vec::new();
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(record);
+            }
+            let end = buf.iter().position(|&b| b == RS);
+            let n = end.unwrap_or(buf.len());
+            record.extend_from_slice(&buf[..n]);
+            self.breaks += breaks(&buf[..n]);
+            self.reader.consume(n);
+            if end.is_some() {
+                return Ok(record);
+            }
+        }
+    }
+
+    /// Parse a record of a JSON text sequence. Numbers, `true`, `false`, and `null` aren't
+    /// self-delimiting, so unless they're followed by whitespace they might have been cut short.
+    fn parse_record(record: &[u8]) -> Result<VecDeque<Value>> {
+        let text = record.trim_ascii_end();
+        if text.trim_ascii_start().is_empty() {
+            return Ok(VecDeque::new());
+        }
+        if text.len() == record.len() && !matches!(text.last(), Some(b'"' | b']' | b'}')) {
+            bail!("record might be truncated");
+        }
+        Stream::new(text).collect()
+    }
+
+    fn next_value(&mut self) -> Result<Option<Value>> {
+        loop {
+            if let Some(value) = self.record.pop_front() {
+                return Ok(Some(value));
+            }
+            if self.done {
+                return Ok(None);
+            }
+            self.skip_whitespace()?;
+            if self.state == State::TopLevel && !self.single && self.peek()? == Some(RS) {
+                self.line = self.breaks + 1;
+                self.reader.consume(1);
+                let record = self.read_record()?;
+                match Self::parse_record(&record) {
+                    Ok(values) => self.record = values,
+                    Err(err) => print::warn(
+                        &mut print::stderr(),
+                        &err.context("skipping invalid record of JSON text sequence"),
+                    )?,
+                }
+                continue;
+            }
+            let Some(b) = self.peek()? else {
+                if self.state != State::TopLevel {
+                    bail!("EOF while parsing a top-level array");
+                }
+                return Ok(None);
+            };
+            match (&self.state, b) {
+                // Anywhere else, an array is a value like any other, since there's no telling
+                // whether more values follow it until it ends.
+                (State::TopLevel, b'[') if self.single => {
+                    self.reader.consume(1);
+                    self.state = State::ArrayStart;
+                    continue;
+                }
+                (State::ArrayStart | State::ArrayElement, b']') => {
+                    self.reader.consume(1);
+                    self.state = State::TopLevel;
+                    self.done = self.single;
+                    continue;
+                }
+                (State::ArrayElement, b',') => {
+                    self.reader.consume(1);
+                    self.state = State::ArrayComma;
+                    continue;
+                }
+                (State::ArrayElement, _) => bail!("expected `,` or `]` after array element"),
+                (State::ArrayComma, b']') => bail!("trailing comma in top-level array"),
+                _ => {}
+            }
+            self.line = self.breaks + 1;
+            let value = serde_json::from_slice(&self.read_value(true)?).context("parsing JSON")?;
+            if self.state == State::TopLevel {
+                self.done = self.single;
+            } else {
+                self.state = State::ArrayElement;
+            }
+            return Ok(Some(value));
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Stream<R> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_value().transpose()
+    }
+}
@@ -0,0 +1,117 @@
+//! Suites of tests for scripts, run with --test, so that scripts shared between people can be
+//! changed without breaking what they were written for.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Error, Result};
+use serde_json::Value;
+
+use crate::{
+    boa::Runtime,
+    diagnostic,
+    diff::{self, Change},
+    parse, path,
+};
+
+/// How a single test went.
+pub enum Outcome {
+    Pass,
+    /// The result differed from what was expected, in each of these ways.
+    Fail(Vec<(String, Change)>),
+    /// SCRIPT couldn't be evaluated, or its result wasn't JSON.
+    Error(Error),
+}
+
+/// An input, a script to evaluate against it, and the result expected.
+pub struct Test {
+    pub name: String,
+    script: String,
+    /// The file the script was read from, if it was read from one.
+    file: Option<String>,
+    input: String,
+    expected: Value,
+}
+
+impl Test {
+    fn new(dir: &Path, i: usize, test: &Value) -> Result<Self> {
+        let name = match test.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            Some(_) => bail!("name should be a string"),
+            None => format!("test {}", i + 1),
+        };
+        let (script, file) = match (test.get("script"), test.get("file")) {
+            (Some(Value::String(script)), None) => (script.clone(), None),
+            (None, Some(Value::String(file))) => {
+                // Relative to the suite, so that it runs from anywhere.
+                let file = dir.join(file);
+                let script = std::fs::read_to_string(&file)
+                    .with_context(|| format!("reading {}", file.display()))?;
+                (script, Some(file.display().to_string()))
+            }
+            _ => bail!("{name} should have either a script or a file, as a string"),
+        };
+        let expected = test
+            .get("expected")
+            .with_context(|| format!("{name} should have an expected result"))?;
+        Ok(Self {
+            name,
+            script: path::script(script),
+            file,
+            input: test.get("input").unwrap_or(&Value::Null).to_string(),
+            expected: expected.clone(),
+        })
+    }
+
+    /// Evaluate the script against the input, and compare the result with what was expected.
+    pub fn run(&self, runtime: &mut Runtime, filename: &str) -> Outcome {
+        let res = runtime
+            .reset()
+            .and_then(|()| runtime.eval(&self.script, &self.input, Some(filename)))
+            .map_err(|err| match &self.file {
+                Some(file) => diagnostic::from_file(err, file),
+                None => err,
+            })
+            .and_then(|res| {
+                serde_json::from_str::<Value>(&res)
+                    .with_context(|| format!("expected a JSON result, found {res}"))
+            });
+        match res {
+            Ok(res) => {
+                let changes = diff::diff(&self.expected, &res);
+                if changes.is_empty() {
+                    Outcome::Pass
+                } else {
+                    Outcome::Fail(changes)
+                }
+            }
+            Err(err) => Outcome::Error(err),
+        }
+    }
+}
+
+/// The tests in the suite at `path`, an array of them under `test`, written in TOML, or in YAML or
+/// JSON, which are recognised by their extensions.
+pub fn load(path: &str) -> Result<Vec<Test>> {
+    let s = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let suite = match extension.as_deref() {
+        Some("yaml" | "yml") => parse::yaml(&s)?,
+        Some("json") => parse::json(&s)?,
+        _ => parse::toml(&s)?,
+    };
+    let suite: Value = serde_json::from_str(&suite)?;
+    let Some(Value::Array(tests)) = suite.get("test") else {
+        bail!("{path} should have an array of tests under test");
+    };
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    tests
+        .iter()
+        .enumerate()
+        .map(|(i, test)| {
+            Test::new(dir, i, test).with_context(|| format!("loading test {} of {path}", i + 1))
+        })
+        .collect()
+}
@@ -0,0 +1,487 @@
+//! Running TypeScript, turned on by the `typescript` feature. Types are stripped by overwriting them
+//! with spaces, so that every line and column of the JavaScript left behind is where it was in the
+//! TypeScript, and errors point at the right place. The little TypeScript which does something at
+//! runtime is lowered to JavaScript instead: enums, and parameter properties. Namespaces aren't
+//! supported. Types aren't checked.
+
+use std::{fmt::Write, ops::Range};
+
+use anyhow::{Error, Result};
+use oxc_allocator::Allocator;
+use oxc_ast::ast::{
+    BindingPattern, Class, ClassElement, Declaration, ExportNamedDeclaration, Expression,
+    FormalParameter, Function, ImportDeclaration, ImportDeclarationSpecifier, ImportOrExportKind,
+    MethodDefinition, MethodDefinitionKind, MethodDefinitionType, ModuleDeclaration,
+    PropertyDefinitionType, Statement, TSAsExpression, TSEnumDeclaration, TSNonNullExpression,
+    TSSatisfiesExpression, TSThisParameter, TSTypeAnnotation, TSTypeAssertion,
+    TSTypeParameterDeclaration, TSTypeParameterInstantiation, VariableDeclarator,
+};
+use oxc_ast_visit::{walk, Visit};
+use oxc_parser::Parser;
+use oxc_span::{GetSpan, SourceType, Span};
+use oxc_syntax::scope::ScopeFlags;
+
+use crate::diagnostic::{Diagnostic, Kind};
+
+/// The modifiers of class members which only matter to the type checker.
+const MEMBER_MODIFIERS: [&str; 7] = [
+    "public",
+    "private",
+    "protected",
+    "readonly",
+    "override",
+    "declare",
+    "abstract",
+];
+
+/// Transpile the TypeScript `source` to JavaScript, failing on a syntax error or anything which
+/// isn't supported.
+pub fn transpile(source: &str) -> Result<String> {
+    let allocator = Allocator::default();
+    let ret = Parser::new(&allocator, source, SourceType::ts()).parse();
+    if let Some(err) = ret.errors.first() {
+        let span = err
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.first())
+            .map(|label| label.offset()..label.offset() + label.len());
+        return Err(error(source, format!("SyntaxError: {}", err.message), span));
+    }
+
+    let mut stripper = Stripper {
+        source,
+        edits: Vec::new(),
+        error: None,
+    };
+    stripper.visit_program(&ret.program);
+    if let Some((span, message)) = stripper.error {
+        return Err(error(source, message, Some(range(span))));
+    }
+
+    let mut edits = stripper.edits;
+    edits.sort_by_key(|(range, _)| (range.start, range.end));
+    let mut js = String::with_capacity(source.len());
+    let mut end = 0;
+    for (range, text) in edits {
+        // Anything inside what's already been stripped went with it.
+        if range.start < end {
+            continue;
+        }
+        js.push_str(&source[end..range.start]);
+        js.push_str(&text.unwrap_or_else(|| blank(&source[range.clone()])));
+        end = range.end;
+    }
+    js.push_str(&source[end..]);
+    Ok(js)
+}
+
+fn error(source: &str, message: String, span: Option<Range<usize>>) -> Error {
+    let diagnostic = Diagnostic::new(Kind::Eval, message);
+    match span {
+        Some(span) => diagnostic.excerpt(source, span).into(),
+        None => diagnostic.into(),
+    }
+}
+
+fn range(span: Span) -> Range<usize> {
+    span.start as usize..span.end as usize
+}
+
+/// `s` with everything but line breaks overwritten with spaces.
+fn blank(s: &str) -> String {
+    s.chars()
+        .map(|c| if matches!(c, '\n' | '\r') { c } else { ' ' })
+        .collect()
+}
+
+/// Walks a TypeScript program, collecting the edits which turn it in to JavaScript: each a range
+/// of the source, and what to replace it with, or `None` to blank it.
+struct Stripper<'s> {
+    source: &'s str,
+    edits: Vec<(Range<usize>, Option<String>)>,
+    /// The first thing found which can't be transpiled, and why.
+    error: Option<(Span, String)>,
+}
+
+impl Stripper<'_> {
+    fn strip(&mut self, range: Range<usize>) {
+        if !range.is_empty() {
+            self.edits.push((range, None));
+        }
+    }
+
+    /// Strip a whole statement or class member, leaving a `;` in its place, so that the lines
+    /// either side of it aren't run together by automatic semicolon insertion.
+    fn strip_statement(&mut self, span: Span) {
+        let range = range(span);
+        let text = format!(";{}", blank(&self.source[range.start + 1..range.end]));
+        self.edits.push((range, Some(text)));
+    }
+
+    /// Strip each of `words` found in `range` as a keyword, rather than part of a decorator.
+    fn strip_words(&mut self, range: Range<usize>, words: &[&str]) {
+        let text = &self.source[range.clone()];
+        let mut start = None;
+        for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+            let word = c.is_alphanumeric() || c == '_' || c == '$';
+            match start {
+                None if word => start = Some(i),
+                Some(s) if !word => {
+                    let decorated = text[..s].ends_with(['@', '.']);
+                    if !decorated && words.contains(&&text[s..i]) {
+                        self.strip(range.start + s..range.start + i);
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Strip the first `mark`, ? or !, between `start` and `end`.
+    fn strip_mark(&mut self, start: u32, end: u32, mark: char) {
+        let (start, end) = (start as usize, end as usize);
+        if let Some(i) = self.source[start..end].find(mark) {
+            self.strip(start + i..start + i + 1);
+        }
+    }
+
+    /// Strip `import { type A }` or `export { type A }`, along with the comma after it.
+    fn strip_type_specifier(&mut self, span: Span) {
+        let rest = &self.source[span.end as usize..];
+        let trimmed = rest.trim_start();
+        let end = if trimmed.starts_with(',') {
+            span.end as usize + rest.len() - trimmed.len() + 1
+        } else {
+            span.end as usize
+        };
+        self.strip(span.start as usize..end);
+    }
+
+    fn fail(&mut self, span: Span, message: &str) {
+        if self.error.is_none() {
+            self.error = Some((span, message.to_string()));
+        }
+    }
+
+    /// Whether `declaration` is only there for the type checker, and can be stripped entirely.
+    fn is_type_only(&mut self, declaration: &Declaration) -> bool {
+        match declaration {
+            Declaration::TSTypeAliasDeclaration(_)
+            | Declaration::TSInterfaceDeclaration(_)
+            | Declaration::TSGlobalDeclaration(_) => true,
+            Declaration::FunctionDeclaration(f) => f.declare || f.body.is_none(),
+            Declaration::TSModuleDeclaration(module) if !module.declare => {
+                self.fail(module.span, "TypeScript namespaces aren't supported");
+                false
+            }
+            Declaration::TSImportEqualsDeclaration(import) => {
+                if !import.import_kind.is_type() {
+                    self.fail(import.span, "import = isn't supported, use import instead");
+                }
+                true
+            }
+            _ => declaration.declare(),
+        }
+    }
+
+    /// Replace `e` with JavaScript which builds the same object TypeScript would, e.g. for
+    /// `enum E { A, B = "b" }`, `var E; (function (E) { E[E["A"] = 0] = "A"; E["B"] = "b"; })(E ||
+    /// (E = {}));`, followed by as many line breaks as it spanned.
+    fn lower_enum(&mut self, e: &TSEnumDeclaration) {
+        let name = e.id.name.as_str();
+        let mut js = format!("var {name}; (function ({name}) {{");
+        // The previous member's value, if it was a number, for the next to count on from.
+        let mut previous: Option<String> = None;
+        for member in &e.body.members {
+            let key = serde_json::to_string(member.id.static_name().as_str())
+                .expect("serializing a string");
+            let (value, reverse) = match &member.initializer {
+                None => match &previous {
+                    None => (String::from("0"), true),
+                    Some(previous) => (format!("{name}[{previous}] + 1"), true),
+                },
+                Some(init) => {
+                    let string = matches!(
+                        init,
+                        Expression::StringLiteral(_) | Expression::TemplateLiteral(_)
+                    );
+                    (self.source[range(init.span())].to_string(), !string)
+                }
+            };
+            if reverse {
+                write!(js, " {name}[{name}[{key}] = {value}] = {key};")
+                    .expect("writing to a string");
+            } else {
+                write!(js, " {name}[{key}] = {value};").expect("writing to a string");
+            }
+            previous = reverse.then_some(key);
+        }
+        write!(js, " }})({name} || ({name} = {{}}));").expect("writing to a string");
+        let span = range(e.span);
+        js.push_str(&"\n".repeat(self.source[span.clone()].matches('\n').count()));
+        self.edits.push((span, Some(js)));
+    }
+
+    /// Lower the parameter properties of `constructor`, e.g. `private x`, to assignments at the
+    /// start of its body, after calling `super` if it does.
+    fn lower_parameter_properties(&mut self, constructor: &Function) {
+        let names = constructor
+            .params
+            .items
+            .iter()
+            .filter(|param| param.accessibility.is_some() || param.readonly)
+            .filter_map(|param| match &param.pattern {
+                BindingPattern::BindingIdentifier(id) => Some(id.name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let Some(body) = &constructor.body else {
+            return;
+        };
+        if names.is_empty() {
+            return;
+        }
+        let calls_super = |statement: &Statement| {
+            matches!(
+                statement,
+                Statement::ExpressionStatement(s)
+                    if matches!(&s.expression, Expression::CallExpression(call)
+                        if matches!(call.callee, Expression::Super(_)))
+            )
+        };
+        let at = match body.statements.iter().find(|s| calls_super(s)) {
+            Some(statement) => statement.span().end,
+            None => body.span.start + 1,
+        } as usize;
+        let mut js = String::new();
+        for name in names {
+            write!(js, " this.{name} = {name};").expect("writing to a string");
+        }
+        self.edits.push((at..at, Some(js)));
+    }
+}
+
+impl<'a> Visit<'a> for Stripper<'_> {
+    fn visit_statement(&mut self, it: &Statement<'a>) {
+        if let Some(declaration) = it.as_declaration() {
+            if self.is_type_only(declaration) {
+                self.strip_statement(it.span());
+                return;
+            }
+        } else if let Some(module) = it.as_module_declaration() {
+            let type_only = match module {
+                ModuleDeclaration::ImportDeclaration(import) => import.import_kind.is_type(),
+                ModuleDeclaration::ExportNamedDeclaration(export) => {
+                    export.export_kind.is_type()
+                        || export
+                            .declaration
+                            .as_ref()
+                            .is_some_and(|declaration| self.is_type_only(declaration))
+                }
+                ModuleDeclaration::ExportAllDeclaration(export) => export.export_kind.is_type(),
+                ModuleDeclaration::TSNamespaceExportDeclaration(_) => true,
+                ModuleDeclaration::TSExportAssignment(export) => {
+                    self.fail(
+                        export.span,
+                        "export = isn't supported, use export default instead",
+                    );
+                    true
+                }
+                ModuleDeclaration::ExportDefaultDeclaration(_) => false,
+            };
+            if type_only {
+                self.strip_statement(it.span());
+                return;
+            }
+        }
+        walk::walk_statement(self, it);
+    }
+
+    fn visit_import_declaration(&mut self, it: &ImportDeclaration<'a>) {
+        for specifier in it.specifiers.iter().flatten() {
+            if let ImportDeclarationSpecifier::ImportSpecifier(s) = specifier
+                && s.import_kind.is_type()
+            {
+                self.strip_type_specifier(s.span);
+            }
+        }
+        walk::walk_import_declaration(self, it);
+    }
+
+    fn visit_export_named_declaration(&mut self, it: &ExportNamedDeclaration<'a>) {
+        for specifier in &it.specifiers {
+            if specifier.export_kind == ImportOrExportKind::Type {
+                self.strip_type_specifier(specifier.span);
+            }
+        }
+        walk::walk_export_named_declaration(self, it);
+    }
+
+    fn visit_ts_enum_declaration(&mut self, it: &TSEnumDeclaration<'a>) {
+        self.lower_enum(it);
+    }
+
+    fn visit_ts_type_annotation(&mut self, it: &TSTypeAnnotation<'a>) {
+        self.strip(range(it.span));
+    }
+
+    fn visit_ts_type_parameter_declaration(&mut self, it: &TSTypeParameterDeclaration<'a>) {
+        self.strip(range(it.span));
+    }
+
+    fn visit_ts_type_parameter_instantiation(&mut self, it: &TSTypeParameterInstantiation<'a>) {
+        self.strip(range(it.span));
+    }
+
+    fn visit_ts_this_parameter(&mut self, it: &TSThisParameter<'a>) {
+        // The comma after `this` goes with it.
+        let end = self.source[it.span.end as usize..]
+            .find(|c: char| !c.is_whitespace())
+            .map_or(it.span.end as usize, |i| it.span.end as usize + i);
+        let end = if self.source[end..].starts_with(',') {
+            end + 1
+        } else {
+            it.span.end as usize
+        };
+        self.strip(it.span.start as usize..end);
+    }
+
+    fn visit_ts_as_expression(&mut self, it: &TSAsExpression<'a>) {
+        self.strip(it.expression.span().end as usize..it.span.end as usize);
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_ts_satisfies_expression(&mut self, it: &TSSatisfiesExpression<'a>) {
+        self.strip(it.expression.span().end as usize..it.span.end as usize);
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_ts_non_null_expression(&mut self, it: &TSNonNullExpression<'a>) {
+        self.strip_mark(it.expression.span().end, it.span.end, '!');
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_ts_type_assertion(&mut self, it: &TSTypeAssertion<'a>) {
+        self.strip(it.span.start as usize..it.expression.span().start as usize);
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_variable_declarator(&mut self, it: &VariableDeclarator<'a>) {
+        if it.definite {
+            let end = it
+                .type_annotation
+                .as_ref()
+                .map_or(it.span.end, |t| t.span.start);
+            self.strip_mark(it.id.span().end, end, '!');
+        }
+        walk::walk_variable_declarator(self, it);
+    }
+
+    fn visit_formal_parameter(&mut self, it: &FormalParameter<'a>) {
+        self.strip_words(
+            it.span.start as usize..it.pattern.span().start as usize,
+            &MEMBER_MODIFIERS,
+        );
+        if it.optional {
+            let end = it
+                .type_annotation
+                .as_ref()
+                .map_or(it.span.end, |t| t.span.start);
+            self.strip_mark(it.pattern.span().end, end, '?');
+        }
+        walk::walk_formal_parameter(self, it);
+    }
+
+    fn visit_function(&mut self, it: &Function<'a>, flags: ScopeFlags) {
+        // An overload has no body, and nothing to run.
+        if it.body.is_none() {
+            self.strip_statement(it.span);
+            return;
+        }
+        walk::walk_function(self, it, flags);
+    }
+
+    fn visit_class(&mut self, it: &Class<'a>) {
+        let start = it
+            .id
+            .as_ref()
+            .map_or(it.body.span.start, |id| id.span.start);
+        self.strip_words(
+            it.span.start as usize..start as usize,
+            &["abstract", "declare"],
+        );
+        if let (Some(first), Some(last)) = (it.implements.first(), it.implements.last()) {
+            let before = &self.source[..first.span.start as usize];
+            let start = before.rfind("implements").unwrap_or(before.len());
+            self.strip(start..last.span.end as usize);
+        }
+        walk::walk_class(self, it);
+    }
+
+    fn visit_class_element(&mut self, it: &ClassElement<'a>) {
+        let (key, end) = match it {
+            ClassElement::TSIndexSignature(_) => {
+                self.strip_statement(it.span());
+                return;
+            }
+            ClassElement::MethodDefinition(method) => {
+                if method.r#type == MethodDefinitionType::TSAbstractMethodDefinition
+                    || method.value.body.is_none()
+                {
+                    self.strip_statement(method.span);
+                    return;
+                }
+                (method.key.span(), method.value.params.span.start)
+            }
+            ClassElement::PropertyDefinition(property) => {
+                if property.declare
+                    || property.r#type == PropertyDefinitionType::TSAbstractPropertyDefinition
+                {
+                    self.strip_statement(property.span);
+                    return;
+                }
+                let end = property
+                    .type_annotation
+                    .as_ref()
+                    .map(|t| t.span.start)
+                    .or_else(|| property.value.as_ref().map(|v| v.span().start))
+                    .unwrap_or(property.span.end);
+                (property.key.span(), end)
+            }
+            ClassElement::AccessorProperty(property) => {
+                if property.r#type.is_abstract() {
+                    self.strip_statement(property.span);
+                    return;
+                }
+                (property.key.span(), property.span.end)
+            }
+            ClassElement::StaticBlock(_) => {
+                walk::walk_class_element(self, it);
+                return;
+            }
+        };
+        // Modifiers come before the key, or before the [ of a computed key.
+        let start = self.source[..key.start as usize]
+            .trim_end()
+            .strip_suffix('[')
+            .map_or(key.start as usize, str::len);
+        self.strip_words(it.span().start as usize..start, &MEMBER_MODIFIERS);
+        // An optional member's ?, or a definite property's !, follows the key.
+        let text = &self.source[key.end as usize..end as usize];
+        let after = key.end as usize + text.len() - text.trim_start_matches([']', ' ']).len();
+        if self.source[after..end as usize].starts_with(['?', '!'])
+            && !self.source[after..].starts_with("?.")
+        {
+            self.strip(after..after + 1);
+        }
+        walk::walk_class_element(self, it);
+    }
+
+    fn visit_method_definition(&mut self, it: &MethodDefinition<'a>) {
+        if it.kind == MethodDefinitionKind::Constructor {
+            self.lower_parameter_properties(&it.value);
+        }
+        walk::walk_method_definition(self, it);
+    }
+}
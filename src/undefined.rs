@@ -0,0 +1,73 @@
+//! Undefined values inside results, which JSON can't represent. By default, as with
+//! `JSON.stringify`, keys whose values are undefined are left out of objects, and undefined elements
+//! of arrays become null, while null itself is kept, so that "missing" and "null" stay distinct.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::{
+    diagnostic::{Diagnostic, Kind},
+    diff,
+};
+
+/// Marks a string which stands in for an undefined value in a result, so that it can be found.
+pub const UNDEFINED: &str = "\u{10FFFF}undefined";
+
+/// What to do with undefined values inside results.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Undefined {
+    /// Leave out keys whose values are undefined, as `JSON.stringify` does.
+    Omit,
+    /// Print undefined values as null, so that their keys are kept.
+    Null,
+    /// Fail, rather than leave anything out.
+    Error,
+}
+
+impl Undefined {
+    /// A JavaScript expression for what `v` is replaced with when stringifying a result.
+    pub fn replacement(self) -> String {
+        match self {
+            Undefined::Omit => String::from("v"),
+            Undefined::Null => String::from("v === undefined ? null : v"),
+            Undefined::Error => format!(
+                "v === undefined ? {} : v",
+                serde_json::to_string(UNDEFINED).expect("serializing a string")
+            ),
+        }
+    }
+}
+
+/// The path to the first undefined value marked in `value`, if there is one.
+fn find(path: &str, value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if s == UNDEFINED => Some(path.to_string()),
+        Value::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .find_map(|(i, v)| find(&format!("{path}[{i}]"), v)),
+        Value::Object(obj) => obj
+            .iter()
+            .find_map(|(k, v)| find(&diff::key_path(path, k), v)),
+        _ => None,
+    }
+}
+
+/// Fail if the result `json` contains an undefined value, saying where.
+pub fn check(json: &str) -> Result<()> {
+    if !json.contains(UNDEFINED) {
+        return Ok(());
+    }
+    let value = serde_json::from_str(json)?;
+    match find("$", &value) {
+        Some(path) => Err(
+            Diagnostic::new(Kind::Convert, format!("{path} is undefined"))
+                .hint(
+                    "use --undefined null to print it as null, or --undefined omit to leave it out",
+                )
+                .into(),
+        ),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fs, io::Read};
+
+use anyhow::{bail, ensure, Context, Result};
+use flate2::read::GzDecoder;
+use semver::Version;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
+const NAME: &str = env!("CARGO_PKG_NAME");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The name that release archives are built for this platform under, if they are.
+fn platform() -> Result<&'static str> {
+    Ok(match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("macos", "aarch64") => "macos",
+        ("linux", "x86_64") => "linux",
+        ("windows", "x86_64") => "windows",
+        (os, arch) => bail!("there's no prebuilt release for {os} on {arch}"),
+    })
+}
+
+fn get(url: &str) -> Result<Vec<u8>> {
+    let mut res = ureq::get(url)
+        .call()
+        .with_context(|| format!("fetching {url}"))?;
+    Ok(res.body_mut().with_config().limit(u64::MAX).read_to_vec()?)
+}
+
+/// The version of the latest release.
+fn latest() -> Result<Version> {
+    let api = REPOSITORY.replace("https://github.com/", "https://api.github.com/repos/");
+    let release: Value = serde_json::from_slice(&get(&format!("{api}/releases/latest"))?)
+        .context("parsing release")?;
+    let tag = release["tag_name"]
+        .as_str()
+        .context("getting release tag")?;
+    Version::parse(tag.trim_start_matches('v'))
+        .with_context(|| format!("parsing release tag {tag}"))
+}
+
+/// Extract the binary from a release archive.
+fn extract(archive: &[u8], dir: &str) -> Result<Vec<u8>> {
+    let bin = format!("{dir}/{NAME}{}", std::env::consts::EXE_SUFFIX);
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.to_str() == Some(bin.as_str()) {
+            let mut res = Vec::new();
+            entry.read_to_end(&mut res)?;
+            return Ok(res);
+        }
+    }
+    bail!("release archive doesn't contain {bin}")
+}
+
+/// Replace the running binary with the latest release, only if it's newer, checking the archive
+/// against the checksum published alongside it. The checksum comes from the same place as the
+/// archive, so it catches a corrupt download, but not a tampered release.
+pub fn update() -> Result<()> {
+    let platform = platform()?;
+    let version = latest().context("finding latest release")?;
+    let current = Version::parse(VERSION)?;
+    if version == current {
+        println!("{NAME} {VERSION} is the latest release");
+        return Ok(());
+    }
+    if version < current {
+        println!("{NAME} {VERSION} is newer than the latest release, {version}");
+        return Ok(());
+    }
+
+    let dir = format!("{NAME}-{version}-{platform}");
+    let url = format!("{REPOSITORY}/releases/download/v{version}/{dir}.tar.gz");
+    let archive = get(&url)?;
+    let checksum = String::from_utf8(get(&format!("{url}.sha256"))?)?;
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .context("reading checksum")?;
+    let actual = format!("{:x}", Sha256::digest(&archive));
+    ensure!(
+        actual.eq_ignore_ascii_case(expected),
+        "checksum of {dir}.tar.gz is {actual}, but {expected} was published"
+    );
+
+    let bin = extract(&archive, &dir)?;
+    let tmp = std::env::temp_dir().join(format!("{dir}{}", std::env::consts::EXE_SUFFIX));
+    fs::write(&tmp, bin).with_context(|| format!("writing {}", tmp.display()))?;
+    let res = self_replace::self_replace(&tmp).context("replacing binary");
+    fs::remove_file(&tmp).ok();
+    res?;
+
+    println!("updated {NAME} from {VERSION} to {version}");
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use anyhow::{bail, Context, Result};
+use wasmtime::{Engine, Linker, Module, Store};
+use wasmtime_wasi::{
+    pipe::{MemoryInputPipe, MemoryOutputPipe},
+    preview1::{self, WasiP1Ctx},
+    I32Exit, WasiCtxBuilder,
+};
+
+/// A transform compiled to a WASI module, evaluated instead of SCRIPT. The module is run once for
+/// each input, which it reads as JSON on STDIN, and writes its result as JSON to STDOUT.
+pub struct Transform {
+    engine: Engine,
+    module: Module,
+    linker: Linker<WasiP1Ctx>,
+}
+
+impl Transform {
+    pub fn new(path: &str) -> Result<Self> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, path).with_context(|| format!("loading {path}"))?;
+        let mut linker = Linker::new(&engine);
+        preview1::add_to_linker_sync(&mut linker, |wasi| wasi)?;
+        Ok(Self {
+            engine,
+            module,
+            linker,
+        })
+    }
+
+    pub fn eval(&self, input: &str) -> Result<String> {
+        let stdout = MemoryOutputPipe::new(usize::MAX);
+        let stderr = MemoryOutputPipe::new(usize::MAX);
+        let wasi = WasiCtxBuilder::new()
+            .stdin(MemoryInputPipe::new(input.to_string()))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .build_p1();
+        let mut store = Store::new(&self.engine, wasi);
+        let instance = self.linker.instantiate(&mut store, &self.module)?;
+        let start = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+
+        // Exiting with 0 is as good as returning.
+        let status = match start.call(&mut store, ()) {
+            Ok(()) => 0,
+            Err(err) => err
+                .downcast_ref::<I32Exit>()
+                .map(|exit| exit.0)
+                .ok_or(err)?,
+        };
+        if status != 0 {
+            bail!(
+                "transform exited with status {status}: {}",
+                String::from_utf8_lossy(&stderr.contents()).trim()
+            );
+        }
+
+        let res = String::from_utf8(stdout.contents().to_vec()).context("reading result")?;
+        Ok(res.trim_end().to_string())
+    }
+}
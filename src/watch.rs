@@ -0,0 +1,37 @@
+use std::{thread, time::Duration, time::SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Watches a set of files for changes by polling their modification times.
+pub struct Watcher<'a> {
+    files: &'a [String],
+    mtimes: Vec<Option<SystemTime>>,
+}
+
+fn mtimes(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(files: &'a [String]) -> Self {
+        Self {
+            files,
+            mtimes: mtimes(files),
+        }
+    }
+
+    /// Block until any of the files is modified, created, or removed.
+    pub fn wait(&mut self) {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let mtimes = mtimes(self.files);
+            if mtimes != self.mtimes {
+                self.mtimes = mtimes;
+                return;
+            }
+        }
+    }
+}
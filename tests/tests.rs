@@ -1,7 +1,13 @@
 use std::{
     env::{self, consts::EXE_SUFFIX},
-    io::Write,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    path::PathBuf,
     process::{Command, Stdio},
+    sync::mpsc,
+    thread::{self, JoinHandle},
+    time::Duration,
 };
 
 use anyhow::{ensure, Context, Result};
@@ -13,18 +19,20 @@ struct Output {
     stderr: String,
 }
 
-fn run<'a, I>(args: &[&str], stdin: &str, vars: I) -> Result<Output>
-where
-    I: IntoIterator<Item = (&'a str, &'a str)>,
-{
-    let bin = env::current_exe()?
+fn bin() -> Result<PathBuf> {
+    Ok(env::current_exe()?
         .parent()
         .context("getting parent")?
         .parent()
         .context("getting parent")?
-        .join(format!("jsq{}", EXE_SUFFIX));
+        .join(format!("jsq{}", EXE_SUFFIX)))
+}
 
-    let mut child = Command::new(bin)
+fn run<'a, I>(args: &[&str], stdin: &str, vars: I) -> Result<Output>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut child = Command::new(bin()?)
         .args(args)
         .envs(vars)
         .stdin(Stdio::piped())
@@ -54,6 +62,33 @@ fn convert(flags: &str, stdin: &str) -> Result<String> {
     Ok(res.stdout)
 }
 
+/// Serve `body` to a single HTTP request, returning the URL to request and a handle which resolves
+/// to the request head.
+fn serve(body: &'static str) -> Result<(String, JoinHandle<Result<String>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/", listener.local_addr()?);
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept()?;
+        let mut head = String::new();
+        let mut reader = BufReader::new(&stream);
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            if line.trim().is_empty() {
+                break;
+            }
+            head.push_str(&line);
+        }
+        write!(
+            &stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+        Ok(head)
+    });
+    Ok((url, handle))
+}
+
 fn ok(stdout: &str) -> Output {
     Output {
         status_code: 0,
@@ -83,6 +118,15 @@ fn test() -> Result<()> {
 
     assert_eq!(run(&["const x = 5; x * x"], "", [])?, ok("25\n"));
 
+    assert_eq!(
+        run(
+            &["-jJ", r#".teams[].members[]["first name"]"#],
+            r#"{ "teams": [{ "members": [{ "first name": "Ada" }] }, { "members": [] }] }"#,
+            []
+        )?,
+        ok("[\n  \"Ada\"\n]\n")
+    );
+
     assert_eq!(run(&["-jJ", "$.foo"], r#"{ "foo": 42 }"#, [])?, ok("42\n"));
 
     assert_eq!(
@@ -101,6 +145,14 @@ fn test() -> Result<()> {
     );
 
     assert_eq!(run(&["$foo"], "", [("foo", "42")])?, ok("42\n"));
+    assert_eq!(
+        run(
+            &["[env.foo, env['foo-bar'], env.missing ?? 0]"],
+            "",
+            [("foo", "42"), ("foo-bar", "43")]
+        )?,
+        ok("42,43,0\n")
+    );
 
     assert_eq!(
         run(&[r"$.match(/foo:(\w*)/)[1]"], "foo:bar baz:42", [])?,
@@ -109,17 +161,70 @@ fn test() -> Result<()> {
 
     assert_eq!(
         run(&["foo"], "", [])?,
-        err("error: ReferenceError: foo is not defined\n")
+        err("error[eval]: ReferenceError: foo is not defined\n")
     );
 
     assert_eq!(
         run(&["return 42"], "", [])?,
-        err("error: SyntaxError: unexpected token 'return', statement at line 1, col 1\n")
+        err(concat!(
+            "error[eval]: SyntaxError: unexpected token 'return', statement at line 1, col 1\n",
+            "  |\n",
+            "1 | return 42\n",
+            "  | ^\n",
+            "  hint: SCRIPT is an expression, so it doesn't need a return\n",
+        ))
     );
 
     assert_eq!(
         run(&["-j"], "foo", [])?,
-        err("error: parsing JSON: expected ident at line 1 column 2\n")
+        err(concat!(
+            "error[parse]: parsing JSON: expected ident at line 1 column 2\n",
+            "  |\n",
+            "1 | foo\n",
+            "  |  ^\n",
+        ))
+    );
+
+    assert_eq!(
+        run(&["-j"], "{}\n{}\n", [])?,
+        err(concat!(
+            "error[parse]: parsing JSON: trailing characters at line 2 column 1\n",
+            "  |\n",
+            "2 | {}\n",
+            "  | ^\n",
+            "  hint: to read more than one value, such as NDJSON, use --stream\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &["-j", "--lenient", "$.a.concat($.b)"],
+            "// comment\n{ a: ['x', /* inline */ 'y\\'s'], 'b': [\"z\",], }",
+            []
+        )?,
+        ok("x,y's,z\n")
+    );
+
+    let dupes = r#"{ "a": 1, "b": { "c": 1, "c": 2 } }"#;
+    assert_eq!(run(&["-jJ", "$.b"], dupes, [])?, ok("{\n  \"c\": 2\n}\n"));
+    assert_eq!(
+        run(&["-jJ", "--dupes", "first", "$.b"], dupes, [])?,
+        ok("{\n  \"c\": 1\n}\n")
+    );
+    assert_eq!(
+        run(&["-j", "--dupes", "error"], dupes, [])?,
+        err(concat!(
+            "error[parse]: parsing JSON: duplicate key $.b.c at line 1 column 28\n",
+            "  |\n",
+            r#"1 | { "a": 1, "b": { "c": 1, "c": 2 } }"#,
+            "\n",
+            "  |                            ^\n",
+            "  hint: use --dupes first or --dupes last to keep one of them\n",
+        ))
+    );
+    assert_eq!(
+        run(&["-y", "--dupes", "first", "$.a"], "a: 1\na: 2\n", [])?,
+        ok("1\n")
     );
 
     assert_eq!(
@@ -129,6 +234,33 @@ fn test() -> Result<()> {
 
     assert_eq!(run(&["-yY"], &publish_yaml, [])?, ok(&publish_yaml));
 
+    assert_eq!(
+        run(
+            &["-yY", "$.web"],
+            "defaults: &defaults { image: app, replicas: 1 }\nweb: { <<: *defaults, replicas: 3 }\n",
+            []
+        )?,
+        ok("image: app\nreplicas: 3\n")
+    );
+    assert_eq!(
+        run(
+            &["-yY", "$.a"],
+            "a: { <<: [{ b: 1 }, { b: 2, c: 2 }], d: 3 }\n",
+            []
+        )?,
+        ok("b: 1\nc: 2\nd: 3\n")
+    );
+    assert_eq!(
+        run(&["-y"], "a: *missing\n", [])?,
+        err(concat!(
+            "error[parse]: parsing YAML: unknown anchor at line 1 column 4\n",
+            "  |\n",
+            "1 | a: *missing\n",
+            "  |    ^\n",
+            "  hint: an alias *NAME must come after the anchor &NAME\n",
+        ))
+    );
+
     assert_eq!(
         run(&["-t", "$.package.name"], &cargo_toml, [])?,
         ok("jsq\n")
@@ -143,6 +275,24 @@ fn test() -> Result<()> {
     assert_eq!(run(&["-Y", "() => {}"], "", [])?, ok("undefined\n"));
     assert_eq!(run(&["-T", "() => {}"], "", [])?, ok("undefined\n"));
 
+    let patch = "({ a: undefined, b: null, c: [undefined] })";
+    assert_eq!(run(&["-Y", patch], "", [])?, ok("b: null\nc:\n  - null\n"));
+    assert_eq!(
+        run(&["-Y", "--undefined", "null", patch], "", [])?,
+        ok("a: null\nb: null\nc:\n  - null\n")
+    );
+    assert_eq!(
+        run(&["-Y", "--undefined", "error", patch], "", [])?,
+        err(concat!(
+            "error[convert]: $.a is undefined\n",
+            "  hint: use --undefined null to print it as null, or --undefined omit to leave it out\n",
+        ))
+    );
+    assert_eq!(
+        run(&["-J", "--undefined", "error", "undefined"], "", [])?,
+        ok("undefined\n")
+    );
+
     assert_eq!(
         convert("-tY", &convert("-jT", &convert("-yJ", &publish_yaml)?)?)?,
         publish_yaml
@@ -180,13 +330,61 @@ fn test() -> Result<()> {
             &cargo_toml,
             []
         )?,
-        ok("version = \"1.0.135\"\nfeatures = [\"preserve_order\"]\n")
+        ok("version = \"1.0.154\"\nfeatures = [\"arbitrary_precision\", \"preserve_order\"]\n")
     );
 
     assert_eq!(
         run(&[r#"TOML.parse(read("Cargo.toml")).package.name"#], "", [])?,
         ok("jsq\n")
     );
+    assert_eq!(
+        run(&["--safe", r#"read("Cargo.toml")"#], "", [])?,
+        err("error[eval]: TypeError: read is disabled by --safe\n")
+    );
+    assert_eq!(
+        run(
+            &["Math.random() < 1 && $foo"],
+            "",
+            [("foo", "42"), ("JSQ_SAFE", "1")]
+        )?,
+        err("error[eval]: TypeError: Math.random is disabled by --safe\n")
+    );
+    assert_eq!(
+        run(&["--safe", "new Date(0).constructor.now()"], "", [])?,
+        err("error[eval]: TypeError: Date.now is disabled by --safe\n")
+    );
+    assert_eq!(
+        run(&["--safe", "typeof $foo"], "", [("foo", "42")])?,
+        ok("undefined\n")
+    );
+    assert_eq!(
+        run(&["--safe", "--from", "csv"], "", [])?,
+        err("error: running jsq-format-csv is disabled by --safe\n")
+    );
+    assert_eq!(
+        run(&["--no-fs", r#"read("Cargo.toml")"#], "", [])?,
+        err("error[eval]: TypeError: read is disabled by --no-fs\n")
+    );
+    assert_eq!(
+        run(&["--no-net", r#"fetch("http://localhost")"#], "", [])?,
+        err("error[eval]: TypeError: fetch is disabled by --no-net\n")
+    );
+    assert_eq!(
+        run(&["--timeout", "100ms", "while (true) {}"], "", [])?,
+        err("error[eval]: evaluating SCRIPT took longer than --timeout 0.1s\n")
+    );
+    assert_eq!(
+        run(
+            &["--no-env", "-J", "[typeof $foo, Math.random() < 1]"],
+            "",
+            [("foo", "42")]
+        )?,
+        ok("[\n  \"undefined\",\n  true\n]\n")
+    );
+    assert_eq!(
+        run(&["--no-env", r#"read("/proc/self/environ")"#], "", [])?,
+        err("error[eval]: Error: reading the environment is disabled by --no-env\n")
+    );
 
     assert_eq!(
         run(&["-N", r#"print("foo"); print(42)"#], "", [])?,
@@ -198,5 +396,1969 @@ fn test() -> Result<()> {
         ok("0\n1\n2\n3\n4\n42\n")
     );
 
+    assert_eq!(
+        run(
+            &[
+                "-t",
+                "[jsq.filename, $.package.name]",
+                "Cargo.toml",
+                "Cargo.toml"
+            ],
+            "",
+            []
+        )?,
+        ok("Cargo.toml,jsq\nCargo.toml,jsq\n")
+    );
+
+    assert_eq!(
+        run(&["-f", "tests/test.js", "Cargo.toml"], "", [])?,
+        ok("0\n1\n2\n3\n4\n42\n")
+    );
+
+    let script = env::temp_dir().join("jsq-test-syntax-error.js");
+    fs::write(&script, "const xs = [1, 2];\nconst x = 3;\nreturn xs\n")?;
+    let script = script.to_str().context("getting script path")?;
+    assert_eq!(
+        run(&["-f", script], "", [])?,
+        err(&format!(
+            concat!(
+                "error[eval]: SyntaxError: unexpected token 'return', statement at line 3, col 1\n",
+                " --> {}:3:1\n",
+                "  |\n",
+                "1 | const xs = [1, 2];\n",
+                "2 | const x = 3;\n",
+                "3 | return xs\n",
+                "  | ^\n",
+            ),
+            script
+        ))
+    );
+
+    assert_eq!(
+        run(&["-J", "--opt", "json.indent=4", "({ a: [1] })"], "", [])?,
+        ok("{\n    \"a\": [\n        1\n    ]\n}\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-Y", "--opt", "yaml.indent=4", "({ a: [{ b: 1, c: 2 }] })"],
+            "",
+            []
+        )?,
+        ok("a:\n    -   b: 1\n        c: 2\n")
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "-T",
+                "--opt",
+                "toml.inline-threshold=2",
+                "({ a: { b: 1, c: 2 } })"
+            ],
+            "",
+            []
+        )?,
+        ok("a = { b = 1, c = 2 }\n")
+    );
+
+    let hints = env::temp_dir().join("jsq-test-type-hints.json");
+    fs::write(
+        &hints,
+        r#"{ "created": "date", "port": "integer", "servers": "inline" }"#,
+    )?;
+    let hints = hints.to_str().context("getting type hints path")?;
+    let script = "({ created: '1979-05-27T07:32:00Z', servers: [{ name: 'a, b', port: 1e21 }] })";
+    assert_eq!(
+        run(&["-T", "--type-hints", hints, script], "", [])?,
+        ok(concat!(
+            "created = 1979-05-27T07:32:00Z\n",
+            "servers = [{ name = \"a, b\", port = 1000000000000000000000 }]\n",
+        ))
+    );
+    assert_eq!(
+        run(&["-Y", "--type-hints", hints, script], "", [])?,
+        ok(concat!(
+            "created: 1979-05-27T07:32:00Z\n",
+            "servers: [{name: \"a, b\", port: 1000000000000000000000}]\n",
+        ))
+    );
+    assert_eq!(
+        run(&["-T", "--type-hints", hints, "({ port: 80.5 })"], "", [])?,
+        err("error[convert]: printing TOML: can't print 80.5 as an integer\n")
+    );
+    fs::write(hints, r#"{ "/a/when": "date", "/b/*/port": "integer" }"#)?;
+    assert_eq!(
+        run(
+            &[
+                "-Y",
+                "--type-hints",
+                hints,
+                "({ a: { when: '1979-05-27' }, b: [{ when: '1979-05-27', port: 8e20 }] })",
+            ],
+            "",
+            []
+        )?,
+        ok("a:\n  when: 1979-05-27\nb:\n  - when: \"1979-05-27\"\n    port: 800000000000000000000\n")
+    );
+
+    let dates = "created = 1979-05-27T07:32:00Z\nday = 1979-05-27\nat = \"07:32:00\"\n";
+    assert_eq!(
+        run(&["-tJ"], dates, [])?,
+        ok(concat!(
+            "{\n",
+            "  \"created\": \"1979-05-27T07:32:00Z\",\n",
+            "  \"day\": \"1979-05-27\",\n",
+            "  \"at\": \"07:32:00\"\n",
+            "}\n",
+        ))
+    );
+    assert_eq!(
+        run(&["-tT", "--opt", "toml.datetimes=true"], dates, [])?,
+        ok(dates)
+    );
+
+    // Types, like counts, are only printed to a terminal.
+    assert_eq!(
+        run(&["-Y", "--types", "({ a: 'x', b: [1, 2] })"], "", [])?,
+        ok("a: x\nb:\n  - 1\n  - 2\n")
+    );
+    let types = jsq::printers::Options {
+        types: true,
+        ..jsq::printers::Options::default()
+    };
+    let mut buf = Vec::new();
+    jsq::printers::yaml(
+        &mut termcolor::NoColor::new(&mut buf),
+        &types,
+        r#"{ "a": "x", "b": [1, 2] }"#,
+    )?;
+    assert_eq!(
+        String::from_utf8(buf)?,
+        "a: x  # string\nb:  # number[2]\n  - 1  # number\n  - 2  # number\n"
+    );
+    let mut buf = Vec::new();
+    jsq::printers::json(
+        &mut termcolor::NoColor::new(&mut buf),
+        &types,
+        r#"{ "a": null, "b": [] }"#,
+    )?;
+    assert_eq!(
+        String::from_utf8(buf)?,
+        "{  // object\n  \"a\": null,  // null\n  \"b\": []  // any[0]\n}\n"
+    );
+
+    // Counts are only printed to a terminal.
+    assert_eq!(
+        run(&["-J", "--counts", "({ a: [1, 2] })"], "", [])?,
+        ok("{\n  \"a\": [\n    1,\n    2\n  ]\n}\n")
+    );
+
+    assert_eq!(
+        run(&["--opt", "csv.delimiter=;"], "", [])?,
+        err("error: setting option csv.delimiter=;: unknown option csv.delimiter\n")
+    );
+
+    let (url, head) = serve(r#"{ "foo": 42 }"#)?;
+    assert_eq!(
+        run(&["-j", "-H", "X-Foo: bar", "$.foo", &url], "", [])?,
+        ok("42\n")
+    );
+    let head = head.join().expect("joining server")?.to_lowercase();
+    assert!(head.contains("accept: application/json"));
+    assert!(head.contains("x-foo: bar"));
+
+    let (url, head) = serve(r#"{ "city": "Oslo" }"#)?;
+    assert_eq!(
+        run(
+            &[
+                "-jJ",
+                "(async () => ({ ...$, geo: await fetch($.url, { headers: { 'X-Foo': 1 } }).then(r => r.json()) }))()",
+            ],
+            &format!(r#"{{ "url": "{url}" }}"#),
+            []
+        )?,
+        ok(&format!("{{\n  \"url\": \"{url}\",\n  \"geo\": {{\n    \"city\": \"Oslo\"\n  }}\n}}\n"))
+    );
+    let head = head.join().expect("joining server")?.to_lowercase();
+    assert!(head.contains("x-foo: 1"));
+
+    let (url, head) = serve(r#"{ "city": "Oslo" }"#)?;
+    let res = run(
+        &[
+            "-j",
+            "--max-requests",
+            "1",
+            "(async () => { await fetch($); await fetch($); })()",
+        ],
+        &format!(r#""{url}""#),
+        [],
+    )?;
+    head.join().expect("joining server")?;
+    assert_eq!(res.status_code, 1);
+    assert!(res.stderr.contains("fetch exceeded --max-requests of 1"));
+
+    assert_eq!(
+        run(&["-s", "--warn-output", "3", "$"], "1 2 3", [])?,
+        Output {
+            status_code: 0,
+            stdout: String::from("1\n2\n3\n"),
+            stderr: String::from("{\"warning\":\"output\",\"record\":1,\"value\":4,\"limit\":3}\n"),
+        }
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--pointer", "", "$.a * 2"],
+            r#"[{ "a": 1 }, { "a": 2 }]"#,
+            []
+        )?,
+        ok("2\n4\n")
+    );
+    // Arrays in a stream are values like any others, as in NDJSON whose records are arrays.
+    assert_eq!(
+        run(&["-s", "$.length"], "[1, 2]\n[3, 4]\n", [])?,
+        ok("2\n2\n")
+    );
+    // Each input starts with the globals as they were, whatever the last changed.
+    for jobs in ["1", "2"] {
+        assert_eq!(
+            run(
+                &[
+                    "-s",
+                    "--jobs",
+                    jobs,
+                    "globalThis.n = (globalThis.n ?? 0) + $; n"
+                ],
+                "1 2 3",
+                []
+            )?,
+            ok("1\n2\n3\n")
+        );
+    }
+
+    assert_eq!(
+        run(
+            &["-sJ", "const a = $.a; ({ a })"],
+            "{ \"a\": 1 }\n{ \"a\": 2 }\n",
+            []
+        )?,
+        ok("{\n  \"a\": 1\n}\n{\n  \"a\": 2\n}\n")
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "-jJ",
+                "(function* (x) { for (const i of x.items) yield i })"
+            ],
+            r#"{ "items": [{ "a": 1 }, { "a": 2 }] }"#,
+            []
+        )?,
+        ok("{\n  \"a\": 1\n}\n{\n  \"a\": 2\n}\n")
+    );
+    assert_eq!(
+        run(
+            &["-sY", "spread($.xs.map(x => x * 2))"],
+            "{ \"xs\": [1, 2] }\n{ \"xs\": [3] }\n",
+            []
+        )?,
+        ok("2\n4\n6\n")
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "-sJ",
+                "--reduce",
+                "(acc, x) => ({ ...acc, [x.k]: (acc[x.k] ?? 0) + x.n })",
+                "--init",
+                "{}",
+                "$",
+            ],
+            "{\"k\":\"a\",\"n\":1}\n{\"k\":\"b\",\"n\":2}\n{\"k\":\"a\",\"n\":3}\n",
+            []
+        )?,
+        ok("{\n  \"a\": 4,\n  \"b\": 2\n}\n")
+    );
+    assert_eq!(
+        run(
+            &["-s", "--reduce", "(acc, x) => acc + x", "--init", "0", "$"],
+            "",
+            []
+        )?,
+        ok("0\n")
+    );
+    assert_eq!(
+        run(&["--reduce", "(acc, x) => x", "--init", "{", "$"], "", [])?,
+        err("error: parsing --init: EOF while parsing an object at line 1 column 1\n")
+    );
+    assert!(run(&["function* (x) { yield x }"], "", [])?
+        .stderr
+        .contains("hint: SCRIPT is an expression, so a function needs parentheses"));
+
+    assert_eq!(
+        run(&["-s", "--pointer", ""], "[1 2]", [])?,
+        Output {
+            status_code: 1,
+            stdout: String::from("1\n"),
+            stderr: String::from("error: expected `,` or `]` after array element\n"),
+        }
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--normalize"],
+            "{\"b\":1.0,\"a\":[2e0,-0,0.5]}\n{\"a\":1}\n",
+            []
+        )?,
+        ok("{\"a\":1}\n{\"a\":[2,0,0.5],\"b\":1}\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-g", "JSON.stringify($)"],
+            "json = {};\njson.a = [];\njson.a[1] = {};\njson.a[1][\"b c\"] = \"d;\";\n",
+            []
+        )?,
+        ok("{\"a\":[null,{\"b c\":\"d;\"}]}\n")
+    );
+    assert_eq!(
+        run(&["-G", "({ a: [{ 'b c': 'd' }, null], e: {} })"], "", [])?,
+        ok(concat!(
+            "json = {};\n",
+            "json.a = [];\n",
+            "json.a[0] = {};\n",
+            "json.a[0][\"b c\"] = \"d\";\n",
+            "json.a[1] = null;\n",
+            "json.e = {};\n",
+        ))
+    );
+    assert_eq!(
+        run(&["-g"], "json.a = nope;\n", [])?,
+        err(concat!(
+            "error[parse]: parsing gron: expected a JSON value at line 1\n",
+            "  |\n",
+            "1 | json.a = nope;\n",
+            "  |          ^\n",
+        ))
+    );
+    assert_eq!(
+        run(&["-g"], "json = [];\njson[99999999999] = 1;\n", [])?,
+        err(concat!(
+            "error[parse]: parsing gron: index 99999999999 is more than 1048576 past the end of ",
+            "the array at line 2\n",
+            "  |\n",
+            "2 | json[99999999999] = 1;\n",
+            "  | ^^^^^^^^^^^^^^^^^^^^^^\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &["--ini-in", "-J", "$"],
+            "; config\nname = app\n[core]\n  editor\n[remote \"origin\"]\nfetch = +a\nfetch = +b\n",
+            []
+        )?,
+        ok(concat!(
+            "{\n",
+            "  \"name\": \"app\",\n",
+            "  \"core\": {\n",
+            "    \"editor\": true\n",
+            "  },\n",
+            "  \"remote\": {\n",
+            "    \"origin\": {\n",
+            "      \"fetch\": [\n",
+            "        \"+a\",\n",
+            "        \"+b\"\n",
+            "      ]\n",
+            "    }\n",
+            "  }\n",
+            "}\n",
+        ))
+    );
+    assert_eq!(
+        run(
+            &[
+                "--ini-out",
+                "({ name: 'app', a: { b: { 'c.d': { k: ' v ', n: 1 } } }, xs: [1, 2] })"
+            ],
+            "",
+            []
+        )?,
+        ok("name = app\nxs = 1\nxs = 2\n\n[a.b.\"c.d\"]\nk = \" v \"\nn = 1\n")
+    );
+    assert_eq!(
+        run(&["--ini-in", "$"], "[a\n", [])?,
+        err(concat!(
+            "error[parse]: parsing INI: expected ] at line 1\n",
+            "  |\n",
+            "1 | [a\n",
+            "  |   ^\n",
+        ))
+    );
+    assert_eq!(
+        run(&["--ini-out", "[1]"], "", [])?,
+        err(concat!(
+            "error[convert]: printing INI: can't convert number[1] to INI\n",
+            "  hint: INI only has sections of keys and values, ",
+            "so print it as JSON or YAML instead\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "--env-out",
+                "({ db: { host: 'h', password: 'p@ss $1', note: 'a\\nb' }, ports: [80], x: null })",
+            ],
+            "",
+            []
+        )?,
+        ok("db_host=h\ndb_password='p@ss $1'\ndb_note=\"a\\nb\"\nports_0=80\n")
+    );
+    assert_eq!(
+        run(
+            &[
+                "--env-out",
+                "--opt",
+                "env.separator=__",
+                "({ a: { b: 1 } })"
+            ],
+            "",
+            []
+        )?,
+        ok("a__b=1\n")
+    );
+    assert_eq!(
+        run(
+            &["--env-in", "-J", "$"],
+            "# secrets\nexport A=1\nB=\"x\\ny\" # comment\nC='$x'\nD=a b # comment\n",
+            []
+        )?,
+        ok("{\n  \"A\": \"1\",\n  \"B\": \"x\\ny\",\n  \"C\": \"$x\",\n  \"D\": \"a b\"\n}\n")
+    );
+    assert_eq!(
+        run(&["--env-in", "$"], "A=\"x\n", [])?,
+        err(concat!(
+            "error[parse]: parsing env: expected a closing quote at line 1\n",
+            "  |\n",
+            "1 | A=\"x\n",
+            "  |   ^\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "--plist-out",
+                "({ name: 'x', n: 3, on: true, tags: ['a', null] })"
+            ],
+            "",
+            []
+        )?,
+        ok(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+            "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+            "<plist version=\"1.0\">\n",
+            "<dict>\n",
+            "\t<key>name</key>\n",
+            "\t<string>x</string>\n",
+            "\t<key>n</key>\n",
+            "\t<integer>3</integer>\n",
+            "\t<key>on</key>\n",
+            "\t<true/>\n",
+            "\t<key>tags</key>\n",
+            "\t<array>\n",
+            "\t\t<string>a</string>\n",
+            "\t</array>\n",
+            "</dict>\n",
+            "</plist>\n",
+        ))
+    );
+    let plist = env::temp_dir().join("jsq-test.plist");
+    let plist = plist.to_str().context("getting plist path")?;
+    assert_eq!(
+        run(
+            &[
+                "-N",
+                "--to",
+                &format!("plist={plist}"),
+                "--opt",
+                "plist.binary=true",
+                "--opt",
+                "plist.dates=true",
+                "({ at: '2024-01-02T03:04:05Z', xs: [1.5] })",
+            ],
+            "",
+            []
+        )?,
+        ok("")
+    );
+    assert!(fs::read(plist)?.starts_with(b"bplist00"));
+    assert_eq!(
+        run(&["--plist-in", "JSON.stringify($)", plist], "", [])?,
+        ok("{\"at\":\"2024-01-02T03:04:05Z\",\"xs\":[1.5]}\n")
+    );
+
+    assert_eq!(
+        run(&["-a", "({ ...$, b: 2 })"], "# config\na: 1\n", [])?,
+        ok("a: 1\nb: 2\n")
+    );
+    assert_eq!(
+        run(&["-a", "$.a"], "{\"a\":1}\n{\"a\":2}\n", [])?,
+        ok("1\n2\n")
+    );
+    assert_eq!(
+        run(&["-a", "$.table"], "[table]\nkey = 1\n", [])?,
+        ok("key = 1\n")
+    );
+    assert_eq!(
+        run(&["-a", "-J", "$"], "a: [1]\n", [])?,
+        ok("{\n  \"a\": [\n    1\n  ]\n}\n")
+    );
+    assert_eq!(run(&["-a", "$"], "a b c\n", [])?, ok("a b c\n"));
+    let yaml = env::temp_dir().join("jsq-test-auto.yml");
+    fs::write(&yaml, "[1, 2]\n")?;
+    let yaml = yaml.to_str().context("getting YAML path")?;
+    assert_eq!(run(&["-a", "$", yaml], "", [])?, ok("- 1\n- 2\n"));
+
+    assert_eq!(
+        run(
+            &["-s", "--merge", "--merge-arrays", "by-key:id"],
+            "{\"a\":{\"b\":1},\"xs\":[{\"id\":1,\"v\":1}]}\n\
+                {\"a\":{\"c\":2},\"xs\":[{\"id\":1,\"w\":2},{\"id\":2}]}\n",
+            []
+        )?,
+        ok("{\"a\":{\"b\":1,\"c\":2},\"xs\":[{\"id\":1,\"v\":1,\"w\":2},{\"id\":2}]}\n")
+    );
+    assert_eq!(
+        run(
+            &["JSON.stringify(merge.with('concat')({ a: [1], b: 1 }, { a: [2] }, { b: 2 }))"],
+            "",
+            []
+        )?,
+        ok("{\"a\":[1,2],\"b\":2}\n")
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "-j",
+                "JSON.stringify({
+                    groups: std.groupBy($, 'k'),
+                    uniq: std.uniqBy($, x => x.k).map(x => x.n),
+                    sorted: std.sortBy($, 'n').map(x => x.n),
+                    pick: std.pick($[0], 'k'),
+                    omit: std.omit($[0], ['k']),
+                    chunks: std.chunk([1, 2, 3], 2),
+                    sum: std.sum($, 'n'),
+                    flat: std.flattenDeep([1, [2, [3]]]),
+                })",
+            ],
+            r#"[{ "k": "a", "n": 2 }, { "k": "b", "n": 3 }, { "k": "a", "n": 1 }]"#,
+            []
+        )?,
+        ok(concat!(
+            r#"{"groups":{"a":[{"k":"a","n":2},{"k":"a","n":1}],"b":[{"k":"b","n":3}]},"#,
+            r#""uniq":[2,3],"sorted":[1,2,3],"pick":{"k":"a"},"omit":{"n":2},"#,
+            r#""chunks":[[1,2],[3]],"sum":6,"flat":[1,2,3]}"#,
+            "\n",
+        ))
+    );
+
+    let step = env::temp_dir().join("jsq-test-step.js");
+    fs::write(&step, "$.items")?;
+    let step = step.to_str().context("getting step path")?;
+    assert_eq!(
+        run(
+            &["-j", "-f", step, "--then", "spread($)", "--then", ".name",],
+            r#"{ "items": [{ "name": "a" }, { "name": "b" }] }"#,
+            []
+        )?,
+        ok("a\nb\n")
+    );
+    fs::remove_file(step)?;
+
+    assert_eq!(
+        run(
+            &["-j", "console.log('a is', $.a), debug($.a.b) + 1"],
+            r#"{ "a": { "b": 1 } }"#,
+            []
+        )?,
+        Output {
+            status_code: 0,
+            stdout: String::from("2\n"),
+            stderr: String::from("a is {\n  \"b\": 1\n}\n1\n"),
+        }
+    );
+
+    assert_eq!(
+        run(&["new Date(0)"], "", [])?,
+        ok("1970-01-01T00:00:00.000Z\n")
+    );
+    assert_eq!(
+        run(&["-J", "({ at: new Date(0) })"], "", [])?,
+        ok("{\n  \"at\": \"1970-01-01T00:00:00.000Z\"\n}\n")
+    );
+    assert_eq!(
+        run(
+            &["JSON.stringify([
+                    time.format('2024-03-05T14:07:09.042Z', '%F %T.%L %a %b %j'),
+                    time.format(0, '%H:%M %z', '-08:00'),
+                    time.toOffset('2024-01-01T00:00:00Z', '+05:30'),
+                    time.parse(1700000000),
+                    time.duration('1h30m'),
+                    time.formatDuration(90061001),
+                ])",],
+            "",
+            []
+        )?,
+        ok(concat!(
+            r#"["2024-03-05 14:07:09.042 Tue Mar 065","16:00 -0800","#,
+            r#""2024-01-01T05:30:00+05:30","2023-11-14T22:13:20.000Z",5400000,"1d1h1m1s1ms"]"#,
+            "\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--annotate-source", "$.a"],
+            "{\"a\":1}\n\n{\"a\":\n2}\n",
+            []
+        )?,
+        ok("{\"file\":null,\"line\":1,\"value\":1}\n{\"file\":null,\"line\":3,\"value\":2}\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--seq"],
+            "\x1e{\"a\":1}\n\x1e{\"a\":\n\x1e2\n\x1e3",
+            []
+        )?,
+        Output {
+            status_code: 0,
+            stdout: String::from("\x1e{\"a\":1}\n\x1e2\n"),
+            stderr: String::from(
+                "warning: skipping invalid record of JSON text sequence: parsing JSON: \
+                    EOF while parsing a value at line 1 column 5\n\
+                    warning: skipping invalid record of JSON text sequence: \
+                    record might be truncated\n"
+            ),
+        }
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "--yaml-docs",
+                "[{ kind: 'Service' }, { kind: 'Deployment' }]"
+            ],
+            "",
+            []
+        )?,
+        ok("---\nkind: Service\n---\nkind: Deployment\n")
+    );
+    assert_eq!(
+        run(&["-s", "--yaml-docs", "$.a"], "{\"a\":1}\n{\"a\":[]}\n", [])?,
+        ok("---\n1\n---\n[]\n")
+    );
+    assert_eq!(
+        run(
+            &["-j", "--jcs", "$"],
+            concat!(
+                r#"{ "b": [1.0, 1e21, 1E-7, 0.000001, -0.0], "a": "\u00e9\n","#,
+                r#" "\u20ac": 1, "\ud83d\ude00": 2 }"#,
+            ),
+            []
+        )?,
+        ok("{\"a\":\"é\\n\",\"b\":[1,1e+21,1e-7,0.000001,0],\"€\":1,\"😀\":2}\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--pointer", "", "--frequencies", "x => x.status"],
+            r#"[{ "status": "ok" }, { "status": "error" }, { "status": "ok" }, { "status": "ok" }]"#,
+            []
+        )?,
+        ok("3  75.00%  \"ok\"\n1  25.00%  \"error\"\n")
+    );
+
+    assert_eq!(
+        run(&["-j", "--frequencies", "x => x % 2"], "[1, 2, 3]", [])?,
+        ok("2  66.67%  1\n1  33.33%  0\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-j", "--frequencies", ".user.role"],
+            r#"[{ "user": { "role": "admin" } }, { "user": { "role": "dev" } }]"#,
+            []
+        )?,
+        ok("1  50.00%  \"admin\"\n1  50.00%  \"dev\"\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-j", "--hist", "x => x", "--buckets", "2"],
+            "[1, 2, 2, 3, 10, null]",
+            []
+        )?,
+        ok("1.0 –  5.5 │██████████████████████████████████████████████████ 4\n5.5 – 10.0 │████████████▌ 1\n")
+    );
+    assert!(
+        run(&["-j", "--hist", "x => x", "--buckets", "0"], "[1]", [])?
+            .stderr
+            .starts_with("error: invalid value '0' for '--buckets <N>': ")
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--table"],
+            r#"
+                { "host": "a", "load": [1, 3, 2, 8, 5], "n": 12 }
+                { "host": "b\tc", "load": [4, 4], "n": 3.5, "up": true }
+                { "host": "d", "load": [1, "x"] }
+                7
+            "#,
+            []
+        )?,
+        ok(concat!(
+            "host  load     n    up    $\n",
+            "a     ▁▃▂█▅     12\n",
+            "b\\tc  ▄▄       3.5  true\n",
+            "d     [1,\"x\"]\n",
+            "                          7\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--pointer", "", "--jobs", "4", "$ * 2"],
+            "[1, 2, 3, 4, 5, 6, 7, 8]",
+            []
+        )?,
+        ok("2\n4\n6\n8\n10\n12\n14\n16\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--jobs", "2", "--frequencies", "x => x % 2"],
+            "1 2 3",
+            []
+        )?,
+        ok("2  66.67%  1\n1  33.33%  0\n")
+    );
+
+    assert_eq!(
+        run(&["-w", "$"], "", [])?,
+        err("error: --watch requires FILES\n")
+    );
+
+    assert_eq!(
+        run(
+            &["--pointer", "/data/items", "$.length"],
+            r#"{ "meta": { "page": 1 }, "data": { "items": [1, 2, 3] } }"#,
+            []
+        )?,
+        ok("3\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--pointer", "/data/items", "$ * 2"],
+            r#"{ "meta": { "page": 1 }, "data": { "items": [1, 2, 3] } }"#,
+            []
+        )?,
+        ok("2\n4\n6\n")
+    );
+
+    assert_eq!(
+        run(
+            &["--pointer", "/data/items/3", "$"],
+            r#"{ "data": { "items": [1, 2, 3] } }"#,
+            []
+        )?,
+        err("error: no value at /data/items/3\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-s", "--dups", "x => x.email"],
+            r#"
+                { "id": 1, "email": "a@example.com" }
+                { "id": 2, "email": "b@example.com" }
+                { "id": 3, "email": "a@example.com" }
+            "#,
+            []
+        )?,
+        ok("2  \"a@example.com\"  {\"id\":1,\"email\":\"a@example.com\"}\n")
+    );
+
+    assert_eq!(
+        run(&["-ji", "$"], "{}", [])?,
+        err("error: --interactive requires a terminal\n")
+    );
+
+    assert_eq!(
+        run(&["-j", "--pick", "$"], "{}", [])?,
+        err("error: --pick requires a terminal\n")
+    );
+
+    assert_eq!(run(&["--no-pager", "1 + 1"], "", [])?, ok("2\n"));
+
+    // On a terminal, output held back in case it needs paging is written once a stream goes quiet,
+    // rather than only once it ends. script gives jsq a terminal to write to.
+    if cfg!(target_os = "linux") && Command::new("script").arg("--version").output().is_ok() {
+        let mut child = Command::new("script")
+            .arg("-qefc")
+            .arg(format!(
+                "stty rows 50; (echo '{{\"a\":1}}'; read x; echo '{{\"a\":2}}') | {} -s '$.a'",
+                bin()?.display()
+            ))
+            .arg("/dev/null")
+            .env("PAGER", "cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().context("getting stdout")?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let first = rx
+            .recv_timeout(Duration::from_secs(10))
+            .context("waiting for the first result")??;
+        assert_eq!(first.trim(), "1");
+        // Lets the stream go on, once the first result is in.
+        child
+            .stdin
+            .take()
+            .context("getting stdin")?
+            .write_all(b"\n")?;
+        child.wait()?;
+        let rest = rx.iter().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            rest.iter()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>(),
+            ["2"]
+        );
+    }
+
+    assert_eq!(
+        run(
+            &["-s", "--schema-drift"],
+            r#"
+                { "id": 1, "email": "a@example.com", "tags": ["a"] }
+                { "id": 2, "email": "b@example.com", "tags": [] }
+                { "id": "3", "mail": "c@example.com", "tags": ["c"] }
+            "#,
+            []
+        )?,
+        ok("3  $.email missing\n3  $.id string, expected number\n3  $.mail new string\n")
+    );
+    assert_eq!(
+        run(
+            &["-s", "--schema-drift"],
+            r#"{ "xs": [1, 2] } { "xs": [3] } { "xs": ["a", 4] }"#,
+            []
+        )?,
+        ok("3  $.xs[] mixed, expected number\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-jY", "--sort-keys", "$"],
+            r#"{ "b": 1, "a": { "d": 2, "c": 3 } }"#,
+            []
+        )?,
+        ok("a:\n  c: 3\n  d: 2\nb: 1\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-jJ", "--depth", "1", "--max-string", "3", "$"],
+            r#"{ "a": { "b": [1] }, "c": [], "d": "abcdef" }"#,
+            []
+        )?,
+        ok("{\n  \"a\": {…},\n  \"c\": [],\n  \"d\": \"abc…\"\n}\n")
+    );
+    assert_eq!(
+        run(&["-jY", "--depth", "1", "$"], "[[1], [2]]", [])?,
+        ok("- […]\n- […]\n")
+    );
+    assert_eq!(
+        run(
+            &["-s", "--seq", "--head", "2", "$.xs"],
+            r#"{"xs":[1,2,3]} {"xs":4}"#,
+            []
+        )?,
+        ok("\u{1e}[1,2]\n\u{1e}4\n")
+    );
+    assert_eq!(
+        run(&["--head", "2", "[1, 'a', { b: 3 }]"], "", [])?,
+        ok("1,a\n")
+    );
+    assert_eq!(
+        run(&["-jG", "--tail", "1", "$"], "[[1], [2]]", [])?,
+        ok("json = [];\njson[0] = [];\njson[0][0] = 2;\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-j", "$.name", "tests/utf16le.json", "tests/latin1.json"],
+            "",
+            []
+        )?,
+        ok("Zoë\nZoë\n")
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "-e",
+                "--diff",
+                "tests/utf16le.json",
+                "Cargo.toml",
+                "({ name: $.name ?? $.package.name })",
+            ],
+            "",
+            []
+        )?,
+        Output {
+            status_code: 1,
+            stdout: String::from("~ $.name: \"Zoë\" → \"jsq\"\n"),
+            stderr: String::new(),
+        }
+    );
+    assert_eq!(
+        run(&["-e", "--diff", "Cargo.toml", "Cargo.toml"], "", [])?,
+        ok("")
+    );
+
+    let suite = env::temp_dir().join("jsq-test-suite.toml");
+    fs::write(
+        &suite,
+        concat!(
+            "[[test]]\n",
+            "name = \"adults\"\n",
+            "input = { people = [{ name = \"Ada\", age = 36 }, { name = \"Bo\", age = 9 }] }\n",
+            "script = \"$.people.filter(p => p.age >= 18).map(p => p.name)\"\n",
+            "expected = [\"Ada\"]\n",
+            "\n",
+            "[[test]]\n",
+            "input = { a = 1 }\n",
+            "script = \"({ ...$, a: $.a + 1 })\"\n",
+            "expected = { a = 1, b = 2 }\n",
+            "\n",
+            "[[test]]\n",
+            "script = \"globalThis.foo = 0\"\n",
+            "expected = 0\n",
+            "\n",
+            "# Globals don't leak from one test to the next.\n",
+            "[[test]]\n",
+            "script = \"foo\"\n",
+            "expected = 0\n",
+        ),
+    )?;
+    let suite = suite.to_str().context("getting suite path")?;
+    assert_eq!(
+        run(&["--test", suite], "", [])?,
+        Output {
+            status_code: 1,
+            stdout: format!(
+                concat!(
+                    "pass {suite}: adults\n",
+                    "FAIL {suite}: test 2\n",
+                    "~ $.a: 1 → 2\n",
+                    "- $.b: 2\n",
+                    "pass {suite}: test 3\n",
+                    "FAIL {suite}: test 4\n",
+                    "error[eval]: ReferenceError: foo is not defined\n",
+                    "2 passed, 2 failed\n",
+                ),
+                suite = suite
+            ),
+            stderr: String::new(),
+        }
+    );
+
+    assert_eq!(
+        run(
+            &[
+                "-j",
+                "--input-encoding",
+                "utf-16le",
+                "$.name",
+                "tests/utf16le.json"
+            ],
+            "",
+            []
+        )?,
+        ok("Zoë\n")
+    );
+
+    assert_eq!(
+        run(&["-jJ", "--indent", "4", "$"], r#"{ "a": [1] }"#, [])?,
+        ok("{\n    \"a\": [\n        1\n    ]\n}\n")
+    );
+
+    assert_eq!(
+        run(&["-jY", "--indent", "4", "$"], r#"{ "a": { "b": 1 } }"#, [])?,
+        ok("a:\n    b: 1\n")
+    );
+
+    assert_eq!(
+        run(&["-jJ", "--tab", "$"], r#"{ "a": [1] }"#, [])?,
+        ok("{\n\t\"a\": [\n\t\t1\n\t]\n}\n")
+    );
+
+    assert_eq!(
+        run(
+            &["--invalid-utf8", "replace", "$", "tests/invalid-utf8.txt"],
+            "",
+            []
+        )?,
+        ok("café \u{FFFD} end\n")
+    );
+
+    assert_eq!(
+        run(
+            &["--invalid-utf8", "escape", "$", "tests/invalid-utf8.txt"],
+            "",
+            []
+        )?,
+        ok("café \\xFF end\n")
+    );
+
+    assert_eq!(run(&["$.split('\\n')"], "a\r\nb\r\n", [])?, ok("a,b,\n"));
+
+    assert_eq!(
+        run(&["-J", "--crlf", "[1]"], "", [])?,
+        ok("[\r\n  1\r\n]\r\n")
+    );
+
+    assert_eq!(
+        run(&["-jJ", "--color", "always", "$"], r#"{ "a": 1 }"#, [])?,
+        ok("{\n  \x1b[0m\x1b[34m\"a\"\x1b[0m: 1\n}\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-jJ", "--color", "always", "--grep", "^b", "$"],
+            r#"{ "a": "bc", "b": 1 }"#,
+            [("JSQ_COLORS", "highlight=red")]
+        )?,
+        ok(concat!(
+            "{\n  \x1b[0m\x1b[34m\"a\"\x1b[0m: \x1b[0m\x1b[31m\"bc\"\x1b[0m,\n",
+            "  \x1b[0m\x1b[31m\"b\"\x1b[0m: 1\n}\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &["-jJ", "$"],
+            r#"{ "a": 1 }"#,
+            [("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]
+        )?,
+        ok("{\n  \"a\": 1\n}\n")
+    );
+
+    assert_eq!(
+        run(&["--lint", "$.filter(x => x.a = 1).map($ => { a: $ })"], "", [])?,
+        err(concat!(
+            "warning: assignment in a condition at 1:19\n",
+            "  help: use === to compare values\n",
+            "warning: $ shadows the input at 1:28\n",
+            "  help: use a different name, so that $ still refers to the input\n",
+            "warning: arrow function body is a block, not an object, so it returns undefined at 1:33\n",
+            "  help: wrap the object in parentheses, e.g. x => ({ a: x })\n",
+            "error: linting SCRIPT failed\n",
+        ))
+    );
+
+    assert_eq!(
+        run(&["--lint", "$.filter(x => x.a === 1)"], "", [])?,
+        ok("")
+    );
+
+    assert_eq!(run(&["--check", "$.a.b.c"], "not JSON", [])?, ok(""));
+    assert_eq!(
+        run(&["--check", "return 42"], "", [])?,
+        err(concat!(
+            "error[eval]: SyntaxError: unexpected token 'return', statement at line 1, col 1\n",
+            "  |\n",
+            "1 | return 42\n",
+            "  | ^\n",
+            "  hint: SCRIPT is an expression, so it doesn't need a return\n",
+        ))
+    );
+
+    assert_eq!(
+        run(
+            &["-jJ", "--color", "always", "$"],
+            r#"{ "a": null }"#,
+            [("JSQ_COLORS", "key=red bold:null=dimmed")]
+        )?,
+        ok("{\n  \x1b[0m\x1b[1m\x1b[31m\"a\"\x1b[0m: \x1b[0m\x1b[2mnull\x1b[0m\n}\n")
+    );
+
+    assert_eq!(
+        run(&["$"], "", [("JSQ_COLORS", "nope=red")])?,
+        err("error: parsing JSQ_COLORS: unknown kind nope\n")
+    );
+
+    assert_eq!(
+        run(&["-jJ", "--ascii-output", "$"], r#"{ "café": "😀" }"#, [])?,
+        ok("{\n  \"caf\\u00e9\": \"\\ud83d\\ude00\"\n}\n")
+    );
+
+    assert_eq!(
+        run(&["-jY", "--ascii-output", "$"], r#"{ "a": "é\nb" }"#, [])?,
+        ok("a: \"\\u00e9\\nb\"\n")
+    );
+
+    assert!(
+        run(&["-j", "for (const x of $.items) {}"], r#"{ "a": 1 }"#, [])?
+            .stderr
+            .ends_with("\n  note: `$.items` is undefined\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-jJ", "$"],
+            r#"{ "id": 12345678901234567890123, "x": 0.1000000000000000000001, "y": 0.1 }"#,
+            []
+        )?,
+        ok("{\n  \"id\": 12345678901234567890123,\n  \"x\": 0.1000000000000000000001,\n  \"y\": 0.1\n}\n")
+    );
+    assert_eq!(
+        run(
+            &["-j", "`${$.x} ${$.y}`"],
+            r#"{ "x": 0.1000000000000000000001, "y": 2 }"#,
+            []
+        )?,
+        ok("0.1 2\n")
+    );
+
+    assert_eq!(
+        run(&["-j", "$.id"], r#"{ "id": 9007199254740993 }"#, [])?,
+        ok("9007199254740993\n")
+    );
+
+    assert_eq!(
+        run(&["-j", "typeof $.id"], r#"{ "id": 9007199254740993 }"#, [])?,
+        ok("bigint\n")
+    );
+
+    assert_eq!(
+        run(
+            &["-jJ", "[$.id + 1n, 2n ** 64n]"],
+            r#"{ "id": 9007199254740993 }"#,
+            []
+        )?,
+        ok("[\n  9007199254740994,\n  18446744073709551616\n]\n")
+    );
+
+    let json = env::temp_dir().join("jsq-test-to.json");
+    let yaml = env::temp_dir().join("jsq-test-to.yaml");
+    assert_eq!(
+        run(
+            &[
+                "-jN",
+                "--to",
+                &format!("json={}", json.display()),
+                "--to",
+                &format!("yaml={}", yaml.display()),
+                "$",
+            ],
+            r#"{ "a": [1] }"#,
+            []
+        )?,
+        ok("")
+    );
+    assert_eq!(fs::read_to_string(&json)?, "{\n  \"a\": [\n    1\n  ]\n}\n");
+    assert_eq!(fs::read_to_string(&yaml)?, "a:\n  - 1\n");
+
+    // Files get the whole result, however it's cut short on STDOUT.
+    assert_eq!(
+        run(
+            &[
+                "-jJ",
+                "--depth",
+                "1",
+                "--max-string",
+                "2",
+                "--head",
+                "1",
+                "--to",
+                &format!("json={}", json.display()),
+                "$",
+            ],
+            r#"[{ "b": "cdef" }, 2]"#,
+            []
+        )?,
+        ok("[\n  {…}\n]\n")
+    );
+    assert_eq!(
+        fs::read_to_string(&json)?,
+        "[\n  {\n    \"b\": \"cdef\"\n  },\n  2\n]\n"
+    );
+    // STDOUT is printed as it would be without --to, and files aren't annotated.
+    assert_eq!(
+        run(
+            &[
+                "-j",
+                "--types",
+                "--to",
+                &format!("json={}", json.display()),
+                "$"
+            ],
+            r#"{ "a": [1] }"#,
+            []
+        )?,
+        ok("[object Object]\n")
+    );
+    assert_eq!(fs::read_to_string(&json)?, "{\n  \"a\": [\n    1\n  ]\n}\n");
+
+    let gzip = env::temp_dir().join("jsq-test-to.json.gz");
+    let zstd = env::temp_dir().join("jsq-test-to.json.zst");
+    assert_eq!(
+        run(
+            &[
+                "-N",
+                "--to",
+                &format!("json={}", gzip.display()),
+                "--to",
+                &format!("json={}", zstd.display()),
+                "$",
+            ],
+            "1",
+            []
+        )?,
+        ok("")
+    );
+    assert!(fs::read(&gzip)?.starts_with(&[0x1f, 0x8b]));
+    assert!(fs::read(&zstd)?.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+
+    // Files are still finished when --timeout cuts the run short.
+    assert_eq!(
+        run(
+            &[
+                "-N",
+                "-s",
+                "--timeout",
+                "100ms",
+                "--to",
+                &format!("json={}", gzip.display()),
+                "--to",
+                &format!("json={}", zstd.display()),
+                "if ($ == 2) while (true) {}; $",
+            ],
+            "1 2",
+            []
+        )?,
+        Output {
+            status_code: 1,
+            stdout: String::from(""),
+            stderr: String::from(
+                "error[eval]: evaluating SCRIPT took longer than --timeout 0.1s\n"
+            ),
+        }
+    );
+    let mut unzipped = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(&gzip)?).read_to_string(&mut unzipped)?;
+    assert_eq!(unzipped, "1\n");
+    assert_eq!(zstd::decode_all(fs::File::open(&zstd)?)?, b"1\n");
+
+    let ndjson = env::temp_dir().join("jsq-test-to.ndjson");
+    fs::write(&ndjson, "0")?;
+    for input in [r#"{ "a": [1, "b"] }"#, "2 3"] {
+        assert_eq!(
+            run(
+                &[
+                    "-sN",
+                    "--to",
+                    &format!("ndjson={}", ndjson.display()),
+                    "--append",
+                    "$",
+                ],
+                input,
+                []
+            )?,
+            ok("")
+        );
+    }
+    assert_eq!(fs::read_to_string(&ndjson)?, "0\n{\"a\":[1,\"b\"]}\n2\n3\n");
+
+    // Each result split in to files of its own, in directories which don't exist yet.
+    let split = env::temp_dir().join("jsq-test-split");
+    if split.exists() {
+        fs::remove_dir_all(&split)?;
+    }
+    let dir = split.to_str().context("getting split path")?;
+    assert_eq!(
+        run(
+            &[
+                "-sJ",
+                "--split-dir",
+                dir,
+                "--split-by",
+                "x => `${x.region}/config.json`",
+                "$",
+            ],
+            r#"{ "region": "eu", "n": 1 } { "region": "us", "n": 2 }"#,
+            []
+        )?,
+        ok("")
+    );
+    assert_eq!(
+        fs::read_to_string(split.join("us").join("config.json"))?,
+        "{\n  \"region\": \"us\",\n  \"n\": 2\n}\n"
+    );
+    assert_eq!(
+        run(
+            &["-j", "--split-dir", dir, "--split", r#"({ "a.yaml": $ })"#],
+            r#"{ "a": 1 }"#,
+            []
+        )?,
+        ok("")
+    );
+    assert_eq!(fs::read_to_string(split.join("a.yaml"))?, "a: 1\n");
+    assert!(run(
+        &[
+            "-j",
+            "--split-dir",
+            dir,
+            "--dry-run",
+            "--split",
+            r#"({ "a.yaml": $ })"#
+        ],
+        r#"{ "a": 2 }"#,
+        []
+    )?
+    .stdout
+    .contains("-a: 1\n+a: 2\n"));
+    assert_eq!(fs::read_to_string(split.join("a.yaml"))?, "a: 1\n");
+    assert_eq!(
+        run(
+            &["-j", "--split-dir", dir, "--split", r#"({ "../a.yaml": $ })"#],
+            "1",
+            []
+        )?,
+        err("error: --split paths must be relative and stay inside the directory, but got ../a.yaml\n")
+    );
+    assert_eq!(
+        run(&["--no-fs", "--split", "({ 'a.json': 1 })"], "", [])?,
+        err("error: --split is disabled by --no-fs\n")
+    );
+    fs::remove_dir_all(&split)?;
+
+    let data = env::temp_dir().join("jsq-test-manifest-data.ndjson");
+    let manifest = env::temp_dir().join("jsq-test-manifest.json");
+    assert_eq!(
+        run(
+            &[
+                "-sN",
+                "--to",
+                &format!("ndjson={}", data.display()),
+                "--manifest",
+                manifest.to_str().context("getting manifest path")?,
+                "$",
+            ],
+            "1 2",
+            []
+        )?,
+        ok("")
+    );
+    assert_eq!(
+        fs::read_to_string(&manifest)?,
+        format!(
+            concat!(
+                "{{\n",
+                "  \"script\": {{\n",
+                "    \"sha256\": \"09fc96082d34c2dfc1295d92073b5ea1dc8ef8da95f14dfded011ffb96d3e54b\"\n",
+                "  }},\n",
+                "  \"files\": [\n",
+                "    {{\n",
+                "      \"path\": {:?},\n",
+                "      \"bytes\": 4,\n",
+                "      \"sha256\": \"a6e2b7a040683432de03a18fd8a1939a2fdf82585b364bfc874bdd4095c4cae1\",\n",
+                "      \"records\": 2\n",
+                "    }}\n",
+                "  ]\n",
+                "}}\n",
+            ),
+            data.display().to_string()
+        )
+    );
+
+    let profile = run(&["-s", "--profile", "$"], "1 2 3", [])?;
+    assert_eq!(profile.stdout, "1\n2\n3\n");
+    assert!(profile.stderr.starts_with("reading input "));
+    assert!(profile
+        .stderr
+        .contains("\nSCRIPT was evaluated 3 times, taking "));
+    if cfg!(target_os = "linux") {
+        let timing = run(&["--timing", "$"], "1", [])?;
+        assert!(timing.stderr.contains("\nPeak memory use was "));
+    }
+
+    assert_eq!(
+        run(&["-je", "$.ok"], r#"{ "ok": true }"#, [])?,
+        ok("true\n")
+    );
+    assert_eq!(
+        run(&["-je", "$.ok"], r#"{ "ok": false }"#, [])?,
+        Output {
+            status_code: 1,
+            stdout: String::from("false\n"),
+            stderr: String::new(),
+        }
+    );
+    assert_eq!(run(&["-sje", "$"], "null 0", [])?, ok("null\n0\n"));
+
+    assert_eq!(
+        run(
+            &[
+                "--arg",
+                "foo",
+                "it's \"quoted\"",
+                "--argjson",
+                "bar",
+                "[1, 2]",
+                "$foo + $bar.length",
+            ],
+            "",
+            [("foo", "42")]
+        )?,
+        ok("it's \"quoted\"2\n")
+    );
+    assert_eq!(
+        run(&["--argjson", "bar", "[1,", "$bar"], "", [])?.stderr,
+        concat!(
+            "error[parse]: parsing --argjson bar: parsing JSON: ",
+            "EOF while parsing a value at line 1 column 3\n",
+            "  |\n",
+            "1 | [1,\n",
+            "  |   ^\n",
+        )
+    );
+
+    let config = env::temp_dir().join("jsq-test-config");
+    fs::create_dir_all(config.join("jsq"))?;
+    fs::write(
+        config.join("jsq").join("init.js"),
+        "function double(x) { return 2 * x; }\nconst answer = 42;\n",
+    )?;
+    let config = config.to_str().context("getting config path")?;
+    assert_eq!(
+        run(&["double(answer)"], "", [("XDG_CONFIG_HOME", config)])?,
+        ok("84\n")
+    );
+    assert_eq!(
+        run(
+            &["-s", "--jobs", "2", "double($)"],
+            "1 2",
+            [("XDG_CONFIG_HOME", config)]
+        )?,
+        ok("2\n4\n")
+    );
+    assert_eq!(
+        run(
+            &["--no-init", "typeof double"],
+            "",
+            [("XDG_CONFIG_HOME", config)]
+        )?,
+        ok("undefined\n")
+    );
+
+    let users = env::temp_dir().join("jsq-test-users.ndjson");
+    fs::write(
+        &users,
+        "{\"id\":1,\"name\":\"ada\"}\n{\"id\":\"2\",\"name\":\"alan\"}\n",
+    )?;
+    let lookup = format!("users={}", users.to_str().context("getting users path")?);
+    assert_eq!(
+        run(
+            &["-s", "--lookup", &lookup, "lookup.users.get($.user)?.name"],
+            "{\"user\":2} {\"user\":\"1\"} {\"user\":3}",
+            []
+        )?,
+        ok("alan\nada\nundefined\n")
+    );
+
+    let records = env::temp_dir().join("jsq-test-records.ndjson");
+    fs::write(
+        &records,
+        "{\"id\":1,\"v\":\"é\"}\n\n{\"id\":2,\"v\":\"b\"}\n{\"id\":1}\n",
+    )?;
+    let records = records.to_str().context("getting records path")?;
+    // Left over from an earlier run, it would be out of date.
+    fs::remove_file(format!("{records}.jsq-index")).ok();
+    let query = [
+        "-s",
+        "--annotate-source",
+        "--where",
+        "id=1",
+        "$.v ?? null",
+        records,
+    ];
+    let expected = format!(
+        "{{\"file\":\"{records}\",\"line\":1,\"value\":\"é\"}}\n\
+            {{\"file\":\"{records}\",\"line\":4,\"value\":null}}\n"
+    );
+    let latin1 = [
+        "-s",
+        "--input-encoding",
+        "latin1",
+        "--where",
+        "id=1",
+        "$.v",
+        records,
+    ];
+    assert_eq!(run(&query, "", [])?, ok(&expected));
+    assert_eq!(run(&latin1, "", [])?, ok("Ã©\nundefined\n"));
+    assert_eq!(run(&["--index", "id", records], "", [])?, ok(""));
+    assert_eq!(run(&query, "", [])?, ok(&expected));
+    // Records found with the index are decoded as the rest of the file would be.
+    assert_eq!(run(&latin1, "", [])?, ok("Ã©\nundefined\n"));
+
+    assert_eq!(
+        run(
+            &["-s", "--batch", "2", "JSON.stringify($)"],
+            "1 2 3 4 5",
+            []
+        )?,
+        ok("[1,2]\n[3,4]\n[5]\n")
+    );
+    assert_eq!(
+        run(
+            &["-s", "--batch", "2", "--annotate-source", "$.length"],
+            "1\n2\n3\n",
+            []
+        )?,
+        ok(concat!(
+            "{\"file\":null,\"line\":1,\"value\":2}\n",
+            "{\"file\":null,\"line\":3,\"value\":1}\n",
+        ))
+    );
+
+    // Large enough to be mapped in to memory.
+    let large = env::temp_dir().join("jsq-test-large.json");
+    fs::write(&large, format!("[\"{}\"]", "x".repeat(16 << 20)))?;
+    let large = large.to_str().context("getting large path")?;
+    assert_eq!(
+        run(&["-j", "$[0].length", large], "", [])?,
+        ok("16777216\n")
+    );
+    assert_eq!(
+        run(&["-j", "--no-mmap", "$[0].length", large], "", [])?,
+        ok("16777216\n")
+    );
+    // Mapped input which isn't plain UTF-8 is decoded as it's read instead.
+    fs::write(large, format!("\u{feff}[\"{}\"]", "x".repeat(16 << 20)))?;
+    assert_eq!(
+        run(&["-j", "$[0].length", large], "", [])?,
+        ok("16777216\n")
+    );
+    fs::remove_file(large)?;
+
+    // With --dry-run, write prints a diff instead of writing.
+    let chart = env::temp_dir().join("jsq-test-chart.yaml");
+    fs::write(&chart, "name: app\nversion: 1.2.2\n")?;
+    let chart = chart.to_str().context("getting chart path")?;
+    assert_eq!(
+        run(
+            &[
+                "-yN",
+                "--dry-run",
+                "write(jsq.filename, YAML.stringify({ ...$, version: '1.2.3' }))",
+                chart,
+            ],
+            "",
+            []
+        )?,
+        ok(&format!(
+            "--- {chart}\n+++ {chart}\n{}",
+            "@@ -1,2 +1,2 @@\n name: app\n-version: 1.2.2\n+version: \"1.2.3\"\n",
+        ))
+    );
+    assert_eq!(fs::read_to_string(chart)?, "name: app\nversion: 1.2.2\n");
+    fs::remove_file(chart)?;
+
+    let schema = env::temp_dir().join("jsq-test-schema.json");
+    fs::write(
+        &schema,
+        r##"{
+            "type": "object",
+            "required": ["port"],
+            "properties": { "port": { "$ref": "#/$defs/port" } },
+            "$defs": { "port": { "type": "integer", "maximum": 65535 } }
+        }"##,
+    )?;
+    let schema = schema.to_str().context("getting schema path")?;
+    assert_eq!(
+        run(
+            &["-j", "--schema", schema, "$.port"],
+            r#"{ "port": 80 }"#,
+            []
+        )?,
+        ok("80\n")
+    );
+    assert_eq!(
+        run(
+            &["-s", "--schema", schema, "$.port"],
+            r#"{ "port": 80 } { "port": "80" } { "port": 65536 }"#,
+            []
+        )?,
+        Output {
+            status_code: 1,
+            stdout: String::from("80\n"),
+            stderr: format!(
+                "error[schema]: expected integer, found string at /port\n\
+                    error: input doesn't match {schema}\n"
+            ),
+        }
+    );
+    assert_eq!(
+        run(
+            &[
+                "-j",
+                "--schema",
+                schema,
+                "--validate-output",
+                "({ port: $.port + 1 })"
+            ],
+            r#"{ "port": 65535 }"#,
+            []
+        )?,
+        err(&format!(
+            "error[schema]: expected at most 65535, found 65536 at /port\n\
+                error: result doesn't match {schema}\n"
+        ))
+    );
+    let strict = env::temp_dir().join("jsq-test-schema-strict.json");
+    fs::write(
+        &strict,
+        r#"{
+            "properties": { "step": { "multipleOf": 0.1 } },
+            "patternProperties": { "^x-": { "type": "string" } },
+            "additionalProperties": { "type": "string", "pattern": "^v[0-9]+$", "format": "date" }
+        }"#,
+    )?;
+    let strict = strict.to_str().context("getting schema path")?;
+    assert_eq!(
+        run(
+            &["-j", "--schema", strict, "$.step"],
+            r#"{ "step": 0.3, "x-a": 1, "b": "v1" }"#,
+            []
+        )?,
+        err(&format!(
+            "error[schema]: expected string, found integer at /x-a\n\
+                error[schema]: expected a date, found v1 at /b\n\
+                error: input doesn't match {strict}\n"
+        ))
+    );
+
+    assert_eq!(
+        run(&["require('./tests/double.mjs').double(21)"], "", [])?,
+        ok("42\n")
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let plugins = env::temp_dir().join("jsq-test-plugins");
+        let plugin = plugins.join("jsq-format-text");
+        fs::create_dir_all(&plugins)?;
+        fs::write(
+            &plugin,
+            [
+                "#!/bin/sh",
+                "case \"$1\" in",
+                "  decode) printf '{\"text\": \"%s\"}' \"$(cat)\" ;;",
+                "  encode) printf 'text=%s\\n' \"$(cat)\" ;;",
+                "esac",
+            ]
+            .join("\n"),
+        )?;
+        fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755))?;
+        let path = format!("{}:{}", plugins.display(), env::var("PATH")?);
+        let out = plugins.join("out.text");
+        assert_eq!(
+            run(
+                &[
+                    "--from",
+                    "text",
+                    "--to",
+                    &format!("text={}", out.display()),
+                    "$.text.length"
+                ],
+                "hello",
+                [("PATH", path.as_str())]
+            )?,
+            ok("5\n")
+        );
+        assert_eq!(fs::read_to_string(&out)?, "text=5\n");
+        assert!(run(&["--from", "missing", "$"], "", [])?
+            .stderr
+            .starts_with("error: running jsq-format-missing: "));
+    }
+
+    #[cfg(feature = "wasm")]
+    assert_eq!(
+        run(&["--wasm", "tests/cat.wat", "-jJ"], r#"{"a":[1,2]}"#, [])?,
+        ok("{\n  \"a\": [\n    1,\n    2\n  ]\n}\n")
+    );
+
+    #[cfg(feature = "simd")]
+    {
+        assert_eq!(
+            run(&["-jJ"], r#"[1, -2, "x\n"]"#, [])?,
+            ok("[\n  1,\n  -2,\n  \"x\\n\"\n]\n")
+        );
+        assert_eq!(
+            run(&["-jJ"], r#"{ "a": [1], "b": 2, "a": [3] }"#, [])?,
+            ok("{\n  \"a\": [\n    3\n  ],\n  \"b\": 2\n}\n")
+        );
+        assert_eq!(
+            run(
+                &[
+                    "-j",
+                    "[Object.is($[2], -0), $[0] + $[1], $[3]['a\"1'], $[3].b]"
+                ],
+                r#"[1.50, 1e3, -0, { "a\"1": "\\2", "b": -2.5e-1 }]"#,
+                []
+            )?,
+            ok("true,1001.5,\\2,-0.25\n")
+        );
+    }
+
+    #[cfg(feature = "typescript")]
+    {
+        let script = env::temp_dir().join("jsq-test-transform.ts");
+        fs::write(
+            &script,
+            concat!(
+                "enum Level { Low, High = 10, Higher }\n",
+                "interface Item { name: string; level?: Level }\n",
+                "class Tagger<T> {\n",
+                "  constructor(private readonly tag: string) {}\n",
+                "  apply(item: T): T & { tag: string } { return { ...item, tag: this.tag }; }\n",
+                "}\n",
+                "const items = $ as Item[];\n",
+                "items.map((item) => new Tagger<Item>(Level[item.level!]).apply(item));\n",
+            ),
+        )?;
+        let script = script.to_str().context("getting script path")?;
+        assert_eq!(
+            run(
+                &["-jJ", "-f", script],
+                r#"[{ "name": "a", "level": 11 }]"#,
+                []
+            )?,
+            ok(concat!(
+                "[\n",
+                "  {\n",
+                "    \"name\": \"a\",\n",
+                "    \"level\": 11,\n",
+                "    \"tag\": \"Higher\"\n",
+                "  }\n",
+                "]\n",
+            ))
+        );
+
+        let script = env::temp_dir().join("jsq-test-syntax-error.ts");
+        fs::write(&script, "const x: number = 1;\nconst y: = 2;\n")?;
+        let script = script.to_str().context("getting script path")?;
+        assert_eq!(
+            run(&["-f", script, "--check"], "", [])?,
+            err(&format!(
+                concat!(
+                    "error[eval]: SyntaxError: Unexpected token\n",
+                    " --> {}:2:10\n",
+                    "  |\n",
+                    "2 | const y: = 2;\n",
+                    "  |          ^\n",
+                ),
+                script
+            ))
+        );
+    }
+
+    #[cfg(feature = "lua")]
+    {
+        assert_eq!(
+            run(
+                &[
+                    "--lang",
+                    "lua",
+                    "-j",
+                    "--argjson",
+                    "n",
+                    "2",
+                    "_.a[2] * arg.n"
+                ],
+                r#"{"a":[1,2,3]}"#,
+                []
+            )?,
+            ok("4\n")
+        );
+        assert_eq!(
+            run(
+                &[
+                    "--lang",
+                    "lua",
+                    "-jJ",
+                    "local t = {} for i, x in ipairs(_) do t[i] = -x end return t"
+                ],
+                "[1,2]",
+                []
+            )?,
+            ok("[\n  -1,\n  -2\n]\n")
+        );
+        assert_eq!(
+            run(
+                &[
+                    "--lang",
+                    "lua",
+                    "--no-net",
+                    "os.execute == nil and io.popen == nil"
+                ],
+                "",
+                []
+            )?,
+            ok("true\n")
+        );
+        assert_eq!(
+            run(
+                &["--lang", "lua", "--no-env", "io.open('/proc/self/environ')"],
+                "",
+                []
+            )?,
+            err("error: reading the environment is disabled by --no-env\n")
+        );
+        assert_eq!(
+            run(
+                &["--lang", "lua", "--split-by", "x => 'a.json'", "_"],
+                "",
+                []
+            )?,
+            err("error: --split-by needs SCRIPT to be JavaScript\n")
+        );
+        assert_eq!(
+            run(
+                &["--lang", "lua", "--timeout", "100ms", "while true do end"],
+                "",
+                []
+            )?,
+            err("error[eval]: evaluating SCRIPT took longer than --timeout 0.1s\n")
+        );
+        assert_eq!(
+            run(
+                &[
+                    "--lang",
+                    "lua",
+                    "--max-memory",
+                    "64M",
+                    "string.rep('x', 1 << 27)"
+                ],
+                "",
+                []
+            )?,
+            err("error[eval]: SCRIPT needed more than --max-memory 64 MiB\n")
+        );
+        assert_eq!(
+            run(&["--lang", "lua", "--safe", "while true do end"], "", [])?,
+            err(
+                "error: runtime error: SCRIPT ran more than the 100000000 instructions allowed by \
+                --safe\n"
+            )
+        );
+    }
+
+    let info = run(&["--info"], "", [])?.stdout;
+    assert_eq!(
+        run(&["-j", "$.version"], &info, [])?,
+        run(&["-t", "$.package.version"], &cargo_toml, [])?
+    );
+    assert_eq!(run(&["-j", "$.engine.name"], &info, [])?, ok("boa\n"));
+    assert_eq!(
+        run(&["-j", "[$.http, $.globals.includes('fetch')]"], &info, [])?.stdout,
+        "true,true\n"
+    );
+    for flag in ["--no-net", "--safe"] {
+        let info = run(&[flag, "--info"], "", [])?.stdout;
+        assert_eq!(
+            run(&["-j", "[$.http, $.globals.includes('fetch')]"], &info, [])?.stdout,
+            "false,false\n"
+        );
+    }
+
+    assert!(run(&["-j", "$", "missing.json"], "", [])?
+        .stderr
+        .starts_with("error: reading missing.json: "));
+
+    let mut engine = jsq::Engine::new()?;
+    assert_eq!(
+        engine.apply("x => x.a + 1", &serde_json::json!({ "a": 1 }))?,
+        Some(serde_json::json!(2))
+    );
+    assert_eq!(engine.eval("$.b", &serde_json::json!({ "a": 1 }))?, None);
+    let mut buf = Vec::new();
+    jsq::printers::yaml(
+        &mut termcolor::NoColor::new(&mut buf),
+        &jsq::printers::Options::default(),
+        r#"{ "a": [1] }"#,
+    )?;
+    assert_eq!(String::from_utf8(buf)?, "a:\n  - 1\n");
+
+    // With --changes, only what changed is printed after the first evaluation.
+    let watched = env::temp_dir().join("jsq-test-changes.json");
+    fs::write(&watched, r#"{ "a": 1, "b": 2 }"#)?;
+    let mut child = Command::new(bin()?)
+        .args(["-w", "--changes", "-j", "$"])
+        .arg(&watched)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdout = BufReader::new(child.stdout.take().context("getting stdout")?);
+    let mut line = String::new();
+    stdout.read_line(&mut line)?;
+    assert_eq!(line, "{\"a\":1,\"b\":2}\n");
+    // Some filesystems only keep modification times to the second.
+    thread::sleep(std::time::Duration::from_secs(1));
+    fs::write(&watched, r#"{ "a": 1, "b": 3, "c": 4 }"#)?;
+    line.clear();
+    stdout.read_line(&mut line)?;
+    stdout.read_line(&mut line)?;
+    child.kill()?;
+    child.wait()?;
+    assert_eq!(line, "~ $.b: 2 → 3\n+ $.c: 4\n");
+
+    // Stopping reading part way through, like head, is not an error.
+    let mut child = Command::new(bin()?)
+        .args(["-J", "Array.from({ length: 100000 }, (_, i) => i)"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().context("getting stdout")?).read_line(&mut line)?;
+    let output = child.wait_with_output()?;
+    assert_eq!(line, "[\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stderr)?, "");
+
     Ok(())
 }